@@ -470,10 +470,7 @@ fn partition_font(request: Json<PartitionFontRequest>) -> Result<PartitionRespon
         return Err(FontError::FontLoadingFailed)
     }
 
-    let font_instance = FontInstance {
-        font_key: font_key,
-        size: Au::from_f64_px(request.point_size),
-    };
+    let font_instance = FontInstance::new(&font_key, Au::from_f64_px(request.point_size));
 
     // Read glyph info.
     let mut paths: Vec<Vec<PathEvent>> = vec![];
@@ -603,10 +600,7 @@ fn render_reference_text(request: Json<RenderTextReferenceRequest>)
                          -> Result<ReferenceImage, FontError> {
     let font_key = FontKey::new();
     let otf_data = try!(otf_data_from_request(&request.face));
-    let font_instance = FontInstance {
-        font_key: font_key,
-        size: Au::from_f64_px(request.point_size),
-    };
+    let font_instance = FontInstance::new(&font_key, Au::from_f64_px(request.point_size));
     let glyph_key = GlyphKey::new(request.glyph, SubpixelOffset(0));
 
     // Rasterize the glyph using the right rasterizer.
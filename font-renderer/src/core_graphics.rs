@@ -28,7 +28,7 @@ use std::hash::Hash;
 use std::iter::Cloned;
 use std::slice::Iter;
 use std::sync::Arc;
-use {FontInstance, GlyphDimensions, GlyphImage, GlyphKey};
+use {FontInstance, GlyphDimensions, GlyphImage, GlyphKey, PixelFormat};
 
 const CG_ZERO_RECT: CGRect = CGRect {
     origin: CG_ZERO_POINT,
@@ -346,9 +346,14 @@ impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
             pixel[2] = b;
         }
 
-        // Return the image.
+        // Return the image. The dimensions have Y pointing up, so their origin is the bottom left
+        // corner.
         Ok(GlyphImage {
             dimensions: dimensions,
+            origin: Point2D::new(dimensions.origin.x,
+                                 -(dimensions.origin.y + dimensions.size.height as i32)),
+            format: PixelFormat::Rgba32,
+            stride: dimensions.size.width as usize * 4,
             pixels: pixels,
         })
     }
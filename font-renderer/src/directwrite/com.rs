@@ -18,11 +18,13 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use uuid;
 use winapi::{E_NOINTERFACE, E_POINTER, GUID, HRESULT, IUnknown, REFIID, S_OK, ULONG};
 
+/// An owned reference to a COM object, released when dropped.
 pub struct PathfinderComPtr<T> {
     ptr: *mut T,
 }
 
 impl<T> PathfinderComPtr<T> {
+    /// Takes ownership of one reference to the given COM object, without calling `AddRef`.
     #[inline]
     pub unsafe fn new(ptr: *mut T) -> PathfinderComPtr<T> {
         PathfinderComPtr {
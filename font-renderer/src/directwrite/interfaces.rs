@@ -0,0 +1,607 @@
+// pathfinder/font-renderer/src/directwrite/interfaces.rs
+//
+// Copyright © 2017 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Declarations of DirectWrite interfaces that are newer than the ones `winapi` knows about.
+//! 
+//! Only the methods that Pathfinder calls have real signatures. The remaining vtable slots are
+//! declared as opaque pointers so that the layout matches the Windows SDK headers.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use std::os::raw::c_void;
+use winapi::{BOOL, DWRITE_FONT_SIMULATIONS, DWRITE_GLYPH_RUN, DWRITE_GLYPH_RUN_DESCRIPTION};
+use winapi::{DWRITE_MATRIX, DWRITE_MEASURING_MODE, FLOAT, HRESULT, IDWriteBitmapRenderTargetVtbl};
+use winapi::{DWRITE_FONT_STRETCH, DWRITE_FONT_STYLE, DWRITE_FONT_WEIGHT, IDWriteFactoryVtbl};
+use winapi::DWRITE_INFORMATIONAL_STRING_ID;
+use winapi::{DWRITE_SCRIPT_ANALYSIS, IDWriteFont, IDWriteFontCollection, WCHAR};
+use winapi::{IDWriteTextAnalysisSource, IDWriteTextAnalyzerVtbl};
+use winapi::{IDWriteFontFace, IDWriteFontFaceVtbl, INT32, IUnknownVtbl, UINT16, UINT32};
+
+DEFINE_GUID! {
+    IID_IDWriteBitmapRenderTarget1,
+    0x791e8298, 0x3ef3, 0x4230, 0x98, 0x80, 0xc9, 0xbd, 0xec, 0xc4, 0x20, 0x64
+}
+DEFINE_GUID! {
+    IID_IDWriteFactory2, 0x0439fc60, 0xca44, 0x4994, 0x8d, 0xee, 0x3a, 0x9a, 0xf7, 0xb7, 0x32, 0xec
+}
+DEFINE_GUID! {
+    IID_IDWriteFontFace1, 0xa71efdb4, 0x9fdb, 0x4838, 0xad, 0x90, 0xcf, 0xc3, 0xbe, 0x8c, 0x3d, 0xaf
+}
+#[cfg(feature = "embedded-bitmaps")]
+DEFINE_GUID! {
+    IID_IDWriteFontFace4, 0x27f2a904, 0x4eb8, 0x441d, 0x96, 0x78, 0x05, 0x63, 0xf5, 0x3e, 0x3e, 0x2f
+}
+DEFINE_GUID! {
+    IID_IDWriteTextAnalyzer1,
+    0x80dad800, 0xe21f, 0x4e83, 0x96, 0xce, 0xbf, 0xcc, 0xe5, 0x00, 0xdb, 0x7c
+}
+DEFINE_GUID! {
+    IID_IDWriteFontFace5, 0x98eff3a5, 0xb667, 0x479a, 0xb1, 0x45, 0xe2, 0xfa, 0x5b, 0x9f, 0xdc, 0x29
+}
+
+// Informational strings added in Windows 7 that `winapi` doesn't know about.
+pub const DWRITE_INFORMATIONAL_STRING_FULL_NAME: DWRITE_INFORMATIONAL_STRING_ID =
+    DWRITE_INFORMATIONAL_STRING_ID(12);
+pub const DWRITE_INFORMATIONAL_STRING_POSTSCRIPT_NAME: DWRITE_INFORMATIONAL_STRING_ID =
+    DWRITE_INFORMATIONAL_STRING_ID(13);
+
+// Returned by `TranslateColorGlyphRun` when the run has no color glyphs.
+pub const DWRITE_E_NOCOLOR: HRESULT = 0x8898500c_u32 as HRESULT;
+
+pub type DWRITE_FONT_AXIS_TAG = UINT32;
+
+pub type DWRITE_TEXT_ANTIALIAS_MODE = UINT32;
+pub const DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE: DWRITE_TEXT_ANTIALIAS_MODE = 0;
+pub const DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE: DWRITE_TEXT_ANTIALIAS_MODE = 1;
+
+pub type DWRITE_GLYPH_IMAGE_FORMATS = UINT32;
+#[cfg(feature = "embedded-bitmaps")]
+pub const DWRITE_GLYPH_IMAGE_FORMATS_PNG: DWRITE_GLYPH_IMAGE_FORMATS = 0x00000010;
+#[cfg(feature = "embedded-bitmaps")]
+pub const DWRITE_GLYPH_IMAGE_FORMATS_JPEG: DWRITE_GLYPH_IMAGE_FORMATS = 0x00000020;
+#[cfg(feature = "embedded-bitmaps")]
+pub const DWRITE_GLYPH_IMAGE_FORMATS_TIFF: DWRITE_GLYPH_IMAGE_FORMATS = 0x00000040;
+#[cfg(feature = "embedded-bitmaps")]
+pub const DWRITE_GLYPH_IMAGE_FORMATS_PREMULTIPLIED_B8G8R8A8: DWRITE_GLYPH_IMAGE_FORMATS =
+    0x00000080;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_UNICODE_RANGE {
+    pub first: UINT32,
+    pub last: UINT32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct D2D1_SIZE_U {
+    pub width: UINT32,
+    pub height: UINT32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct D2D1_POINT_2L {
+    pub x: INT32,
+    pub y: INT32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_GLYPH_IMAGE_DATA {
+    pub imageData: *const c_void,
+    pub imageDataSize: UINT32,
+    pub uniqueDataId: UINT32,
+    pub pixelsPerEm: UINT32,
+    pub pixelSize: D2D1_SIZE_U,
+    pub horizontalLeftOrigin: D2D1_POINT_2L,
+    pub horizontalRightOrigin: D2D1_POINT_2L,
+    pub verticalTopOrigin: D2D1_POINT_2L,
+    pub verticalBottomOrigin: D2D1_POINT_2L,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_COLOR_F {
+    pub r: FLOAT,
+    pub g: FLOAT,
+    pub b: FLOAT,
+    pub a: FLOAT,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DWRITE_COLOR_GLYPH_RUN {
+    pub glyphRun: DWRITE_GLYPH_RUN,
+    pub glyphRunDescription: *mut DWRITE_GLYPH_RUN_DESCRIPTION,
+    pub baselineOriginX: FLOAT,
+    pub baselineOriginY: FLOAT,
+    pub runColor: DWRITE_COLOR_F,
+    pub paletteIndex: UINT16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_SCRIPT_PROPERTIES {
+    pub isoScriptCode: UINT32,
+    pub isoScriptNumber: UINT32,
+    pub clusterLookahead: UINT32,
+    pub justificationCharacter: UINT32,
+    // `restrictCaretToClusters` through `isCursiveWriting`, one bit each.
+    pub flags: UINT32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_FONT_AXIS_VALUE {
+    pub axisTag: DWRITE_FONT_AXIS_TAG,
+    pub value: FLOAT,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DWRITE_FONT_AXIS_RANGE {
+    pub axisTag: DWRITE_FONT_AXIS_TAG,
+    pub minValue: FLOAT,
+    pub maxValue: FLOAT,
+}
+
+#[repr(C)]
+pub struct IDWriteBitmapRenderTarget1 {
+    pub lpVtbl: *const IDWriteBitmapRenderTarget1Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteBitmapRenderTarget1Vtbl {
+    pub parent: IDWriteBitmapRenderTargetVtbl,
+    pub GetTextAntialiasMode: unsafe extern "system" fn(This: *mut IDWriteBitmapRenderTarget1)
+                                                       -> DWRITE_TEXT_ANTIALIAS_MODE,
+    pub SetTextAntialiasMode: unsafe extern "system" fn(This: *mut IDWriteBitmapRenderTarget1,
+                                                        antialiasMode: DWRITE_TEXT_ANTIALIAS_MODE)
+                                                        -> HRESULT,
+}
+
+impl IDWriteBitmapRenderTarget1 {
+    #[inline]
+    pub unsafe fn SetTextAntialiasMode(&mut self, antialiasMode: DWRITE_TEXT_ANTIALIAS_MODE)
+                                       -> HRESULT {
+        ((*self.lpVtbl).SetTextAntialiasMode)(self, antialiasMode)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFactory1Vtbl {
+    pub parent: IDWriteFactoryVtbl,
+    pub GetEudcFontCollection: *const c_void,
+    pub CreateCustomRenderingParams: *const c_void,
+}
+
+#[repr(C)]
+pub struct IDWriteFactory2 {
+    pub lpVtbl: *const IDWriteFactory2Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFactory2Vtbl {
+    pub parent: IDWriteFactory1Vtbl,
+    pub GetSystemFontFallback: unsafe extern "system" fn(
+        This: *mut IDWriteFactory2,
+        fontFallback: *mut *mut IDWriteFontFallback)
+        -> HRESULT,
+    pub CreateFontFallbackBuilder: *const c_void,
+    pub TranslateColorGlyphRun: unsafe extern "system" fn(
+        This: *mut IDWriteFactory2,
+        baselineOriginX: FLOAT,
+        baselineOriginY: FLOAT,
+        glyphRun: *const DWRITE_GLYPH_RUN,
+        glyphRunDescription: *const DWRITE_GLYPH_RUN_DESCRIPTION,
+        measuringMode: DWRITE_MEASURING_MODE,
+        worldToDeviceTransform: *const DWRITE_MATRIX,
+        colorPaletteIndex: UINT32,
+        colorLayers: *mut *mut IDWriteColorGlyphRunEnumerator)
+        -> HRESULT,
+    pub CreateCustomRenderingParams: *const c_void,
+    pub CreateGlyphRunAnalysis: *const c_void,
+}
+
+impl IDWriteFactory2 {
+    #[inline]
+    pub unsafe fn GetSystemFontFallback(&mut self, fontFallback: *mut *mut IDWriteFontFallback)
+                                        -> HRESULT {
+        ((*self.lpVtbl).GetSystemFontFallback)(self, fontFallback)
+    }
+
+    #[inline]
+    pub unsafe fn TranslateColorGlyphRun(
+            &mut self,
+            baselineOriginX: FLOAT,
+            baselineOriginY: FLOAT,
+            glyphRun: *const DWRITE_GLYPH_RUN,
+            glyphRunDescription: *const DWRITE_GLYPH_RUN_DESCRIPTION,
+            measuringMode: DWRITE_MEASURING_MODE,
+            worldToDeviceTransform: *const DWRITE_MATRIX,
+            colorPaletteIndex: UINT32,
+            colorLayers: *mut *mut IDWriteColorGlyphRunEnumerator)
+            -> HRESULT {
+        ((*self.lpVtbl).TranslateColorGlyphRun)(self,
+                                                baselineOriginX,
+                                                baselineOriginY,
+                                                glyphRun,
+                                                glyphRunDescription,
+                                                measuringMode,
+                                                worldToDeviceTransform,
+                                                colorPaletteIndex,
+                                                colorLayers)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteColorGlyphRunEnumerator {
+    pub lpVtbl: *const IDWriteColorGlyphRunEnumeratorVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteColorGlyphRunEnumeratorVtbl {
+    pub parent: IUnknownVtbl,
+    pub MoveNext: unsafe extern "system" fn(This: *mut IDWriteColorGlyphRunEnumerator,
+                                            hasRun: *mut BOOL)
+                                            -> HRESULT,
+    pub GetCurrentRun: unsafe extern "system" fn(This: *mut IDWriteColorGlyphRunEnumerator,
+                                                 colorGlyphRun: *mut *const DWRITE_COLOR_GLYPH_RUN)
+                                                 -> HRESULT,
+}
+
+impl IDWriteColorGlyphRunEnumerator {
+    #[inline]
+    pub unsafe fn MoveNext(&mut self, hasRun: *mut BOOL) -> HRESULT {
+        ((*self.lpVtbl).MoveNext)(self, hasRun)
+    }
+
+    #[inline]
+    pub unsafe fn GetCurrentRun(&mut self, colorGlyphRun: *mut *const DWRITE_COLOR_GLYPH_RUN)
+                                -> HRESULT {
+        ((*self.lpVtbl).GetCurrentRun)(self, colorGlyphRun)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace1 {
+    pub lpVtbl: *const IDWriteFontFace1Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace1Vtbl {
+    pub parent: IDWriteFontFaceVtbl,
+    pub GetMetrics: *const c_void,
+    pub GetGdiCompatibleMetrics: *const c_void,
+    pub GetCaretMetrics: *const c_void,
+    pub GetUnicodeRanges: unsafe extern "system" fn(This: *mut IDWriteFontFace1,
+                                                    maxRangeCount: UINT32,
+                                                    unicodeRanges: *mut DWRITE_UNICODE_RANGE,
+                                                    actualRangeCount: *mut UINT32)
+                                                    -> HRESULT,
+    pub IsMonospacedFont: unsafe extern "system" fn(This: *mut IDWriteFontFace1) -> BOOL,
+    pub GetDesignGlyphAdvances: *const c_void,
+    pub GetGdiCompatibleGlyphAdvances: *const c_void,
+    pub GetKerningPairAdjustments: *const c_void,
+    pub HasKerningPairs: *const c_void,
+    pub GetRecommendedRenderingMode: *const c_void,
+    pub GetVerticalGlyphVariants: *const c_void,
+    pub HasVerticalGlyphVariants: *const c_void,
+}
+
+impl IDWriteFontFace1 {
+    #[inline]
+    pub unsafe fn GetUnicodeRanges(&mut self,
+                                   maxRangeCount: UINT32,
+                                   unicodeRanges: *mut DWRITE_UNICODE_RANGE,
+                                   actualRangeCount: *mut UINT32)
+                                   -> HRESULT {
+        ((*self.lpVtbl).GetUnicodeRanges)(self, maxRangeCount, unicodeRanges, actualRangeCount)
+    }
+
+    #[inline]
+    pub unsafe fn IsMonospacedFont(&mut self) -> BOOL {
+        ((*self.lpVtbl).IsMonospacedFont)(self)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace2Vtbl {
+    pub parent: IDWriteFontFace1Vtbl,
+    pub IsColorFont: *const c_void,
+    pub GetColorPaletteCount: *const c_void,
+    pub GetPaletteEntryCount: *const c_void,
+    pub GetPaletteEntries: *const c_void,
+    pub GetRecommendedRenderingMode: *const c_void,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace3Vtbl {
+    pub parent: IDWriteFontFace2Vtbl,
+    pub GetFontFaceReference: *const c_void,
+    pub GetPanose: *const c_void,
+    pub GetWeight: *const c_void,
+    pub GetStretch: *const c_void,
+    pub GetStyle: *const c_void,
+    pub GetFamilyNames: *const c_void,
+    pub GetFaceNames: *const c_void,
+    pub GetInformationalStrings: *const c_void,
+    pub HasCharacter: *const c_void,
+    pub GetRecommendedRenderingMode: *const c_void,
+    pub IsCharacterLocal: *const c_void,
+    pub IsGlyphLocal: *const c_void,
+    pub AreCharactersLocal: *const c_void,
+    pub AreGlyphsLocal: *const c_void,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace4 {
+    pub lpVtbl: *const IDWriteFontFace4Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace4Vtbl {
+    pub parent: IDWriteFontFace3Vtbl,
+    pub GetGlyphImageFormats_2: unsafe extern "system" fn(
+        This: *mut IDWriteFontFace4,
+        glyphId: UINT16,
+        pixelsPerEmFirst: UINT32,
+        pixelsPerEmLast: UINT32,
+        glyphImageFormats: *mut DWRITE_GLYPH_IMAGE_FORMATS)
+        -> HRESULT,
+    pub GetGlyphImageFormats_1: *const c_void,
+    pub GetGlyphImageData: unsafe extern "system" fn(
+        This: *mut IDWriteFontFace4,
+        glyphId: UINT16,
+        pixelsPerEm: UINT32,
+        glyphImageFormat: DWRITE_GLYPH_IMAGE_FORMATS,
+        glyphData: *mut DWRITE_GLYPH_IMAGE_DATA,
+        glyphDataContext: *mut *mut c_void)
+        -> HRESULT,
+    pub ReleaseGlyphImageData: unsafe extern "system" fn(This: *mut IDWriteFontFace4,
+                                                         glyphDataContext: *mut c_void),
+}
+
+#[cfg(feature = "embedded-bitmaps")]
+impl IDWriteFontFace4 {
+    #[inline]
+    pub unsafe fn GetGlyphImageFormats(&mut self,
+                                       glyphId: UINT16,
+                                       pixelsPerEmFirst: UINT32,
+                                       pixelsPerEmLast: UINT32,
+                                       glyphImageFormats: *mut DWRITE_GLYPH_IMAGE_FORMATS)
+                                       -> HRESULT {
+        ((*self.lpVtbl).GetGlyphImageFormats_2)(self,
+                                                glyphId,
+                                                pixelsPerEmFirst,
+                                                pixelsPerEmLast,
+                                                glyphImageFormats)
+    }
+
+    #[inline]
+    pub unsafe fn GetGlyphImageData(&mut self,
+                                    glyphId: UINT16,
+                                    pixelsPerEm: UINT32,
+                                    glyphImageFormat: DWRITE_GLYPH_IMAGE_FORMATS,
+                                    glyphData: *mut DWRITE_GLYPH_IMAGE_DATA,
+                                    glyphDataContext: *mut *mut c_void)
+                                    -> HRESULT {
+        ((*self.lpVtbl).GetGlyphImageData)(self,
+                                           glyphId,
+                                           pixelsPerEm,
+                                           glyphImageFormat,
+                                           glyphData,
+                                           glyphDataContext)
+    }
+
+    #[inline]
+    pub unsafe fn ReleaseGlyphImageData(&mut self, glyphDataContext: *mut c_void) {
+        ((*self.lpVtbl).ReleaseGlyphImageData)(self, glyphDataContext)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace5 {
+    pub lpVtbl: *const IDWriteFontFace5Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFace5Vtbl {
+    pub parent: IDWriteFontFace4Vtbl,
+    pub GetFontAxisValueCount: unsafe extern "system" fn(This: *mut IDWriteFontFace5) -> UINT32,
+    pub GetFontAxisValues: unsafe extern "system" fn(This: *mut IDWriteFontFace5,
+                                                     fontAxisValues: *mut DWRITE_FONT_AXIS_VALUE,
+                                                     fontAxisValueCount: UINT32)
+                                                     -> HRESULT,
+    pub HasVariations: unsafe extern "system" fn(This: *mut IDWriteFontFace5) -> BOOL,
+    pub GetFontResource: unsafe extern "system" fn(This: *mut IDWriteFontFace5,
+                                                   fontResource: *mut *mut IDWriteFontResource)
+                                                   -> HRESULT,
+    pub Equals: unsafe extern "system" fn(This: *mut IDWriteFontFace5,
+                                          fontFace: *mut IDWriteFontFace)
+                                          -> BOOL,
+}
+
+impl IDWriteFontFace5 {
+    #[inline]
+    pub unsafe fn GetFontAxisValueCount(&mut self) -> UINT32 {
+        ((*self.lpVtbl).GetFontAxisValueCount)(self)
+    }
+    #[inline]
+    pub unsafe fn GetFontAxisValues(&mut self,
+                                    fontAxisValues: *mut DWRITE_FONT_AXIS_VALUE,
+                                    fontAxisValueCount: UINT32)
+                                    -> HRESULT {
+        ((*self.lpVtbl).GetFontAxisValues)(self, fontAxisValues, fontAxisValueCount)
+    }
+    #[inline]
+    pub unsafe fn GetFontResource(&mut self, fontResource: *mut *mut IDWriteFontResource)
+                                  -> HRESULT {
+        ((*self.lpVtbl).GetFontResource)(self, fontResource)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFontResource {
+    pub lpVtbl: *const IDWriteFontResourceVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFontResourceVtbl {
+    pub parent: IUnknownVtbl,
+    pub GetFontFile: *const c_void,
+    pub GetFontFaceIndex: *const c_void,
+    pub GetFontAxisCount: unsafe extern "system" fn(This: *mut IDWriteFontResource) -> UINT32,
+    pub GetDefaultFontAxisValues: unsafe extern "system" fn(
+        This: *mut IDWriteFontResource,
+        fontAxisValues: *mut DWRITE_FONT_AXIS_VALUE,
+        fontAxisValueCount: UINT32)
+        -> HRESULT,
+    pub GetFontAxisRanges: unsafe extern "system" fn(This: *mut IDWriteFontResource,
+                                                     fontAxisRanges: *mut DWRITE_FONT_AXIS_RANGE,
+                                                     fontAxisRangeCount: UINT32)
+                                                     -> HRESULT,
+    pub GetFontAxisAttributes: *const c_void,
+    pub GetAxisNames: *const c_void,
+    pub GetAxisValueNameCount: *const c_void,
+    pub GetAxisValueNames: *const c_void,
+    pub HasVariations: *const c_void,
+    pub CreateFontFace: unsafe extern "system" fn(This: *mut IDWriteFontResource,
+                                                  fontSimulations: DWRITE_FONT_SIMULATIONS,
+                                                  fontAxisValues: *const DWRITE_FONT_AXIS_VALUE,
+                                                  fontAxisValueCount: UINT32,
+                                                  fontFace: *mut *mut IDWriteFontFace5)
+                                                  -> HRESULT,
+    pub CreateFontFaceReference: *const c_void,
+}
+
+impl IDWriteFontResource {
+    #[inline]
+    pub unsafe fn GetFontAxisCount(&mut self) -> UINT32 {
+        ((*self.lpVtbl).GetFontAxisCount)(self)
+    }
+
+    #[inline]
+    pub unsafe fn GetDefaultFontAxisValues(&mut self,
+                                           fontAxisValues: *mut DWRITE_FONT_AXIS_VALUE,
+                                           fontAxisValueCount: UINT32)
+                                           -> HRESULT {
+        ((*self.lpVtbl).GetDefaultFontAxisValues)(self, fontAxisValues, fontAxisValueCount)
+    }
+
+    #[inline]
+    pub unsafe fn GetFontAxisRanges(&mut self,
+                                    fontAxisRanges: *mut DWRITE_FONT_AXIS_RANGE,
+                                    fontAxisRangeCount: UINT32)
+                                    -> HRESULT {
+        ((*self.lpVtbl).GetFontAxisRanges)(self, fontAxisRanges, fontAxisRangeCount)
+    }
+
+    #[inline]
+    pub unsafe fn CreateFontFace(&mut self,
+                                 fontSimulations: DWRITE_FONT_SIMULATIONS,
+                                 fontAxisValues: *const DWRITE_FONT_AXIS_VALUE,
+                                 fontAxisValueCount: UINT32,
+                                 fontFace: *mut *mut IDWriteFontFace5)
+                                 -> HRESULT {
+        ((*self.lpVtbl).CreateFontFace)(self,
+                                        fontSimulations,
+                                        fontAxisValues,
+                                        fontAxisValueCount,
+                                        fontFace)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteFontFallback {
+    pub lpVtbl: *const IDWriteFontFallbackVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFontFallbackVtbl {
+    pub parent: IUnknownVtbl,
+    pub MapCharacters: unsafe extern "system" fn(This: *mut IDWriteFontFallback,
+                                                 analysisSource: *mut IDWriteTextAnalysisSource,
+                                                 textPosition: UINT32,
+                                                 textLength: UINT32,
+                                                 baseFontCollection: *mut IDWriteFontCollection,
+                                                 baseFamilyName: *const WCHAR,
+                                                 baseWeight: DWRITE_FONT_WEIGHT,
+                                                 baseStyle: DWRITE_FONT_STYLE,
+                                                 baseStretch: DWRITE_FONT_STRETCH,
+                                                 mappedLength: *mut UINT32,
+                                                 mappedFont: *mut *mut IDWriteFont,
+                                                 scale: *mut FLOAT)
+                                                 -> HRESULT,
+}
+
+impl IDWriteFontFallback {
+    #[inline]
+    pub unsafe fn MapCharacters(&mut self,
+                                analysisSource: *mut IDWriteTextAnalysisSource,
+                                textPosition: UINT32,
+                                textLength: UINT32,
+                                baseFontCollection: *mut IDWriteFontCollection,
+                                baseFamilyName: *const WCHAR,
+                                baseWeight: DWRITE_FONT_WEIGHT,
+                                baseStyle: DWRITE_FONT_STYLE,
+                                baseStretch: DWRITE_FONT_STRETCH,
+                                mappedLength: *mut UINT32,
+                                mappedFont: *mut *mut IDWriteFont,
+                                scale: *mut FLOAT)
+                                -> HRESULT {
+        ((*self.lpVtbl).MapCharacters)(self,
+                                       analysisSource,
+                                       textPosition,
+                                       textLength,
+                                       baseFontCollection,
+                                       baseFamilyName,
+                                       baseWeight,
+                                       baseStyle,
+                                       baseStretch,
+                                       mappedLength,
+                                       mappedFont,
+                                       scale)
+    }
+}
+
+#[repr(C)]
+pub struct IDWriteTextAnalyzer1 {
+    pub lpVtbl: *const IDWriteTextAnalyzer1Vtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteTextAnalyzer1Vtbl {
+    pub parent: IDWriteTextAnalyzerVtbl,
+    pub ApplyCharacterSpacing: *const c_void,
+    pub GetBaseline: *const c_void,
+    pub AnalyzeVerticalGlyphOrientation: *const c_void,
+    pub GetGlyphOrientationTransform: *const c_void,
+    pub GetScriptProperties: unsafe extern "system" fn(
+        This: *mut IDWriteTextAnalyzer1,
+        scriptAnalysis: DWRITE_SCRIPT_ANALYSIS,
+        scriptProperties: *mut DWRITE_SCRIPT_PROPERTIES)
+        -> HRESULT,
+    pub GetTextComplexity: *const c_void,
+    pub GetJustificationOpportunities: *const c_void,
+    pub JustifyGlyphAdvances: *const c_void,
+    pub GetJustifiedGlyphs: *const c_void,
+}
+
+impl IDWriteTextAnalyzer1 {
+    #[inline]
+    pub unsafe fn GetScriptProperties(&mut self,
+                                      scriptAnalysis: DWRITE_SCRIPT_ANALYSIS,
+                                      scriptProperties: *mut DWRITE_SCRIPT_PROPERTIES)
+                                      -> HRESULT {
+        ((*self.lpVtbl).GetScriptProperties)(self, scriptAnalysis, scriptProperties)
+    }
+}
@@ -13,35 +13,99 @@
 #![allow(non_snake_case, non_upper_case_globals)]
 
 use dwrite;
-use euclid::{Point2D, Size2D};
-use kernel32;
+use euclid::{Point2D, Rect, Size2D, Transform2D, Vector2D};
+use flate2::read::GzDecoder;
+use gdi32;
+#[cfg(feature = "embedded-bitmaps")]
+use image;
+use lyon_geom::{CubicBezierSegment, QuadraticBezierSegment};
 use lyon_path::PathEvent;
-use std::collections::BTreeMap;
+use lyon_path::builder::{FlatPathBuilder, PathBuilder};
+use lyon_path::default::Path;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Keys;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
 use std::hash::Hash;
+use std::io::Read;
 use std::iter::Cloned;
 use std::mem;
-use std::os::raw::c_void;
+use std::ops::Range;
+use std::os::raw::{c_int, c_void};
+use std::path::Path as FilePath;
 use std::ptr;
 use std::slice::{self, Iter};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::u16;
 use uuid::IID_ID2D1SimplifiedGeometrySink;
 use winapi::winerror::{self, S_OK};
 use winapi::{self, BOOL, D2D1_BEZIER_SEGMENT, D2D1_FIGURE_BEGIN, D2D1_FIGURE_END};
-use winapi::{D2D1_FIGURE_END_CLOSED, D2D1_FILL_MODE, D2D1_PATH_SEGMENT, D2D1_POINT_2F};
-use winapi::{DWRITE_FONT_METRICS, DWRITE_GLYPH_METRICS, E_BOUNDS, E_INVALIDARG, FALSE, FILETIME};
+use winapi::{D2D1_FIGURE_END_CLOSED, D2D1_FILL_MODE, D2D1_FILL_MODE_ALTERNATE, D2D1_PATH_SEGMENT};
+use winapi::D2D1_POINT_2F;
+use winapi::{DWRITE_FONT_METRICS, DWRITE_GLYPH_METRICS, E_BOUNDS, E_INVALIDARG, FALSE};
 use winapi::{FLOAT, GUID, HRESULT, ID2D1SimplifiedGeometrySinkVtbl, IDWriteFactory};
-use winapi::{IDWriteFontCollectionLoader, IDWriteFontCollectionLoaderVtbl, IDWriteFontFace};
+use winapi::{DWRITE_FONT_SIMULATIONS_NONE, IDWriteFontCollection, IDWriteFontCollectionLoader};
+use winapi::{IDWriteFont, IDWriteFontCollectionLoaderVtbl, IDWriteFontFace};
 use winapi::{IDWriteFontFile, IDWriteFontFileEnumerator, IDWriteFontFileEnumeratorVtbl};
 use winapi::{IDWriteFontFileLoader, IDWriteFontFileLoaderVtbl, IDWriteFontFileStream};
 use winapi::{IDWriteFontFileStreamVtbl, IDWriteGeometrySink, IUnknown, IUnknownVtbl, TRUE, UINT16};
-use winapi::{UINT32, UINT64, UINT};
-use winapi::{DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL};
+use winapi::{BITMAP, COLORREF, DWRITE_GLYPH_OFFSET, DWRITE_GLYPH_RUN, IDWriteBitmapRenderTarget};
+use winapi::{DWRITE_MEASURING_MODE_NATURAL, E_FAIL, OBJ_BITMAP, RECT, UINT32, UINT64, UINT};
+use winapi::{DWRITE_FONT_STRETCH, DWRITE_FONT_STYLE_ITALIC, DWRITE_FONT_STYLE_NORMAL};
+use winapi::{DWRITE_FONT_STYLE, DWRITE_FONT_STYLE_OBLIQUE, DWRITE_FONT_WEIGHT};
+use winapi::{DWRITE_FONT_FEATURE, DWRITE_FONT_FEATURE_TAG, DWRITE_SCRIPT_ANALYSIS};
+use winapi::{DWRITE_SCRIPT_SHAPES_DEFAULT, DWRITE_SHAPING_GLYPH_PROPERTIES};
+use winapi::{DWRITE_SHAPING_TEXT_PROPERTIES, DWRITE_TYPOGRAPHIC_FEATURES};
+use winapi::{DWRITE_PIXEL_GEOMETRY_FLAT, DWRITE_PIXEL_GEOMETRY_RGB, DWRITE_RENDERING_MODE_ALIASED};
+use winapi::{DWRITE_RENDERING_MODE, DWRITE_RENDERING_MODE_DEFAULT};
+use winapi::{DWRITE_RENDERING_MODE_GDI_CLASSIC, DWRITE_RENDERING_MODE_GDI_NATURAL};
+use winapi::{DWRITE_RENDERING_MODE_NATURAL, DWRITE_RENDERING_MODE_NATURAL_SYMMETRIC};
+use winapi::{DWRITE_RENDERING_MODE_OUTLINE, IDWriteLocalizedStrings};
+use winapi::{DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_WEIGHT_NORMAL, DWRITE_READING_DIRECTION};
+use winapi::{DWRITE_READING_DIRECTION_LEFT_TO_RIGHT, IDWriteNumberSubstitution};
+use winapi::{IDWriteTextAnalysisSource, IDWriteTextAnalysisSourceVtbl, IDWriteTextAnalyzer, WCHAR};
+use winapi::{DWRITE_INFORMATIONAL_STRING_ID, DWRITE_INFORMATIONAL_STRING_PREFERRED_FAMILY_NAMES};
+use winapi::DWRITE_INFORMATIONAL_STRING_WIN32_FAMILY_NAMES;
 use widestring::WideCString;
 
-use self::com::{PathfinderCoclass, PathfinderComObject, PathfinderComPtr};
-use {FontInstance, GlyphDimensions, GlyphImage, GlyphKey};
+use self::com::{PathfinderCoclass, PathfinderComObject};
+use self::interfaces::{DWRITE_FONT_AXIS_RANGE, DWRITE_FONT_AXIS_VALUE, IDWriteFontFace5};
+use self::interfaces::{DWRITE_E_NOCOLOR, IDWriteFactory2};
+use self::interfaces::{DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE, DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE};
+use self::interfaces::{IDWriteBitmapRenderTarget1, IDWriteFontFace1};
+use self::interfaces::{IDWriteFontResource, IID_IDWriteBitmapRenderTarget1, IID_IDWriteFactory2};
+use self::interfaces::{IID_IDWriteFontFace1, IID_IDWriteFontFace5};
+#[cfg(feature = "embedded-bitmaps")]
+use self::interfaces::{DWRITE_GLYPH_IMAGE_DATA, DWRITE_GLYPH_IMAGE_FORMATS_JPEG};
+#[cfg(feature = "embedded-bitmaps")]
+use self::interfaces::{DWRITE_GLYPH_IMAGE_FORMATS_PNG, DWRITE_GLYPH_IMAGE_FORMATS_TIFF};
+#[cfg(feature = "embedded-bitmaps")]
+use self::interfaces::{DWRITE_GLYPH_IMAGE_FORMATS_PREMULTIPLIED_B8G8R8A8, IDWriteFontFace4};
+#[cfg(feature = "embedded-bitmaps")]
+use self::interfaces::IID_IDWriteFontFace4;
+use self::interfaces::{DWRITE_UNICODE_RANGE, IDWriteFontFallback};
+use self::interfaces::{DWRITE_SCRIPT_PROPERTIES, IDWriteTextAnalyzer1, IID_IDWriteTextAnalyzer1};
+use self::interfaces::DWRITE_INFORMATIONAL_STRING_FULL_NAME;
+use self::interfaces::DWRITE_INFORMATIONAL_STRING_POSTSCRIPT_NAME;
+use {CollectionFaceInfo, ColorGlyphLayer, FeatureTag, FillMode, FontInstance, FontMetrics};
+use {FontNames, FontStyle, FontVariation, GlyphCacheKey, GlyphClass, GlyphDesignMetrics};
+use {GlyphDimensions, GlyphExtents, GlyphImage};
+use GlyphKey;
+use {LineMetrics, MathConstants, NamedInstance, OutlineFlavor, PaintGraph, PixelFormat};
+use {RasterizationMode, RenderingMode, ShapedGlyph, SubpixelOffset};
+use {VariationAxis, VerticalGlyphDimensions};
+
+pub use self::com::PathfinderComPtr;
 
 mod com;
+mod interfaces;
+mod tables;
+#[cfg(feature = "woff")]
+mod woff;
 
 DEFINE_GUID! {
     IID_IDWriteFactory, 0xb859ee5a, 0xd838, 0x4b5b, 0xa2, 0xe8, 0x1a, 0xdc, 0x7d, 0x93, 0xdb, 0x48
@@ -62,32 +126,290 @@ DEFINE_GUID! {
     IID_IDWriteFontFileStream,
     0x6d4865fe, 0x0ab8, 0x4d91, 0x8f, 0x62, 0x5d, 0xd6, 0xbe, 0x34, 0xa3, 0xe0
 }
+DEFINE_GUID! {
+    IID_IDWriteTextAnalysisSource,
+    0x688e1a58, 0x5094, 0x47c8, 0xad, 0xc8, 0xfb, 0xce, 0xa6, 0x0a, 0xe9, 0x2b
+}
+
+// The number of blank pixels to leave around a glyph when rasterizing it natively, to make room
+// for antialiasing and any inaccuracy in the design metrics.
+const RASTERIZATION_PADDING: i32 = 2;
+
+const WHITE_COLORREF: COLORREF = 0x00ffffff;
+
+// The palette index of color glyph layers that should be drawn in the text color.
+const FOREGROUND_PALETTE_INDEX: UINT16 = 0xffff;
+
+// `HRESULT_FROM_WIN32(ERROR_INSUFFICIENT_BUFFER)`.
+const E_NOT_SUFFICIENT_BUFFER: HRESULT = 0x8007007a_u32 as HRESULT;
+
+// The locale that text is shaped in, which affects locale-specific forms, and that localized names
+// are looked up in.
+const DEFAULT_LOCALE_NAME: &'static str = "en-us";
+
+// The maximum number of times a cubic Bézier curve is split in half when approximating it with
+// quadratic curves. This bounds the work done for degenerate curves or a tolerance of zero.
+const MAX_CUBIC_SUBDIVISION_DEPTH: u8 = 16;
+
+
+/// An error that occurred while loading or querying a font with DirectWrite.
+/// 
+/// Variants that wrap an `HRESULT` carry the failure code returned by DirectWrite, so that it can
+/// be logged or looked up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontError {
+    /// The DirectWrite factory couldn't be created.
+    FactoryCreationFailed(HRESULT),
+    /// DirectWrite failed to load the font file, collection, family, or face.
+    FontFileLoadFailed(HRESULT),
+    /// No font with the given key has been loaded into this context.
+    FontNotLoaded,
+    /// The glyph doesn't exist in the font.
+    GlyphNotFound,
+    /// The font data is truncated or malformed.
+    /// 
+    /// This carries the tag of the table that is truncated, malformed, or doesn't match its
+    /// checksum, or `None` if the header or table directory of the font is itself bad.
+    CorruptFont(Option<[u8; 4]>),
+    /// The requested family isn't installed in the system font collection.
+    SystemFontMissing,
+    /// The family name can't be passed to DirectWrite, because it contains a NUL character.
+    InvalidFamilyName,
+    /// The font collection has no face at the requested index.
+    FontIndexOutOfRange(u32),
+    /// The font loaded, but it has no glyphs.
+    NoGlyphs,
+    /// The font has no character map that DirectWrite can use to map characters to glyphs.
+    NoCharacterMap,
+    /// The font declares zero units per em, so it can't be scaled.
+    InvalidUnitsPerEm,
+    /// DirectWrite failed to answer a query about an already-loaded font.
+    QueryFailed(HRESULT),
+    /// DirectWrite failed to rasterize a glyph.
+    RasterizationFailed(HRESULT),
+    /// The glyph image doesn't fit in the destination buffer at the requested position.
+    DestinationTooSmall,
+    /// An argument is out of range or inconsistent with the others, such as a text range that
+    /// isn't on a character boundary or a script that DirectWrite doesn't know.
+    InvalidArgument,
+    /// The operation isn't supported by this backend or this version of DirectWrite.
+    Unsupported,
+}
+
+impl Display for FontError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            FontError::FactoryCreationFailed(result) => {
+                write!(formatter, "failed to create the DirectWrite factory (HRESULT {:#010x})",
+                       result)
+            }
+            FontError::FontFileLoadFailed(result) => {
+                write!(formatter, "failed to load the font (HRESULT {:#010x})", result)
+            }
+            FontError::FontNotLoaded => formatter.write_str("no font is loaded with that key"),
+            FontError::GlyphNotFound => formatter.write_str("the glyph isn't present in the font"),
+            FontError::CorruptFont(None) => {
+                formatter.write_str("the font's header or table directory is corrupt")
+            }
+            FontError::CorruptFont(Some(tag)) => {
+                write!(formatter, "the font's `{}` table is corrupt", String::from_utf8_lossy(&tag))
+            }
+            FontError::SystemFontMissing => {
+                formatter.write_str("the font family isn't installed on the system")
+            }
+            FontError::InvalidFamilyName => {
+                formatter.write_str("the font family name contains a NUL character")
+            }
+            FontError::FontIndexOutOfRange(font_index) => {
+                write!(formatter, "the font collection has no face at index {}", font_index)
+            }
+            FontError::NoGlyphs => formatter.write_str("the font has no glyphs"),
+            FontError::NoCharacterMap => {
+                formatter.write_str("the font has no usable character map")
+            }
+            FontError::InvalidUnitsPerEm => formatter.write_str("the font has zero units per em"),
+            FontError::QueryFailed(result) => {
+                write!(formatter, "a DirectWrite query failed (HRESULT {:#010x})", result)
+            }
+            FontError::RasterizationFailed(result) => {
+                write!(formatter, "failed to rasterize the glyph (HRESULT {:#010x})", result)
+            }
+            FontError::DestinationTooSmall => {
+                formatter.write_str("the glyph doesn't fit in the destination buffer")
+            }
+            FontError::InvalidArgument => formatter.write_str("an argument is invalid"),
+            FontError::Unsupported => formatter.write_str("the operation isn't supported"),
+        }
+    }
+}
+
+impl Error for FontError {
+    fn description(&self) -> &str {
+        match *self {
+            FontError::FactoryCreationFailed(_) => "failed to create the DirectWrite factory",
+            FontError::FontFileLoadFailed(_) => "failed to load the font",
+            FontError::FontNotLoaded => "no font is loaded with that key",
+            FontError::GlyphNotFound => "the glyph isn't present in the font",
+            FontError::CorruptFont(_) => "the font data is corrupt",
+            FontError::SystemFontMissing => "the font family isn't installed on the system",
+            FontError::InvalidFamilyName => "the font family name contains a NUL character",
+            FontError::FontIndexOutOfRange(_) => "the font collection has no face at that index",
+            FontError::NoGlyphs => "the font has no glyphs",
+            FontError::NoCharacterMap => "the font has no usable character map",
+            FontError::InvalidUnitsPerEm => "the font has zero units per em",
+            FontError::QueryFailed(_) => "a DirectWrite query failed",
+            FontError::RasterizationFailed(_) => "failed to rasterize the glyph",
+            FontError::DestinationTooSmall => "the glyph doesn't fit in the destination buffer",
+            FontError::InvalidArgument => "an argument is invalid",
+            FontError::Unsupported => "the operation isn't supported",
+        }
+    }
+}
 
-static PATHFINDER_FONT_COLLECTION_KEY: [u8; 17] = *b"MEMORY_COLLECTION";
-static PATHFINDER_FONT_FILE_KEY: [u8; 11] = *b"MEMORY_FILE";
+/// The storage behind a font loaded with `FontContext::add_font_from_backing`.
+/// 
+/// DirectWrite reads fonts a fragment at a time, so a backing doesn't have to hold the whole font
+/// in memory: it can be a memory-mapped file, or a seekable reader that copies each fragment out
+/// on demand. `Vec<u8>` implements this trait for fonts that are already in memory.
+pub trait FontFileBacking: Send + Sync {
+    /// Returns the size of the font file in bytes.
+    fn len(&self) -> u64;
+
+    /// Returns `length` bytes of the font file starting at `offset`, or `None` if they can't be
+    /// read.
+    /// 
+    /// Borrowed fragments must remain valid for as long as the backing is alive. Owned fragments
+    /// are freed when DirectWrite is done with them.
+    fn read_fragment(&self, offset: u64, length: u64) -> Option<Cow<[u8]>>;
+}
+
+impl FontFileBacking for Vec<u8> {
+    #[inline]
+    fn len(&self) -> u64 {
+        (**self).len() as u64
+    }
+
+    #[inline]
+    fn read_fragment(&self, offset: u64, length: u64) -> Option<Cow<[u8]>> {
+        if offset > self.len() as u64 || length > self.len() as u64 - offset {
+            return None
+        }
+        Some(Cow::Borrowed(&self[(offset as usize)..((offset + length) as usize)]))
+    }
+}
+
+// A loaded font face, along with the font in a collection that it was created from.
+#[derive(Clone)]
+struct LoadedFontFace {
+    font_face: PathfinderComPtr<IDWriteFontFace>,
+    // Faces loaded directly from a file don't belong to a collection, so they have no font.
+    font: Option<PathfinderComPtr<IDWriteFont>>,
+    // The font-wide metrics of the face, fetched once so that per-glyph queries don't have to.
+    font_metrics: DWRITE_FONT_METRICS,
+}
+
+impl LoadedFontFace {
+    unsafe fn new(font_face: PathfinderComPtr<IDWriteFontFace>,
+                  font: Option<PathfinderComPtr<IDWriteFont>>)
+                  -> LoadedFontFace {
+        let mut font_metrics: DWRITE_FONT_METRICS = mem::zeroed();
+        (**font_face).GetMetrics(&mut font_metrics);
+        LoadedFontFace {
+            font_face: font_face,
+            font: font,
+            font_metrics: font_metrics,
+        }
+    }
+}
 
 /// An object that loads and renders fonts using Windows DirectWrite.
+/// 
+/// A font context can be sent to another thread, but it can't be shared between threads.
 pub struct FontContext<FK> where FK: Clone + Hash + Eq + Ord {
     dwrite_factory: PathfinderComPtr<IDWriteFactory>,
-    dwrite_font_faces: BTreeMap<FK, PathfinderComPtr<IDWriteFontFace>>,
+    dwrite_font_faces: BTreeMap<FK, LoadedFontFace>,
+    // The loaders that serve fonts added from memory. These are registered with the factory for
+    // the lifetime of the context and unregistered when the registrations are dropped, collection
+    // loader first.
+    font_collection_loader: FontCollectionLoaderRegistration,
+    font_file_loader: FontFileLoaderRegistration,
+    // The key of the in-memory font file that backs each font added from memory.
+    font_file_keys: BTreeMap<FK, u64>,
+    next_font_file_key: Cell<u64>,
+    // Instances of variable fonts, created on demand from the faces in `dwrite_font_faces`.
+    dwrite_variable_font_faces: RefCell<BTreeMap<(FK, Vec<FontVariation>), LoadedFontFace>>,
+    // Outlines that have already been extracted. The font instance keys the cache by size and
+    // variation as well as by font. Within an instance, outlines are keyed by glyph index,
+    // sideways, and right-to-left, since outlines don't depend on the subpixel offset.
+    outline_cache: BTreeMap<FontInstance<FK>, BTreeMap<(u32, bool, bool), GlyphOutline>>,
+    // Character-to-glyph mappings that have already been looked up. Variations don't affect the
+    // `cmap`, so this is keyed by font alone and filled lazily.
+    cmap_cache: RefCell<BTreeMap<FK, HashMap<u32, u16>>>,
+    // The sink that outlines are extracted into, reused from glyph to glyph so that extraction
+    // doesn't have to construct a COM object each time.
+    geometry_sink: PathfinderComPtr<PathfinderGeometrySink>,
+    // Whether outlines are flipped to be Y-up.
+    y_flip: bool,
+    // How far to push outlines outward, in ems.
+    outline_dilation: f32,
 }
 
+// The factory is created with `DWRITE_FACTORY_TYPE_SHARED`, and DirectWrite documents the shared
+// factory and the font objects created from it as free-threaded, so the COM pointers can be used
+// from any thread, regardless of apartment. Our own COM objects use atomic reference counts and
+// keep their mutable state behind a `Mutex`, including the geometry sink that every outline
+// extraction shares. The context isn't `Sync`, however: its caches use `Cell` and `RefCell`, so it
+// can be moved to another thread but not shared between them.
+unsafe impl<FK> Send for FontContext<FK> where FK: Clone + Hash + Eq + Ord + Send {}
+
 impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
     /// Creates a new font context instance.
-    pub fn new() -> Result<FontContext<FK>, ()> {
+    pub fn new() -> Result<FontContext<FK>, FontError> {
         unsafe {
             let mut factory: *mut IDWriteFactory = ptr::null_mut();
-            if !winerror::SUCCEEDED(dwrite::DWriteCreateFactory(winapi::DWRITE_FACTORY_TYPE_SHARED,
-                                                                &IID_IDWriteFactory,
-                                                                &mut factory as *mut *mut _ as
-                                                                *mut *mut IUnknown)) {
-                return Err(())
+            let result = dwrite::DWriteCreateFactory(winapi::DWRITE_FACTORY_TYPE_SHARED,
+                                                     &IID_IDWriteFactory,
+                                                     &mut factory as *mut *mut _ as
+                                                     *mut *mut IUnknown);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::FactoryCreationFailed(result))
             }
-            let factory = PathfinderComPtr::new(factory);
+            FontContext::from_factory(PathfinderComPtr::new(factory))
+        }
+    }
+
+    /// Creates a new font context instance that uses an existing DirectWrite factory instead of
+    /// the shared one.
+    /// 
+    /// This is useful with an isolated factory, so that the font loaders that this context
+    /// registers don't affect other users of the shared factory. The loaders are unregistered from
+    /// the factory again when the context is dropped.
+    pub fn from_factory(factory: PathfinderComPtr<IDWriteFactory>)
+                        -> Result<FontContext<FK>, FontError> {
+        unsafe {
+            // If registering the collection loader fails, dropping the file loader registration
+            // unregisters the file loader again.
+            let font_file_loader = try!(FontFileLoaderRegistration::new(
+                &factory,
+                PathfinderFontFileLoader::new()).map_err(FontError::FactoryCreationFailed));
+            let font_collection_loader = try!(FontCollectionLoaderRegistration::new(
+                &factory,
+                PathfinderFontCollectionLoader::new(font_file_loader.loader.clone()))
+                .map_err(FontError::FactoryCreationFailed));
 
             Ok(FontContext {
                 dwrite_factory: factory,
                 dwrite_font_faces: BTreeMap::new(),
+                font_collection_loader: font_collection_loader,
+                font_file_loader: font_file_loader,
+                font_file_keys: BTreeMap::new(),
+                next_font_file_key: Cell::new(0),
+                dwrite_variable_font_faces: RefCell::new(BTreeMap::new()),
+                outline_cache: BTreeMap::new(),
+                cmap_cache: RefCell::new(BTreeMap::new()),
+                geometry_sink: PathfinderGeometrySink::new(true),
+                y_flip: true,
+                outline_dilation: 0.0,
             })
         }
     }
@@ -100,285 +422,4104 @@ impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
     /// `bytes` is the raw OpenType data (i.e. the contents of the `.otf` or `.ttf` file on disk).
     /// 
     /// `font_index` is the index of the font within the collection, if `bytes` refers to a
-    /// collection (`.ttc`).
-    pub fn add_font_from_memory(&mut self, font_key: &FK, bytes: Arc<Vec<u8>>, _: u32)
-                                -> Result<(), ()> {
+    /// collection (`.ttc`). If the collection has no face at that index,
+    /// `FontError::FontIndexOutOfRange` is returned.
+    #[inline]
+    pub fn add_font_from_memory(&mut self, font_key: &FK, bytes: Arc<Vec<u8>>, font_index: u32)
+                                -> Result<(), FontError> {
+        self.add_font_from_backing(font_key, bytes, font_index)
+    }
+
+    /// Loads an OpenType font from memory, first checking that it isn't truncated or corrupt.
+    /// 
+    /// The table directory of the face at `font_index` and every table that it lists must lie
+    /// within `bytes`, and each table must match the checksum recorded in the directory.
+    /// Otherwise, `FontError::CorruptFont` is returned, and the font isn't loaded. This catches
+    /// damaged files up front, rather than as confusing failures when the bad table is used.
+    /// 
+    /// The arguments are the same as those of `add_font_from_memory`.
+    pub fn add_font_from_memory_checked(&mut self,
+                                        font_key: &FK,
+                                        bytes: Arc<Vec<u8>>,
+                                        font_index: u32)
+                                        -> Result<(), FontError> {
+        if self.dwrite_font_faces.contains_key(font_key) {
+            return Ok(())
+        }
+        if let Err(tag) = tables::validate_sfnt(&bytes, font_index) {
+            return Err(FontError::CorruptFont(tag))
+        }
+        self.add_font_from_memory(font_key, bytes, font_index)
+    }
+
+    /// Loads a WOFF or WOFF2 web font from memory.
+    /// 
+    /// DirectWrite only understands raw OpenType data, so the font is unwrapped first, and then
+    /// loaded as with `add_font_from_memory`. Raw OpenType data is accepted too, and is loaded as
+    /// is, so that downloaded fonts can be passed in without checking their format.
+    /// 
+    /// If the web font is truncated or malformed, `FontError::CorruptFont` is returned. WOFF2
+    /// collections aren't supported, and return `FontError::Unsupported`.
+    /// 
+    /// This is only available with the `woff` feature.
+    #[cfg(feature = "woff")]
+    pub fn add_woff_from_memory(&mut self, font_key: &FK, bytes: Arc<Vec<u8>>, font_index: u32)
+                                -> Result<(), FontError> {
+        if self.dwrite_font_faces.contains_key(font_key) {
+            return Ok(())
+        }
+        if !woff::is_woff(&bytes) {
+            return self.add_font_from_memory(font_key, bytes, font_index)
+        }
+
+        match woff::decode(&bytes) {
+            Ok(sfnt) => self.add_font_from_memory(font_key, Arc::new(sfnt), font_index),
+            Err(woff::WoffError::Corrupt(tag)) => Err(FontError::CorruptFont(tag)),
+            Err(woff::WoffError::Unsupported) => Err(FontError::Unsupported),
+        }
+    }
+
+    /// Loads an OpenType font whose data DirectWrite reads on demand from the given backing.
+    /// 
+    /// Use this instead of `add_font_from_memory` to avoid reading a large font into memory all
+    /// at once: for example, by supplying a memory-mapped file. The backing is kept alive for as
+    /// long as the font is loaded.
+    /// 
+    /// `font_key` and `font_index` have the same meaning as in `add_font_from_memory`.
+    pub fn add_font_from_backing(&mut self,
+                                 font_key: &FK,
+                                 backing: Arc<FontFileBacking>,
+                                 font_index: u32)
+                                 -> Result<(), FontError> {
+        if self.dwrite_font_faces.contains_key(font_key) {
+            return Ok(())
+        }
+
+        unsafe {
+            let font_file_key = self.allocate_font_file_key();
+            (**self.font_file_loader.loader).buffers.lock().unwrap().insert(font_file_key, backing);
+
+            let collection_key = font_file_key_bytes(font_file_key);
+            let mut font_collection = ptr::null_mut();
+            let result = (**self.dwrite_factory).CreateCustomFontCollection(
+                *self.font_collection_loader.loader as *mut IDWriteFontCollectionLoader,
+                collection_key.as_ptr() as *const c_void,
+                collection_key.len() as UINT32,
+                &mut font_collection);
+            if !winerror::SUCCEEDED(result) {
+                (**self.font_file_loader.loader).buffers.lock().unwrap().remove(&font_file_key);
+                return Err(FontError::FontFileLoadFailed(result))
+            }
+            let font_collection = PathfinderComPtr::new(font_collection);
+
+            let (font, font_face) = match find_font_face_in_collection(&font_collection,
+                                                                       font_index) {
+                Ok(font_and_face) => font_and_face,
+                Err(error) => {
+                    (**self.font_file_loader.loader).buffers.lock().unwrap().remove(&font_file_key);
+                    return Err(error)
+                }
+            };
+
+            self.replace_font_file_key((*font_key).clone(), Some(font_file_key));
+            self.dwrite_font_faces.insert((*font_key).clone(),
+                                          LoadedFontFace::new(font_face, Some(font)));
+            Ok(())
+        }
+    }
+
+    /// Loads several OpenType fonts from memory at once, such as all the styles of a family.
+    /// 
+    /// Each entry has the same meaning as the arguments of `add_font_from_memory`. All the files
+    /// are placed in a single DirectWrite font collection, which is cheaper than building one
+    /// collection per font. Entries whose font key is already loaded are skipped.
+    /// 
+    /// If any entry fails to load, none of the fonts in the batch are added.
+    pub fn add_fonts_from_memory(&mut self, entries: &[(FK, Arc<Vec<u8>>, u32)])
+                                 -> Result<(), FontError> {
+        let entries: Vec<_> = entries.iter().filter(|&&(ref font_key, _, _)| {
+            !self.dwrite_font_faces.contains_key(font_key)
+        }).collect();
+        if entries.is_empty() {
+            return Ok(())
+        }
+
+        unsafe {
+            // The collection key lists the keys of all the files in the collection.
+            let mut font_file_keys = vec![];
+            let mut collection_key = vec![];
+            for &&(_, ref bytes, _) in &entries {
+                let font_file_key = self.allocate_font_file_key();
+                let backing: Arc<FontFileBacking> = bytes.clone();
+                (**self.font_file_loader.loader).buffers
+                                                .lock()
+                                                .unwrap()
+                                                .insert(font_file_key, backing);
+                font_file_keys.push(font_file_key);
+                collection_key.extend_from_slice(&font_file_key_bytes(font_file_key));
+            }
+
+            match self.load_font_faces_from_memory(&entries, &font_file_keys, &collection_key) {
+                Ok(font_faces) => {
+                    for ((&&(ref font_key, _, _), &font_file_key), loaded_font_face) in
+                            entries.iter().zip(font_file_keys.iter()).zip(font_faces) {
+                        // A font key that appears twice in the batch keeps its first font.
+                        if self.dwrite_font_faces.contains_key(font_key) {
+                            (**self.font_file_loader.loader).buffers
+                                                            .lock()
+                                                            .unwrap()
+                                                            .remove(&font_file_key);
+                            continue
+                        }
+                        self.replace_font_file_key((*font_key).clone(), Some(font_file_key));
+                        self.dwrite_font_faces.insert((*font_key).clone(), loaded_font_face);
+                    }
+                    Ok(())
+                }
+                Err(error) => {
+                    let mut buffers = (**self.font_file_loader.loader).buffers.lock().unwrap();
+                    for font_file_key in &font_file_keys {
+                        buffers.remove(font_file_key);
+                    }
+                    Err(error)
+                }
+            }
+        }
+    }
+
+    // Creates the collection for a batch of fonts added with `add_fonts_from_memory`, and finds
+    // the face for each entry in it.
+    unsafe fn load_font_faces_from_memory(&self,
+                                          entries: &[&(FK, Arc<Vec<u8>>, u32)],
+                                          font_file_keys: &[u64],
+                                          collection_key: &[u8])
+                                          -> Result<Vec<LoadedFontFace>, FontError> {
+        let mut font_collection = ptr::null_mut();
+        let result = (**self.dwrite_factory).CreateCustomFontCollection(
+            *self.font_collection_loader.loader as *mut IDWriteFontCollectionLoader,
+            collection_key.as_ptr() as *const c_void,
+            collection_key.len() as UINT32,
+            &mut font_collection);
+        if !winerror::SUCCEEDED(result) {
+            return Err(FontError::FontFileLoadFailed(result))
+        }
+        let font_collection = PathfinderComPtr::new(font_collection);
+
+        let fonts_and_faces = try!(fonts_and_faces_in_collection(&font_collection));
+        entries.iter().zip(font_file_keys.iter()).map(|(&&(_, _, font_index), &font_file_key)| {
+            fonts_and_faces.iter().find(|&&(_, ref font_face)| {
+                font_face_file_key(font_face) == Some(font_file_key) &&
+                    (**font_face).GetIndex() == font_index
+            }).map(|&(ref font, ref font_face)| {
+                LoadedFontFace::new(font_face.clone(), Some(font.clone()))
+            }).ok_or(FontError::FontIndexOutOfRange(font_index))
+        }).collect()
+    }
+
+    /// Lists the faces in an OpenType font or font collection (`.ttc`) without loading any of
+    /// them.
+    /// 
+    /// The data is loaded into a temporary DirectWrite collection, which is discarded before this
+    /// returns. Faces are returned in order of their index in the collection. Names are picked
+    /// for `locale`, such as `ja-JP`, in the same way as in `system_font_families`.
+    pub fn probe_collection(&self, bytes: Arc<Vec<u8>>, locale: &str)
+                            -> Result<Vec<CollectionFaceInfo>, FontError> {
         unsafe {
-            let font_file_loader = PathfinderFontFileLoader::new(bytes.clone());
+            let font_file_key = self.allocate_font_file_key();
+            let backing: Arc<FontFileBacking> = bytes;
+            (**self.font_file_loader.loader).buffers.lock().unwrap().insert(font_file_key, backing);
+
+            let result = self.probe_font_file(font_file_key, locale);
+            (**self.font_file_loader.loader).buffers.lock().unwrap().remove(&font_file_key);
+            result
+        }
+    }
 
-            let result = (**self.dwrite_factory).RegisterFontFileLoader(
-                font_file_loader.clone().into_raw() as *mut IDWriteFontFileLoader);
+    // Describes each face in the in-memory font file with the given key, for `probe_collection`.
+    unsafe fn probe_font_file(&self, font_file_key: u64, locale: &str)
+                              -> Result<Vec<CollectionFaceInfo>, FontError> {
+        let collection_key = font_file_key_bytes(font_file_key);
+        let mut font_collection = ptr::null_mut();
+        let result = (**self.dwrite_factory).CreateCustomFontCollection(
+            *self.font_collection_loader.loader as *mut IDWriteFontCollectionLoader,
+            collection_key.as_ptr() as *const c_void,
+            collection_key.len() as UINT32,
+            &mut font_collection);
+        if !winerror::SUCCEEDED(result) {
+            return Err(FontError::FontFileLoadFailed(result))
+        }
+        let font_collection = PathfinderComPtr::new(font_collection);
+
+        let mut faces = vec![];
+        for (font, font_face) in try!(fonts_and_faces_in_collection(&font_collection)) {
+            let mut font_family = ptr::null_mut();
+            let result = (**font).GetFontFamily(&mut font_family);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let font_family = PathfinderComPtr::new(font_family);
+
+            let mut family_names = ptr::null_mut();
+            let result = (**font_family).GetFamilyNames(&mut family_names);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let family_names = PathfinderComPtr::new(family_names);
+
+            let mut face_names = ptr::null_mut();
+            let result = (**font).GetFaceNames(&mut face_names);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::QueryFailed(result))
             }
+            let face_names = PathfinderComPtr::new(face_names);
+
+            faces.push(CollectionFaceInfo {
+                font_index: (**font_face).GetIndex(),
+                family_name: try!(localized_string(&family_names, locale)),
+                face_name: try!(localized_string(&face_names, locale)),
+                weight: (**font).GetWeight().0 as u16,
+                stretch: (**font).GetStretch().0 as u8,
+                style: font_style((**font).GetStyle()),
+            })
+        }
+
+        faces.sort_by_key(|face| face.font_index);
+        Ok(faces)
+    }
+
+    // Returns a font file key that hasn't been used before. Every load gets a fresh key, since
+    // DirectWrite caches collections by key.
+    fn allocate_font_file_key(&self) -> u64 {
+        let font_file_key = self.next_font_file_key.get();
+        self.next_font_file_key.set(font_file_key + 1);
+        font_file_key
+    }
+
+    /// Loads an OpenType font from a file on disk.
+    /// 
+    /// DirectWrite maps the file into memory itself, so this is cheaper than reading the file and
+    /// passing its contents to `add_font_from_memory`. The file must remain in place for as long
+    /// as the font is loaded.
+    /// 
+    /// `font_key` and `font_index` have the same meaning as in `add_font_from_memory`.
+    pub fn add_font_from_file(&mut self, font_key: &FK, path: &FilePath, font_index: u32)
+                              -> Result<(), FontError> {
+        if self.dwrite_font_faces.contains_key(font_key) {
+            return Ok(())
+        }
+
+        unsafe {
+            let path = match WideCString::from_str(path) {
+                Ok(path) => path,
+                Err(_) => return Err(FontError::FontFileLoadFailed(E_INVALIDARG)),
+            };
 
             let mut font_file = ptr::null_mut();
-            let result = (**self.dwrite_factory).CreateCustomFontFileReference(
-                PATHFINDER_FONT_FILE_KEY.as_ptr() as *const c_void,
-                PATHFINDER_FONT_FILE_KEY.len() as UINT,
-                font_file_loader.clone().into_raw() as *mut IDWriteFontFileLoader,
-                &mut font_file);
+            let result = (**self.dwrite_factory).CreateFontFileReference(path.as_ptr(),
+                                                                         ptr::null(),
+                                                                         &mut font_file);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font_file = PathfinderComPtr::new(font_file);
 
-            let font_collection_loader = PathfinderFontCollectionLoader::new(font_file);
+            let (mut is_supported, mut file_type, mut face_type, mut face_count) =
+                (FALSE, mem::zeroed(), mem::zeroed(), 0);
+            let result = (**font_file).Analyze(&mut is_supported,
+                                               &mut file_type,
+                                               &mut face_type,
+                                               &mut face_count);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::FontFileLoadFailed(result))
+            }
+            if is_supported == FALSE {
+                return Err(FontError::FontFileLoadFailed(E_FAIL))
+            }
+            if font_index >= face_count {
+                return Err(FontError::FontIndexOutOfRange(font_index))
+            }
 
-            let result = (**self.dwrite_factory).RegisterFontCollectionLoader(
-                font_collection_loader.clone().into_raw() as *mut IDWriteFontCollectionLoader);
+            let mut font_face = ptr::null_mut();
+            let result = (**self.dwrite_factory).CreateFontFace(face_type,
+                                                                1,
+                                                                &*font_file,
+                                                                font_index,
+                                                                DWRITE_FONT_SIMULATIONS_NONE,
+                                                                &mut font_face);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
+            let font_face = PathfinderComPtr::new(font_face);
+
+            self.replace_font_file_key((*font_key).clone(), None);
+            self.dwrite_font_faces.insert((*font_key).clone(),
+                                          LoadedFontFace::new(font_face, None));
+            Ok(())
+        }
+    }
+
+    /// Loads a font from system font collection.
+    /// 
+    /// `font_key` is a handle that is used to refer to the font later. If this context has already
+    /// loaded a font with the same font key, nothing is done, and `Ok` is returned.
+    /// 
+    /// `name` is the family name of the font. If it contains a NUL character, which no installed
+    /// family can, `FontError::InvalidFamilyName` is returned.
+    /// 
+    /// `font_index` is the index of the font within the family. Index 0 selects the face that
+    /// best matches a normal weight, stretch, and style; other indices select the face at that
+    /// position in the family, returning `FontError::FontIndexOutOfRange` if there is none.
+    #[inline]
+    pub fn add_system_font(&mut self, font_key: &FK, name: &str, font_index: u32)
+                           -> Result<(), FontError> {
+        self.add_system_font_styled(font_key, name, 400, 5, FontStyle::Normal, font_index)
+    }
+
+    /// Loads the face of a system font family that best matches the given style.
+    /// 
+    /// `weight` ranges from 1 to 999, with 400 being normal and 700 being bold. `stretch` ranges
+    /// from 1 (ultra-condensed) to 9 (ultra-expanded), with 5 being normal. These follow the
+    /// OpenType `usWeightClass` and `usWidthClass` conventions.
+    /// 
+    /// The other arguments are the same as those of `add_system_font`. In particular, the style
+    /// is only used to pick a face when `font_index` is 0.
+    pub fn add_system_font_styled(&mut self,
+                                  font_key: &FK,
+                                  name: &str,
+                                  weight: u16,
+                                  stretch: u8,
+                                  style: FontStyle,
+                                  font_index: u32)
+                                  -> Result<(), FontError> {
+        if self.dwrite_font_faces.contains_key(font_key) {
+            return Ok(())
+        }
 
+        unsafe {
             let mut font_collection = ptr::null_mut();
-            let result = (**self.dwrite_factory).CreateCustomFontCollection(
-                font_collection_loader.clone().into_raw() as *mut IDWriteFontCollectionLoader,
-                PATHFINDER_FONT_COLLECTION_KEY.as_ptr() as *const c_void,
-                PATHFINDER_FONT_COLLECTION_KEY.len() as UINT32,
-                &mut font_collection);
+            let result = (**self.dwrite_factory).GetSystemFontCollection(
+                &mut font_collection, FALSE);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font_collection = PathfinderComPtr::new(font_collection);
 
+            let mut font_family_index = 0;
+            let mut exists = 0;
+            let family_name = match WideCString::from_str(name) {
+                Ok(family_name) => family_name,
+                Err(_) => return Err(FontError::InvalidFamilyName),
+            };
+            let result = (**font_collection).FindFamilyName(
+                family_name.as_ptr(), &mut font_family_index, &mut exists);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::FontFileLoadFailed(result))
+            }
+            if exists == FALSE {
+                return Err(FontError::SystemFontMissing)
+            }
+            
             let mut font_family = ptr::null_mut();
-            let result = (**font_collection).GetFontFamily(0, &mut font_family);
+            let result = (**font_collection).GetFontFamily(
+                font_family_index, &mut font_family);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font_family = PathfinderComPtr::new(font_family);
 
+            // Index 0 keeps the historical behavior of picking the face that matches the style.
             let mut font = ptr::null_mut();
-            let result = (**font_family).GetFont(0, &mut font);
+            let result = if font_index == 0 {
+                let style = match style {
+                    FontStyle::Normal => DWRITE_FONT_STYLE_NORMAL,
+                    FontStyle::Oblique => DWRITE_FONT_STYLE_OBLIQUE,
+                    FontStyle::Italic => DWRITE_FONT_STYLE_ITALIC,
+                };
+                (**font_family).GetFirstMatchingFont(DWRITE_FONT_WEIGHT(weight as u32),
+                                                     DWRITE_FONT_STRETCH(stretch as u32),
+                                                     style,
+                                                     &mut font)
+            } else {
+                if font_index >= (**font_family).GetFontCount() {
+                    return Err(FontError::FontIndexOutOfRange(font_index))
+                }
+                (**font_family).GetFont(font_index, &mut font)
+            };
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font = PathfinderComPtr::new(font);
 
             let mut font_face = ptr::null_mut();
             let result = (**font).CreateFontFace(&mut font_face);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font_face = PathfinderComPtr::new(font_face);
 
-            let result = (**self.dwrite_factory).UnregisterFontCollectionLoader(
-                font_collection_loader.into_raw() as *mut IDWriteFontCollectionLoader);
-            if !winerror::SUCCEEDED(result) {
-                return Err(())
-            }
+            self.replace_font_file_key((*font_key).clone(), None);
+            self.dwrite_font_faces.insert((*font_key).clone(),
+                                          LoadedFontFace::new(font_face, Some(font)));
+            Ok(())
+        }
+    }
 
-            let result = (**self.dwrite_factory).UnregisterFontFileLoader(
-                font_file_loader.into_raw() as *mut IDWriteFontFileLoader);
+    /// Returns the names of the font families installed on the system, in the order DirectWrite
+    /// lists them.
+    /// 
+    /// `locale` is a locale name such as `ja-JP`. Names are returned in that locale when the family
+    /// localizes its name, falling back to another region of the same language, then to US
+    /// English, and then to the first name the family lists. Any of these can be passed to
+    /// `add_system_font`.
+    pub fn system_font_families(&self, locale: &str) -> Result<Vec<String>, FontError> {
+        unsafe {
+            let mut font_collection = ptr::null_mut();
+            let result = (**self.dwrite_factory).GetSystemFontCollection(&mut font_collection,
+                                                                         FALSE);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::QueryFailed(result))
             }
+            let font_collection = PathfinderComPtr::new(font_collection);
 
-            self.dwrite_font_faces.insert((*font_key).clone(), font_face);
-            Ok(())
+            let family_count = (**font_collection).GetFontFamilyCount();
+            let mut family_names = Vec::with_capacity(family_count as usize);
+            for family_index in 0..family_count {
+                let mut font_family = ptr::null_mut();
+                let result = (**font_collection).GetFontFamily(family_index, &mut font_family);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+                let font_family = PathfinderComPtr::new(font_family);
+
+                let mut names = ptr::null_mut();
+                let result = (**font_family).GetFamilyNames(&mut names);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+                let names = PathfinderComPtr::new(names);
+
+                family_names.push(try!(localized_string(&names, locale)));
+            }
+            Ok(family_names)
         }
     }
-    /// Loads a font from system font collection.
-    /// 
-    /// `font_key` is a handle that is used to refer to the font later. If this context has already
-    /// loaded a font with the same font key, nothing is done, and `Ok` is returned.
+
+    /// Returns the family name of the installed font that the system suggests for the start of
+    /// the given text, for use when the primary font lacks glyphs for it.
     /// 
-    /// `name` is the family name of the font.
+    /// The suggested family can be loaded with `add_system_font`. It covers at least the first
+    /// character of the text, but not necessarily all of it, so split runs of mixed scripts
+    /// before asking. If no installed font covers the text, `Ok(None)` is returned. Font fallback
+    /// requires Windows 8.1 or later; on older systems this fails with `FontError::Unsupported`.
     /// 
-    /// `font_index` is the index of the font within the collection, if `bytes` refers to a
-    /// collection (`.ttc`).
-    pub fn add_system_font(&mut self, font_key: &FK, name: &str, _: u32) -> Result<(), ()> {
+    /// `locale` is the locale name of the text, such as `ja-JP`. It guides the choice between
+    /// fonts for scripts shared between languages, such as Han ideographs, and the family name is
+    /// returned in that locale as in `system_font_families`.
+    pub fn fallback_font_for(&self, text: &str, locale: &str)
+                             -> Result<Option<String>, FontError> {
+        let text: Vec<u16> = text.encode_utf16().collect();
+        if text.is_empty() {
+            return Ok(None)
+        }
+
         unsafe {
+            let mut factory_2: *mut IDWriteFactory2 = ptr::null_mut();
+            let result = (**self.dwrite_factory).QueryInterface(&IID_IDWriteFactory2,
+                                                                &mut factory_2 as *mut *mut _ as
+                                                                *mut *mut c_void);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::Unsupported)
+            }
+            let factory_2 = PathfinderComPtr::new(factory_2);
+
+            let mut font_fallback = ptr::null_mut();
+            let result = (**factory_2).GetSystemFontFallback(&mut font_fallback);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let font_fallback: PathfinderComPtr<IDWriteFontFallback> =
+                PathfinderComPtr::new(font_fallback);
+
             let mut font_collection = ptr::null_mut();
-            let result = (**self.dwrite_factory).GetSystemFontCollection(
-                &mut font_collection, FALSE);
+            let result = (**self.dwrite_factory).GetSystemFontCollection(&mut font_collection,
+                                                                         FALSE);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::QueryFailed(result))
             }
             let font_collection = PathfinderComPtr::new(font_collection);
 
-            let mut font_family_index = 0;
-            let mut exists = 0;
-            let family_name = WideCString::from_str(name).unwrap();
-            let result = (**font_collection).FindFamilyName(
-                family_name.as_ptr(), &mut font_family_index, &mut exists);
-            if !winerror::SUCCEEDED(result) {
-                return Err(())
-            }
-            if exists == FALSE {
-                return Err(())
-            }
-            
-            let mut font_family = ptr::null_mut();
-            let result = (**font_collection).GetFontFamily(
-                font_family_index, &mut font_family);
-            if !winerror::SUCCEEDED(result) {
-                return Err(())
-            }
-            let font_family = PathfinderComPtr::new(font_family);
+            let text_length = text.len() as UINT32;
+            let analysis_source = PathfinderTextAnalysisSource::new(text, locale);
+            let (mut mapped_length, mut mapped_font, mut scale) = (0, ptr::null_mut(), 0.0);
+            let result = (**font_fallback).MapCharacters(
+                *analysis_source as *mut IDWriteTextAnalysisSource,
+                0,
+                text_length,
+                *font_collection,
+                ptr::null(),
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                &mut mapped_length,
+                &mut mapped_font,
+                &mut scale);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            if mapped_font.is_null() {
+                return Ok(None)
+            }
+            let mapped_font = PathfinderComPtr::new(mapped_font);
+
+            let mut font_family = ptr::null_mut();
+            let result = (**mapped_font).GetFontFamily(&mut font_family);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let font_family = PathfinderComPtr::new(font_family);
+
+            let mut names = ptr::null_mut();
+            let result = (**font_family).GetFamilyNames(&mut names);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let names = PathfinderComPtr::new(names);
+
+            localized_string(&names, locale).map(Some)
+        }
+    }
+
+    /// Checks that the given font is usable for rendering text.
+    /// 
+    /// DirectWrite accepts some malformed fonts and only fails once a glyph is requested. Call
+    /// this after loading a font to reject those up front: it returns `FontError::NoGlyphs`,
+    /// `FontError::NoCharacterMap`, or `FontError::InvalidUnitsPerEm` if the font is missing the
+    /// corresponding data.
+    pub fn validate_font(&self, font_key: &FK) -> Result<(), FontError> {
+        let (font_face, font_metrics) = try!(self.font_face_and_metrics(font_key));
+        unsafe {
+            if (**font_face).GetGlyphCount() == 0 {
+                return Err(FontError::NoGlyphs)
+            }
+            if font_metrics.designUnitsPerEm == 0 {
+                return Err(FontError::InvalidUnitsPerEm)
+            }
+            match try!(FontTable::new(&font_face, tables::CMAP)) {
+                Some(ref cmap_table) if tables::has_unicode_subtable(cmap_table.bytes()) => Ok(()),
+                _ => Err(FontError::NoCharacterMap),
+            }
+        }
+    }
+
+    /// Returns true if a font is loaded with the given font key.
+    #[inline]
+    pub fn is_loaded(&self, font_key: &FK) -> bool {
+        self.dwrite_font_faces.contains_key(font_key)
+    }
+
+    /// Returns an iterator over the keys of all the fonts loaded into this context, in order.
+    /// 
+    /// This is useful for reconciling a cache of fonts kept by the caller with the fonts that are
+    /// actually resident.
+    #[inline]
+    pub fn loaded_fonts(&self) -> LoadedFonts<FK> {
+        LoadedFonts {
+            font_keys: self.dwrite_font_faces.keys(),
+        }
+    }
+
+    /// Returns the number of fonts loaded into this context.
+    #[inline]
+    pub fn loaded_count(&self) -> usize {
+        self.dwrite_font_faces.len()
+    }
+
+    /// Unloads the font with the given font key from memory.
+    /// 
+    /// Returns true if the font was loaded. If it wasn't, does nothing and returns false.
+    pub fn delete_font(&mut self, font_key: &FK) -> bool {
+        if self.dwrite_font_faces.remove(font_key).is_none() {
+            return false
+        }
+        self.replace_font_file_key((*font_key).clone(), None);
+
+        let mut variable_font_faces = self.dwrite_variable_font_faces.borrow_mut();
+        let stale_keys: Vec<_> = variable_font_faces.keys()
+                                                    .filter(|&&(ref key, _)| key == font_key)
+                                                    .cloned()
+                                                    .collect();
+        for stale_key in stale_keys {
+            variable_font_faces.remove(&stale_key);
+        }
+
+        let stale_keys: Vec<_> = self.outline_cache
+                                     .keys()
+                                     .filter(|font_instance| font_instance.font_key == *font_key)
+                                     .cloned()
+                                     .collect();
+        for stale_key in stale_keys {
+            self.outline_cache.remove(&stale_key);
+        }
+
+        self.cmap_cache.borrow_mut().remove(font_key);
+
+        true
+    }
+
+    /// Returns the number of glyphs in the given font.
+    /// 
+    /// Valid glyph indices range from 0 up to, but not including, this number.
+    pub fn glyph_count(&self, font_key: &FK) -> Result<u16, FontError> {
+        let font_face = try!(self.font_face(font_key));
+        unsafe {
+            Ok((**font_face).GetGlyphCount())
+        }
+    }
+
+    /// Returns true if every glyph in the given font has the same advance.
+    pub fn is_monospace(&self, font_key: &FK) -> Result<bool, FontError> {
+        unsafe {
+            let font_face = try!(self.font_face(font_key));
+
+            let mut font_face_1: *mut IDWriteFontFace1 = ptr::null_mut();
+            let result = (**font_face).QueryInterface(&IID_IDWriteFontFace1,
+                                                      &mut font_face_1 as *mut *mut _ as
+                                                      *mut *mut c_void);
+            if winerror::SUCCEEDED(result) {
+                let font_face_1 = PathfinderComPtr::new(font_face_1);
+                return Ok((**font_face_1).IsMonospacedFont() != FALSE)
+            }
+
+            // Older versions of DirectWrite can't answer this directly, so compare the advances
+            // of the printable ASCII characters instead. Characters that the font doesn't cover
+            // and zero-width glyphs are ignored.
+            let characters: Vec<UINT32> = (0x21..0x7f).collect();
+            let mut glyph_indices: Vec<UINT16> = vec![0; characters.len()];
+            let result = (**font_face).GetGlyphIndices(characters.as_ptr(),
+                                                       characters.len() as UINT32,
+                                                       glyph_indices.as_mut_ptr());
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            glyph_indices.retain(|&glyph_index| glyph_index != 0);
+            if glyph_indices.is_empty() {
+                return Ok(false)
+            }
+
+            let mut metrics: Vec<DWRITE_GLYPH_METRICS> = vec![mem::zeroed(); glyph_indices.len()];
+            let result = (**font_face).GetDesignGlyphMetrics(glyph_indices.as_ptr(),
+                                                             glyph_indices.len() as UINT32,
+                                                             metrics.as_mut_ptr(),
+                                                             FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            let mut advances = metrics.iter()
+                                      .map(|metrics| metrics.advanceWidth)
+                                      .filter(|&advance| advance != 0);
+            match advances.next() {
+                None => Ok(false),
+                Some(first_advance) => Ok(advances.all(|advance| advance == first_advance)),
+            }
+        }
+    }
+
+    /// Returns true if the given font maps the given Unicode code point to a glyph.
+    /// 
+    /// Unlike `load_glyph_indices_for_characters`, this distinguishes a missing character from
+    /// one that maps to glyph 0.
+    pub fn has_glyph(&self, font_key: &FK, codepoint: u32) -> Result<bool, FontError> {
+        let loaded_font_face = try!(self.loaded_font_face(font_key));
+        unsafe {
+            match loaded_font_face.font {
+                Some(ref font) => {
+                    let mut exists = FALSE;
+                    let result = (**font).HasCharacter(codepoint, &mut exists);
+                    if !winerror::SUCCEEDED(result) {
+                        return Err(FontError::QueryFailed(result))
+                    }
+                    Ok(exists != FALSE)
+                }
+                None => {
+                    // Without a font, consult the face's character map directly. Glyph 0 is
+                    // `.notdef`, which DirectWrite returns for unmapped characters.
+                    let font_face = &loaded_font_face.font_face;
+                    let mut glyph_index = 0;
+                    let result = (**font_face).GetGlyphIndices(&codepoint, 1, &mut glyph_index);
+                    if !winerror::SUCCEEDED(result) {
+                        return Err(FontError::QueryFailed(result))
+                    }
+                    Ok(glyph_index != 0)
+                }
+            }
+        }
+    }
+
+    /// Returns every Unicode code point that the given font maps to a glyph, in ascending order.
+    /// 
+    /// This requires `IDWriteFontFace1` (Windows 8 or the Platform Update for Windows 7);
+    /// `FontError::Unsupported` is returned on older versions of DirectWrite.
+    pub fn glyph_coverage(&self, font_key: &FK) -> Result<Vec<u32>, FontError> {
+        unsafe {
+            let font_face = try!(self.font_face(font_key));
+
+            let mut font_face_1: *mut IDWriteFontFace1 = ptr::null_mut();
+            let result = (**font_face).QueryInterface(&IID_IDWriteFontFace1,
+                                                      &mut font_face_1 as *mut *mut _ as
+                                                      *mut *mut c_void);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::Unsupported)
+            }
+            let font_face_1 = PathfinderComPtr::new(font_face_1);
+
+            // Ask for the number of ranges first. This call fails with
+            // `E_NOT_SUFFICIENT_BUFFER` unless the font covers nothing.
+            let mut range_count = 0;
+            (**font_face_1).GetUnicodeRanges(0, ptr::null_mut(), &mut range_count);
+            if range_count == 0 {
+                return Ok(vec![])
+            }
+
+            let mut ranges = vec![DWRITE_UNICODE_RANGE { first: 0, last: 0 };
+                                  range_count as usize];
+            let result = (**font_face_1).GetUnicodeRanges(range_count,
+                                                          ranges.as_mut_ptr(),
+                                                          &mut range_count);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            ranges.truncate(range_count as usize);
+
+            // DirectWrite doesn't document the order of the ranges, so sort the result.
+            let mut codepoints = vec![];
+            for range in &ranges {
+                codepoints.extend(range.first..(range.last + 1))
+            }
+            codepoints.sort();
+            codepoints.dedup();
+            Ok(codepoints)
+        }
+    }
+
+    /// Returns the dimensions of the given glyph in the given font.
+    /// 
+    /// If `exact` is true, then the raw outline extents as specified by the font designer are
+    /// returned. These may differ from the extents when rendered on screen, because some font
+    /// libraries (including Pathfinder) apply modifications to the outlines: for example, to
+    /// dilate them for easier reading. To retrieve extents that account for these modifications,
+    /// such as the dilation set with `set_outline_dilation`, set `exact` to false.
+    /// 
+    /// The bounding box is in the same Y-up space as the outlines that `glyph_outline` returns, so
+    /// the origin is the lower left corner of the box that contains the outline. This holds even
+    /// if Y flipping has been turned off with `set_y_flip`, in which case the outline lies between
+    /// `-(origin.y + size.height)` and `-origin.y` vertically.
+    pub fn glyph_dimensions(&self,
+                            font_instance: &FontInstance<FK>,
+                            glyph_key: &GlyphKey,
+                            exact: bool)
+                            -> Result<GlyphDimensions, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index, 1, &mut metrics, FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            glyph_dimensions_from_metrics(&font_face,
+                                          &self.geometry_sink,
+                                          &font_metrics,
+                                          &metrics,
+                                          font_instance.size.to_f32_px(),
+                                          glyph_key,
+                                          exact,
+                                          self.outline_dilation)
+        }
+    }
+
+    /// Returns the dimensions of the given glyph both in font design units and in pixels.
+    /// 
+    /// The pixel dimensions are the same as those that `glyph_dimensions` returns with the same
+    /// value of `exact`, and they're computed from the same design metrics, which are only
+    /// fetched from DirectWrite once. For variable fonts, the design metrics are those of the
+    /// instance's variations.
+    pub fn glyph_extents(&self,
+                         font_instance: &FontInstance<FK>,
+                         glyph_key: &GlyphKey,
+                         exact: bool)
+                         -> Result<GlyphExtents, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index, 1, &mut metrics, FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            let dimensions = try!(glyph_dimensions_from_metrics(&font_face,
+                                                                &self.geometry_sink,
+                                                                &font_metrics,
+                                                                &metrics,
+                                                                font_instance.size.to_f32_px(),
+                                                                glyph_key,
+                                                                exact,
+                                                                self.outline_dilation));
+            Ok(GlyphExtents {
+                design_metrics: GlyphDesignMetrics {
+                    left_side_bearing: metrics.leftSideBearing,
+                    advance_width: metrics.advanceWidth,
+                    right_side_bearing: metrics.rightSideBearing,
+                    top_side_bearing: metrics.topSideBearing,
+                    advance_height: metrics.advanceHeight,
+                    bottom_side_bearing: metrics.bottomSideBearing,
+                    vertical_origin_y: metrics.verticalOriginY,
+                    units_per_em: font_metrics.designUnitsPerEm,
+                },
+                dimensions: dimensions,
+            })
+        }
+    }
+
+    /// Returns the outline of the given glyph along with its dimensions, fetching the glyph
+    /// metrics only once.
+    /// 
+    /// This is equivalent to calling `glyph_outline` and then `glyph_dimensions` with `exact` set
+    /// to false, but the dimensions are measured from the returned outline instead of extracting
+    /// it a second time. Like `glyph_outline`, this uses and fills the outline cache.
+    pub fn glyph_outline_and_metrics(&mut self,
+                                     font_instance: &FontInstance<FK>,
+                                     glyph_key: &GlyphKey)
+                                     -> Result<(GlyphOutline, GlyphDimensions), FontError> {
+        let outline = try!(self.glyph_outline(font_instance, glyph_key));
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index, 1, &mut metrics, FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            let advance = metrics.advanceWidth as f32 * font_instance.size.to_f32_px() /
+                font_metrics.designUnitsPerEm as f32;
+            let dimensions = outline_dimensions(&outline, glyph_key, advance, self.y_flip);
+            Ok((outline, dimensions))
+        }
+    }
+
+    /// Returns the dimensions of the given glyph as the native hinted rasterizer lays it out.
+    /// 
+    /// Unlike `glyph_dimensions`, which returns design metrics, these are rounded to the pixel
+    /// grid the same way GDI rounds them, so they match what `rasterize_glyph` produces at small
+    /// sizes. `ppem` is the number of device pixels per em that the glyph is hinted for; passing
+    /// the size of the font instance in pixels corresponds to one pixel per DIP. `ppem` must be
+    /// positive and finite; otherwise, this returns `FontError::InvalidArgument`.
+    pub fn gdi_glyph_dimensions(&self,
+                                font_instance: &FontInstance<FK>,
+                                glyph_key: &GlyphKey,
+                                ppem: f32)
+                                -> Result<GlyphDimensions, FontError> {
+        if !(ppem > 0.0 && ppem.is_finite()) {
+            return Err(FontError::InvalidArgument)
+        }
+
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let em_size = font_instance.size.to_f32_px();
+            let pixels_per_dip = if em_size > 0.0 { ppem / em_size } else { 1.0 };
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetGdiCompatibleGlyphMetrics(em_size,
+                                                                    pixels_per_dip,
+                                                                    ptr::null(),
+                                                                    FALSE,
+                                                                    &glyph_index,
+                                                                    1,
+                                                                    &mut metrics,
+                                                                    FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            // The hinted metrics are still expressed in design units, so they can be measured
+            // just like the design metrics.
+            glyph_dimensions_from_metrics(&font_face,
+                                          &self.geometry_sink,
+                                          &font_metrics,
+                                          &metrics,
+                                          em_size,
+                                          glyph_key,
+                                          true,
+                                          0.0)
+        }
+    }
+
+    /// Returns the dimensions of each of the given glyphs in the given font.
+    /// 
+    /// This is equivalent to calling `glyph_dimensions` for each glyph, but it fetches the design
+    /// metrics of all the glyphs at once. Glyphs that don't exist in the font, or whose outlines
+    /// couldn't be measured when `exact` is false, are `None`.
+    pub fn glyph_dimensions_batch(&self,
+                                  font_instance: &FontInstance<FK>,
+                                  glyph_keys: &[GlyphKey],
+                                  exact: bool)
+                                  -> Result<Vec<Option<GlyphDimensions>>, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            // DirectWrite rejects the whole call if any index is out of range, so only ask about
+            // the glyphs that exist.
+            let glyph_count = (**font_face).GetGlyphCount() as u32;
+            let glyph_indices: Vec<UINT16> = glyph_keys.iter()
+                                                       .filter(|glyph_key| {
+                                                           glyph_key.glyph_index < glyph_count
+                                                       })
+                                                       .map(|glyph_key| {
+                                                           glyph_key.glyph_index as UINT16
+                                                       })
+                                                       .collect();
+            let mut metrics: Vec<DWRITE_GLYPH_METRICS> = vec![mem::zeroed(); glyph_indices.len()];
+            if !glyph_indices.is_empty() {
+                let result = (**font_face).GetDesignGlyphMetrics(glyph_indices.as_ptr(),
+                                                                 glyph_indices.len() as UINT32,
+                                                                 metrics.as_mut_ptr(),
+                                                                 FALSE);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+            }
+
+            let mut metrics = metrics.iter();
+            Ok(glyph_keys.iter().map(|glyph_key| {
+                if glyph_key.glyph_index >= glyph_count {
+                    return None
+                }
+                let metrics = metrics.next().unwrap();
+                glyph_dimensions_from_metrics(&font_face,
+                                              &self.geometry_sink,
+                                              &font_metrics,
+                                              metrics,
+                                              font_instance.size.to_f32_px(),
+                                              glyph_key,
+                                              exact,
+                                              self.outline_dilation).ok()
+            }).collect())
+        }
+    }
+
+    /// Returns the advance of each of the given glyphs in the given font, in pixels.
+    /// 
+    /// This is cheaper than `glyph_dimensions_batch` when only advances are needed. If any of the
+    /// glyphs doesn't exist in the font, `FontError::GlyphNotFound` is returned.
+    /// 
+    /// For an instance of a variable font, the advances are those at the instance's variations,
+    /// as interpolated from the font's `HVAR` table (or its outlines, if it has none).
+    pub fn glyph_advances(&self, font_instance: &FontInstance<FK>, glyph_keys: &[GlyphKey])
+                          -> Result<Vec<f32>, FontError> {
+        if glyph_keys.is_empty() {
+            return Ok(vec![])
+        }
+
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let glyph_count = (**font_face).GetGlyphCount() as u32;
+            if glyph_keys.iter().any(|glyph_key| glyph_key.glyph_index >= glyph_count) {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            let glyph_indices: Vec<UINT16> = glyph_keys.iter().map(|glyph_key| {
+                glyph_key.glyph_index as UINT16
+            }).collect();
+            let mut metrics: Vec<DWRITE_GLYPH_METRICS> = vec![mem::zeroed(); glyph_indices.len()];
+            let result = (**font_face).GetDesignGlyphMetrics(glyph_indices.as_ptr(),
+                                                             glyph_indices.len() as UINT32,
+                                                             metrics.as_mut_ptr(),
+                                                             FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            let scale = font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
+            Ok(metrics.iter().map(|metrics| metrics.advanceWidth as f32 * scale).collect())
+        }
+    }
+
+    /// Returns the tight bounding rectangle of the ink of the given glyph, in pixels, with Y
+    /// pointing up and the origin at the pen position.
+    /// 
+    /// The rectangle is measured from the extrema of the glyph outline, so unlike
+    /// `glyph_dimensions` it isn't rounded out to pixel boundaries. Glyphs with no ink, such as
+    /// spaces, return `None`.
+    pub fn glyph_ink_bounds(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                            -> Result<Option<Rect<f32>>, FontError> {
+        unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+            if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            let outline = try!(extract_glyph_outline(&font_face,
+                                                     &self.geometry_sink,
+                                                     font_instance.size.to_f32_px(),
+                                                     glyph_key.glyph_index as UINT16,
+                                                     false,
+                                                     false,
+                                                     true));
+            if outline.is_empty() {
+                return Ok(None)
+            }
+            Ok(Some(outline.bounds()))
+        }
+    }
+
+    /// Returns the metrics of the given glyph in the given font when it's set in vertical text,
+    /// in pixels.
+    pub fn vertical_glyph_dimensions(&self,
+                                     font_instance: &FontInstance<FK>,
+                                     glyph_key: &GlyphKey)
+                                     -> Result<VerticalGlyphDimensions, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            // Sideways metrics only differ when DirectWrite is simulating an oblique face, but
+            // ask for them anyway so that the slant matches `glyph_outline_with_orientation`.
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index, 1, &mut metrics, TRUE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            // Fonts without vertical metrics get a vertical origin centered horizontally over
+            // the glyph, which is the same convention that DirectWrite uses when it lays out
+            // vertical text.
+            let scale = font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
+            Ok(VerticalGlyphDimensions {
+                origin: Point2D::new(metrics.advanceWidth as f32 * 0.5 * scale,
+                                     metrics.verticalOriginY as f32 * scale),
+                advance: metrics.advanceHeight as f32 * scale,
+                top_side_bearing: metrics.topSideBearing as f32 * scale,
+                bottom_side_bearing: metrics.bottomSideBearing as f32 * scale,
+            })
+        }
+    }
+
+    /// Returns a list of path commands that represent the given glyph in the given font.
+    /// 
+    /// Outlines are cached, and the returned outline shares its path commands with the cache, so
+    /// repeated calls for the same glyph are cheap. Use `clear_outline_cache` to free them.
+    /// 
+    /// If the glyph index is out of range for the font, `FontError::GlyphNotFound` is returned.
+    /// Glyphs that exist but have no contours, such as spaces, have empty outlines.
+    #[inline]
+    pub fn glyph_outline(&mut self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                         -> Result<GlyphOutline, FontError> {
+        self.glyph_outline_with_orientation(font_instance, glyph_key, false, false)
+    }
+
+    /// Returns a list of path commands that represent the given glyph in the given font, set
+    /// either upright or sideways and in either direction.
+    /// 
+    /// If `sideways` is true, the glyph is rotated 90° counterclockwise and positioned using its
+    /// vertical metrics. To lay out vertical text, extract each glyph sideways and then rotate
+    /// the whole run 90° clockwise.
+    /// 
+    /// If `is_right_to_left` is true, the glyph belongs to a right-to-left run, so the pen
+    /// advances leftward and the glyph lies to the left of its origin instead of to the right.
+    pub fn glyph_outline_with_orientation(&mut self,
+                                          font_instance: &FontInstance<FK>,
+                                          glyph_key: &GlyphKey,
+                                          sideways: bool,
+                                          is_right_to_left: bool)
+                                          -> Result<GlyphOutline, FontError> {
+        let cache_key = (glyph_key.glyph_index, sideways, is_right_to_left);
+        if let Some(outline) = self.outline_cache
+                                   .get(font_instance)
+                                   .and_then(|outlines| outlines.get(&cache_key)) {
+            return Ok((*outline).clone())
+        }
+
+        let outline = unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+
+            // DirectWrite happily produces an empty outline for a glyph that doesn't exist, which
+            // would be indistinguishable from a glyph with no contours, such as a space.
+            if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            try!(extract_glyph_outline(&font_face,
+                                       &self.geometry_sink,
+                                       font_instance.size.to_f32_px(),
+                                       glyph_index,
+                                       sideways,
+                                       is_right_to_left,
+                                       self.y_flip))
+        };
+        let outline = dilate_outline(outline,
+                                     self.outline_dilation * font_instance.size.to_f32_px());
+
+        if !self.outline_cache.contains_key(font_instance) {
+            self.outline_cache.insert((*font_instance).clone(), BTreeMap::new());
+        }
+        self.outline_cache.get_mut(font_instance).unwrap().insert(cache_key, outline.clone());
+        Ok(outline)
+    }
+
+    /// Returns the outline of the given glyph in font design units, independent of any font size.
+    /// 
+    /// The outline is extracted at an em size equal to the units per em of the font, so its
+    /// coordinates are the raw design coordinates. It's extracted from the default instance of
+    /// variable fonts, and, like `glyph_outline`, it's Y-up unless flipping has been turned off
+    /// with `set_y_flip`. Design outlines aren't cached.
+    pub fn glyph_outline_design(&self, font_key: &FK, glyph_index: u16)
+                                -> Result<GlyphOutline, FontError> {
+        let (font_face, font_metrics) = try!(self.font_face_and_metrics(font_key));
+        unsafe {
+            if glyph_index >= (**font_face).GetGlyphCount() {
+                return Err(FontError::GlyphNotFound)
+            }
+            extract_glyph_outline(&font_face,
+                                  &self.geometry_sink,
+                                  font_metrics.designUnitsPerEm as f32,
+                                  glyph_index,
+                                  false,
+                                  false,
+                                  self.y_flip)
+        }
+    }
+
+    /// Returns the outline of the given glyph for a display with the given number of physical
+    /// pixels per device-independent pixel (DIP), such as 2.0 for a 192 DPI display.
+    ///
+    /// `glyph_outline` treats the size of the font instance as physical pixels, which matches a
+    /// 96 DPI display at 100% scaling. This instead treats it as DIPs and extracts the outline at
+    /// the effective em size of `size * pixels_per_dip`, so the returned coordinates are in
+    /// physical pixels. Dilation set with `set_outline_dilation` scales along with it. Outlines at
+    /// a scale other than 1.0 aren't cached. A scale that isn't positive and finite returns
+    /// `FontError::InvalidArgument`.
+    pub fn glyph_outline_at_pixels_per_dip(&mut self,
+                                           font_instance: &FontInstance<FK>,
+                                           glyph_key: &GlyphKey,
+                                           pixels_per_dip: f32)
+                                           -> Result<GlyphOutline, FontError> {
+        if pixels_per_dip == 1.0 {
+            return self.glyph_outline(font_instance, glyph_key)
+        }
+        if !(pixels_per_dip > 0.0 && pixels_per_dip.is_finite()) {
+            return Err(FontError::InvalidArgument)
+        }
+
+        let em_size = font_instance.size.to_f32_px() * pixels_per_dip;
+        let outline = unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+            if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            try!(extract_glyph_outline(&font_face,
+                                       &self.geometry_sink,
+                                       em_size,
+                                       glyph_key.glyph_index as UINT16,
+                                       false,
+                                       false,
+                                       self.y_flip))
+        };
+        Ok(dilate_outline(outline, self.outline_dilation * em_size))
+    }
+
+    /// Returns true if the given glyph has at least one contour.
+    /// 
+    /// Glyphs in bitmap-only fonts, such as some emoji fonts, have no contours, so this can be used
+    /// to decide between `glyph_outline` and `glyph_bitmap`. Glyphs that are blank by design, such
+    /// as spaces, have no contours either.
+    pub fn glyph_has_outline(&self, font_key: &FK, glyph_index: u16) -> Result<bool, FontError> {
+        let outline = try!(self.glyph_outline_design(font_key, glyph_index));
+        Ok(outline.contours().next().is_some())
+    }
+
+    /// Returns the outline of the given glyph with its origin moved by a fractional pixel offset,
+    /// for subpixel text positioning.
+    /// 
+    /// `subpixel_offset` is in pixels, with X pointing right and Y pointing up. The offset is
+    /// applied to the glyph origin, as `rasterize_glyph` does with the subpixel offset of the
+    /// glyph key, so the outline lines up with the rasterized glyph. The subpixel offset in
+    /// `glyph_key` itself is ignored. Dilation set with `set_outline_dilation` is applied, so at
+    /// an offset of (0, 0) this returns the same outline as `glyph_outline`. Offset outlines
+    /// aren't cached.
+    pub fn glyph_outline_at_subpixel_offset(&self,
+                                            font_instance: &FontInstance<FK>,
+                                            glyph_key: &GlyphKey,
+                                            subpixel_offset: (f32, f32))
+                                            -> Result<GlyphOutline, FontError> {
+        if glyph_key.glyph_index > u16::MAX as u32 {
+            return Err(FontError::GlyphNotFound)
+        }
+        let outline = try!(self.glyph_run_outline(font_instance,
+                                                  &[glyph_key.glyph_index as u16],
+                                                  &[0.0],
+                                                  &[subpixel_offset]));
+        Ok(dilate_outline(outline, self.outline_dilation * font_instance.size.to_f32_px()))
+    }
+
+    /// Returns the outlines of many glyphs in the given font at once, extracting them in parallel
+    /// on the Rayon thread pool. This requires the `parallel` feature.
+    /// 
+    /// The result for each glyph key is in the same position as the key, and is the same as what
+    /// `glyph_outline` would return. The outline cache is neither consulted nor filled.
+    #[cfg(feature = "parallel")]
+    pub fn glyph_outlines_parallel(&self, font_instance: &FontInstance<FK>, keys: &[GlyphKey])
+                                   -> Vec<Result<GlyphOutline, FontError>> {
+        let font_face = match self.instance_font_face(font_instance) {
+            Ok(font_face) => SharedFontFace(font_face),
+            Err(error) => return keys.iter().map(|_| Err(error)).collect(),
+        };
+        let font_size = font_instance.size.to_f32_px();
+        let (y_flip, dilation) = (self.y_flip, self.outline_dilation * font_size);
+
+        keys.par_iter().map(|glyph_key| {
+            unsafe {
+                let font_face = font_face.0.clone();
+                if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                    return Err(FontError::GlyphNotFound)
+                }
+                let geometry_sink = PathfinderGeometrySink::new(y_flip);
+                let outline = try!(extract_glyph_outline(&font_face,
+                                                         &geometry_sink,
+                                                         font_size,
+                                                         glyph_key.glyph_index as UINT16,
+                                                         false,
+                                                         false,
+                                                         y_flip));
+                Ok(dilate_outline(outline, dilation))
+            }
+        }).collect()
+    }
+
+    /// Returns the outline of the given glyph with every point passed through the given
+    /// transform.
+    /// 
+    /// This is useful for synthesizing styles that the font lacks: for example, a skew of about
+    /// 12° produces a faux italic. The transform applies in the coordinate space of the outline,
+    /// so it sees Y-up coordinates unless flipping has been turned off with `set_y_flip`. Any
+    /// dilation set with `set_outline_dilation` is applied before the transform. Transformed
+    /// outlines aren't cached.
+    pub fn glyph_outline_transformed(&self,
+                                     font_instance: &FontInstance<FK>,
+                                     glyph_key: &GlyphKey,
+                                     transform: Transform2D<f32>)
+                                     -> Result<GlyphOutline, FontError> {
+        let outline = unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+            if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            try!(extract_glyph_outline(&font_face,
+                                       &self.geometry_sink,
+                                       font_instance.size.to_f32_px(),
+                                       glyph_key.glyph_index as UINT16,
+                                       false,
+                                       false,
+                                       self.y_flip))
+        };
+        let outline = dilate_outline(outline,
+                                     self.outline_dilation * font_instance.size.to_f32_px());
+
+        let events = outline.iter().map(|event| {
+            match event {
+                PathEvent::MoveTo(to) => PathEvent::MoveTo(transform.transform_point(&to)),
+                PathEvent::LineTo(to) => PathEvent::LineTo(transform.transform_point(&to)),
+                PathEvent::QuadraticTo(ctrl, to) => {
+                    PathEvent::QuadraticTo(transform.transform_point(&ctrl),
+                                           transform.transform_point(&to))
+                }
+                PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                    PathEvent::CubicTo(transform.transform_point(&ctrl1),
+                                       transform.transform_point(&ctrl2),
+                                       transform.transform_point(&to))
+                }
+                // DirectWrite never produces arcs.
+                event @ PathEvent::Arc(..) | event @ PathEvent::Close => event,
+            }
+        }).collect();
+
+        Ok(GlyphOutline {
+            events: Arc::new(events),
+            fill_mode: outline.fill_mode,
+            error: outline.error,
+        })
+    }
+
+    /// Returns the outline of the given glyph made heavier by pushing each contour outward by
+    /// `strength` ems, to synthesize a bold face for fonts that lack one.
+    ///
+    /// Outer contours grow and counters shrink, so stems get thicker by twice `strength`. About
+    /// 0.02 ems is comparable to the difference between a regular and a bold weight. This applies
+    /// on top of any dilation set with `set_outline_dilation`. The advance of the glyph isn't
+    /// changed, so callers should widen it to match if they want the spacing to stay even.
+    /// Emboldened outlines aren't cached.
+    pub fn glyph_outline_emboldened(&self,
+                                    font_instance: &FontInstance<FK>,
+                                    glyph_key: &GlyphKey,
+                                    strength: f32)
+                                    -> Result<GlyphOutline, FontError> {
+        let font_size = font_instance.size.to_f32_px();
+        let outline = unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+            if glyph_key.glyph_index >= (**font_face).GetGlyphCount() as u32 {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            try!(extract_glyph_outline(&font_face,
+                                       &self.geometry_sink,
+                                       font_size,
+                                       glyph_key.glyph_index as UINT16,
+                                       false,
+                                       false,
+                                       self.y_flip))
+        };
+        Ok(dilate_outline(outline, (self.outline_dilation + strength) * font_size))
+    }
+
+    /// Returns the combined outline of a run of positioned glyphs, such as the output of `shape`.
+    /// 
+    /// `advances` are the distances, in pixels, to move the pen after each glyph. `offsets` are
+    /// the offsets of each glyph from the pen position, in pixels, as (advance, ascender) pairs:
+    /// that is, X points in the reading direction and Y points up. All three slices must have the
+    /// same length, or `FontError::InvalidArgument` is returned. The run starts at the origin. If
+    /// any glyph index is out of range, `FontError::GlyphNotFound` is returned.
+    /// 
+    /// Unlike `glyph_outline`, run outlines aren't cached.
+    pub fn glyph_run_outline(&self,
+                             font_instance: &FontInstance<FK>,
+                             glyph_indices: &[u16],
+                             advances: &[f32],
+                             offsets: &[(f32, f32)])
+                             -> Result<GlyphOutline, FontError> {
+        if advances.len() != glyph_indices.len() || offsets.len() != glyph_indices.len() {
+            return Err(FontError::InvalidArgument)
+        }
+
+        let glyph_offsets: Vec<_> = offsets.iter().map(|&(advance_offset, ascender_offset)| {
+            DWRITE_GLYPH_OFFSET {
+                advanceOffset: advance_offset,
+                ascenderOffset: ascender_offset,
+            }
+        }).collect();
+
+        unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+
+            let glyph_count = (**font_face).GetGlyphCount();
+            if glyph_indices.iter().any(|&glyph_index| glyph_index >= glyph_count) {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            extract_glyph_run_outline(&font_face,
+                                      &self.geometry_sink,
+                                      font_instance.size.to_f32_px(),
+                                      glyph_indices,
+                                      advances,
+                                      &glyph_offsets,
+                                      false,
+                                      false,
+                                      self.y_flip)
+        }
+    }
+
+    /// Sets whether `glyph_outline` flips outlines vertically.
+    /// 
+    /// By default, this is true, and outlines are Y-up, with the baseline at Y = 0 and ascenders
+    /// in positive Y. If this is false, outlines are left Y-down, as DirectWrite produces them.
+    /// This only affects outlines; glyph dimensions are always Y-up.
+    pub fn set_y_flip(&mut self, y_flip: bool) {
+        if self.y_flip != y_flip {
+            self.y_flip = y_flip;
+            self.outline_cache.clear()
+        }
+    }
+
+    /// Sets how far `glyph_outline` pushes the contours of glyphs outward, in ems.
+    /// 
+    /// Each point of each contour moves along the normal of the outline by this amount, so stems
+    /// get thicker by twice the amount and counters get smaller, which keeps thin text readable
+    /// at small sizes. Negative amounts make glyphs thinner instead. The default is 0, which
+    /// leaves outlines as the font designer drew them. This also applies to the dimensions that
+    /// `glyph_dimensions` returns when `exact` is false.
+    pub fn set_outline_dilation(&mut self, amount: f32) {
+        if self.outline_dilation != amount {
+            self.outline_dilation = amount;
+            self.outline_cache.clear()
+        }
+    }
+
+    /// Discards every cached glyph outline.
+    /// 
+    /// Outlines that have already been returned from `glyph_outline` remain valid.
+    #[inline]
+    pub fn clear_outline_cache(&mut self) {
+        self.outline_cache.clear()
+    }
+
+    /// Discards every cached character-to-glyph mapping.
+    /// 
+    /// `load_glyph_indices_for_characters` remembers the glyph for every character it has looked
+    /// up; call this to bound the memory that uses.
+    #[inline]
+    pub fn clear_cmap_cache(&self) {
+        self.cmap_cache.borrow_mut().clear()
+    }
+
+    /// Uses DirectWrite to rasterize a glyph on CPU with grayscale antialiasing.
+    /// 
+    /// This is equivalent to `rasterize_glyph` with `RasterizationMode::Grayscale` and
+    /// `RenderingMode::Recommended`. Because the
+    /// image is always cropped to the pixels that DirectWrite actually touched, `exact` has no
+    /// effect on this backend. The image is always `PixelFormat::A8`.
+    #[inline]
+    pub fn rasterize_glyph_with_native_rasterizer(&self,
+                                                  font_instance: &FontInstance<FK>,
+                                                  glyph_key: &GlyphKey,
+                                                  _: bool)
+                                                  -> Result<GlyphImage, FontError> {
+        self.rasterize_glyph(font_instance,
+                             glyph_key,
+                             RasterizationMode::Grayscale,
+                             RenderingMode::Recommended)
+    }
+
+    /// Uses DirectWrite to rasterize a glyph on CPU with the given kind of antialiasing.
+    /// 
+    /// The glyph is drawn onto a GDI-compatible bitmap render target and then cropped to the
+    /// black box that DirectWrite reports. The returned image has rows stored top to bottom. It's
+    /// `PixelFormat::A8` coverage in the aliased and grayscale modes and `PixelFormat::Rgb24`
+    /// coverage in the subpixel (ClearType) mode, with tightly packed rows. Its `origin` is the
+    /// offset from the pen position to the top left corner of the image, with Y pointing down;
+    /// the origin of its dimensions is the lower left corner, with Y pointing up, as
+    /// `glyph_dimensions` reports it.
+    /// 
+    /// `rendering_mode` selects how DirectWrite hints the outline, with
+    /// `RenderingMode::Recommended` leaving the choice to
+    /// `IDWriteFontFace::GetRecommendedRenderingMode`. It's ignored in the aliased mode, which is
+    /// a DirectWrite rendering mode of its own.
+    /// 
+    /// The coverage is gamma-encoded for display, as DirectWrite produces it. Use
+    /// `rasterize_glyph_with_gamma` to get linear coverage instead.
+    #[inline]
+    pub fn rasterize_glyph(&self,
+                           font_instance: &FontInstance<FK>,
+                           glyph_key: &GlyphKey,
+                           mode: RasterizationMode,
+                           rendering_mode: RenderingMode)
+                           -> Result<GlyphImage, FontError> {
+        self.rasterize_glyph_with_gamma(font_instance, glyph_key, mode, rendering_mode, 1.0)
+    }
+
+    /// Returns the key under which to cache the image that `rasterize_glyph` produces for the
+    /// given glyph.
+    /// 
+    /// The size is kept in app units, which is the precision that the rasterizer works at, so
+    /// instances whose sizes differ by less than that share a key. The key doesn't include the
+    /// gamma passed to `rasterize_glyph_with_gamma`; callers that use more than one gamma should
+    /// pair it with the key themselves. Aliased rasterization ignores the rendering mode, so
+    /// aliased keys always have a rendering mode of `RenderingMode::Recommended`.
+    pub fn glyph_cache_key(&self,
+                           font_instance: &FontInstance<FK>,
+                           glyph_key: &GlyphKey,
+                           mode: RasterizationMode,
+                           rendering_mode: RenderingMode)
+                           -> GlyphCacheKey<FK> {
+        let rendering_mode = match mode {
+            RasterizationMode::Aliased => RenderingMode::Recommended,
+            RasterizationMode::Grayscale | RasterizationMode::Subpixel => rendering_mode,
+        };
+        GlyphCacheKey {
+            font_key: font_instance.font_key.clone(),
+            variations: font_instance.variations.clone(),
+            glyph_index: glyph_key.glyph_index,
+            size: font_instance.size,
+            subpixel_offset: glyph_key.subpixel_offset,
+            mode: mode,
+            rendering_mode: rendering_mode,
+        }
+    }
+
+    /// Like `rasterize_glyph`, but decodes the coverage with the given gamma before returning it.
+    /// 
+    /// Each coverage value `c`, from 0.0 to 1.0, becomes `c^gamma`: the exponent is applied as
+    /// given, not inverted, so pass about 2.2 to linearize coverage that DirectWrite encoded for a
+    /// typical display, which is what a renderer that blends in linear space wants. A gamma of
+    /// 1.0 leaves the coverage untouched. The gamma must be positive and finite; otherwise, this
+    /// returns `FontError::InvalidArgument`.
+    pub fn rasterize_glyph_with_gamma(&self,
+                                      font_instance: &FontInstance<FK>,
+                                      glyph_key: &GlyphKey,
+                                      mode: RasterizationMode,
+                                      rendering_mode: RenderingMode,
+                                      gamma: f32)
+                                      -> Result<GlyphImage, FontError> {
+        if !(gamma > 0.0) || !gamma.is_finite() {
+            return Err(FontError::InvalidArgument)
+        }
+
+        let drawn_glyph = try!(self.draw_glyph(font_instance, glyph_key, mode, rendering_mode));
+
+        let format = rasterization_pixel_format(mode);
+        let size = drawn_glyph.dimensions.size;
+        let stride = size.width as usize * format.bytes_per_pixel();
+        let mut pixels = vec![0; stride * size.height as usize];
+        for (y, row) in pixels.chunks_mut(stride).enumerate() {
+            drawn_glyph.copy_row(y, mode, row)
+        }
+
+        if gamma != 1.0 {
+            let mut gamma_table = [0; 256];
+            for (value, entry) in gamma_table.iter_mut().enumerate() {
+                *entry = ((value as f32 / 255.0).powf(gamma) * 255.0).round() as u8
+            }
+            for pixel in &mut pixels {
+                *pixel = gamma_table[*pixel as usize]
+            }
+        }
+
+        // The dimensions have Y pointing up, so their origin is the lower left corner. Flip it
+        // to find the top left corner with Y pointing down.
+        let origin = drawn_glyph.dimensions.origin;
+        Ok(GlyphImage {
+            dimensions: drawn_glyph.dimensions,
+            origin: Point2D::new(origin.x, -(origin.y + size.height as i32)),
+            format: format,
+            stride: stride,
+            pixels: pixels,
+        })
+    }
+
+    /// Like `rasterize_glyph`, but writes the coverage straight into a buffer owned by the caller,
+    /// such as the backing store of a glyph atlas.
+    /// 
+    /// The image is written with its top left corner at `dest_origin`, in pixels, in the same
+    /// format that `rasterize_glyph` would return. `dest_stride` is the distance between rows of
+    /// `dest` in bytes. Pixels of `dest` outside the glyph's bounding box are left untouched. If
+    /// the image doesn't fit, this fails with `FontError::DestinationTooSmall` and leaves `dest`
+    /// unchanged. The returned dimensions describe the region that was written, in the same
+    /// Y-up space as `glyph_dimensions`, so their origin is the offset from the pen position to
+    /// the lower left corner of the image; the top left corner that lands at `dest_origin` is
+    /// `origin.y + size.height` above the baseline.
+    pub fn rasterize_glyph_into(&self,
+                                font_instance: &FontInstance<FK>,
+                                glyph_key: &GlyphKey,
+                                mode: RasterizationMode,
+                                rendering_mode: RenderingMode,
+                                dest: &mut [u8],
+                                dest_stride: usize,
+                                dest_origin: (u32, u32))
+                                -> Result<GlyphDimensions, FontError> {
+        let drawn_glyph = try!(self.draw_glyph(font_instance, glyph_key, mode, rendering_mode));
+
+        let size = drawn_glyph.dimensions.size;
+        if size.width == 0 || size.height == 0 {
+            return Ok(drawn_glyph.dimensions)
+        }
+
+        let bytes_per_pixel = rasterization_pixel_format(mode).bytes_per_pixel();
+        let row_start = dest_origin.0 as usize * bytes_per_pixel;
+        let row_end = row_start + size.width as usize * bytes_per_pixel;
+        let last_row = dest_origin.1 as usize + size.height as usize - 1;
+        if row_end > dest_stride || last_row * dest_stride + row_end > dest.len() {
+            return Err(FontError::DestinationTooSmall)
+        }
+
+        for y in 0..(size.height as usize) {
+            let dest_row_offset = (dest_origin.1 as usize + y) * dest_stride;
+            let dest_row = &mut dest[(dest_row_offset + row_start)..(dest_row_offset + row_end)];
+            drawn_glyph.copy_row(y, mode, dest_row)
+        }
+
+        Ok(drawn_glyph.dimensions)
+    }
+
+    // Draws a glyph onto a bitmap render target, for `rasterize_glyph_with_gamma` and
+    // `rasterize_glyph_into` to copy out of.
+    fn draw_glyph(&self,
+                  font_instance: &FontInstance<FK>,
+                  glyph_key: &GlyphKey,
+                  mode: RasterizationMode,
+                  rendering_mode: RenderingMode)
+                  -> Result<DrawnGlyph, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let mut glyph_metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index,
+                                                             1,
+                                                             &mut glyph_metrics,
+                                                             FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            // Size the render target generously from the design metrics. The image is cropped to
+            // the black box afterward, so overestimating only costs a little memory.
+            let font_size = font_instance.size.to_f32_px();
+            let scale = font_size / font_metrics.designUnitsPerEm as f32;
+            let ink_width = (glyph_metrics.advanceWidth as i32 - glyph_metrics.leftSideBearing -
+                             glyph_metrics.rightSideBearing) as f32 * scale;
+            let ink_height = (glyph_metrics.advanceHeight as i32 - glyph_metrics.topSideBearing -
+                              glyph_metrics.bottomSideBearing) as f32 * scale;
+            let ink_left = glyph_metrics.leftSideBearing as f32 * scale;
+            let ink_top = (glyph_metrics.verticalOriginY - glyph_metrics.topSideBearing) as f32 *
+                scale;
+
+            let baseline_origin = Point2D::new(RASTERIZATION_PADDING - ink_left.floor() as i32,
+                                               RASTERIZATION_PADDING + ink_top.ceil() as i32);
+            let target_size = Size2D::new(ink_width.max(0.0).ceil() as i32 +
+                                          RASTERIZATION_PADDING * 2 + 1,
+                                          ink_height.max(0.0).ceil() as i32 +
+                                          RASTERIZATION_PADDING * 2 + 1);
+
+            let mut gdi_interop = ptr::null_mut();
+            let result = (**self.dwrite_factory).GetGdiInterop(&mut gdi_interop);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+            let gdi_interop = PathfinderComPtr::new(gdi_interop);
+
+            let mut render_target = ptr::null_mut();
+            let result = (**gdi_interop).CreateBitmapRenderTarget(ptr::null_mut(),
+                                                                  target_size.width as UINT32,
+                                                                  target_size.height as UINT32,
+                                                                  &mut render_target);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+            let render_target = PathfinderComPtr::new(render_target);
+
+            // Render at one pixel per DIP regardless of the DPI of the monitor.
+            let result = (**render_target).SetPixelsPerDip(1.0);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+
+            // Start from the system's gamma and contrast, and override the antialiasing.
+            let mut default_rendering_params = ptr::null_mut();
+            let result =
+                (**self.dwrite_factory).CreateRenderingParams(&mut default_rendering_params);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+            let default_rendering_params = PathfinderComPtr::new(default_rendering_params);
+
+            let (clear_type_level, pixel_geometry) = match mode {
+                RasterizationMode::Aliased | RasterizationMode::Grayscale => {
+                    (0.0, DWRITE_PIXEL_GEOMETRY_FLAT)
+                }
+                RasterizationMode::Subpixel => (1.0, DWRITE_PIXEL_GEOMETRY_RGB),
+            };
+            let rendering_mode = match (mode, rendering_mode) {
+                (RasterizationMode::Aliased, _) => DWRITE_RENDERING_MODE_ALIASED,
+                (_, RenderingMode::Recommended) => {
+                    let mut recommended_rendering_mode = DWRITE_RENDERING_MODE_DEFAULT;
+                    let result = (**font_face).GetRecommendedRenderingMode(
+                        font_size,
+                        1.0,
+                        DWRITE_MEASURING_MODE_NATURAL,
+                        *default_rendering_params,
+                        &mut recommended_rendering_mode);
+                    if !winerror::SUCCEEDED(result) {
+                        return Err(FontError::RasterizationFailed(result))
+                    }
+                    recommended_rendering_mode
+                }
+                (_, rendering_mode) => dwrite_rendering_mode(rendering_mode),
+            };
+            let mut rendering_params = ptr::null_mut();
+            let result = (**self.dwrite_factory).CreateCustomRenderingParams(
+                (**default_rendering_params).GetGamma(),
+                (**default_rendering_params).GetEnhancedContrast(),
+                clear_type_level,
+                pixel_geometry,
+                rendering_mode,
+                &mut rendering_params);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+            let rendering_params = PathfinderComPtr::new(rendering_params);
+
+            // Windows 8 and later also let us pick the antialiasing mode of the render target
+            // directly. Earlier versions go by the ClearType level alone.
+            let mut render_target_1: *mut IDWriteBitmapRenderTarget1 = ptr::null_mut();
+            let result = (**render_target).QueryInterface(&IID_IDWriteBitmapRenderTarget1,
+                                                          &mut render_target_1 as *mut *mut _ as
+                                                          *mut *mut c_void);
+            if winerror::SUCCEEDED(result) {
+                let render_target_1 = PathfinderComPtr::new(render_target_1);
+                let antialias_mode = match mode {
+                    RasterizationMode::Subpixel => DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+                    RasterizationMode::Aliased | RasterizationMode::Grayscale => {
+                        DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE
+                    }
+                };
+                let result = (**render_target_1).SetTextAntialiasMode(antialias_mode);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::RasterizationFailed(result))
+                }
+            }
+
+            let glyph_advance = 0.0;
+            let glyph_offset = DWRITE_GLYPH_OFFSET {
+                advanceOffset: 0.0,
+                ascenderOffset: 0.0,
+            };
+            let glyph_run = DWRITE_GLYPH_RUN {
+                fontFace: *font_face,
+                fontEmSize: font_size,
+                glyphCount: 1,
+                glyphIndices: &glyph_index,
+                glyphAdvances: &glyph_advance,
+                glyphOffsets: &glyph_offset,
+                isSideways: FALSE,
+                bidiLevel: 0,
+            };
+
+            // The render target starts out black, so drawing in white leaves coverage in every
+            // channel.
+            let subpixel_offset: f32 = glyph_key.subpixel_offset.into();
+            let mut black_box: RECT = mem::zeroed();
+            let result = (**render_target).DrawGlyphRun(baseline_origin.x as f32 +
+                                                        subpixel_offset,
+                                                        baseline_origin.y as f32,
+                                                        DWRITE_MEASURING_MODE_NATURAL,
+                                                        &glyph_run,
+                                                        *rendering_params,
+                                                        WHITE_COLORREF,
+                                                        &mut black_box);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::RasterizationFailed(result))
+            }
+
+            // Find the top-down 32-bit DIB section that backs the render target.
+            let bitmap = gdi32::GetCurrentObject((**render_target).GetMemoryDC(), OBJ_BITMAP);
+            let mut bitmap_info: BITMAP = mem::zeroed();
+            if gdi32::GetObjectW(bitmap,
+                                 mem::size_of::<BITMAP>() as c_int,
+                                 &mut bitmap_info as *mut BITMAP as *mut c_void) == 0 ||
+                    bitmap_info.bmBits.is_null() {
+                return Err(FontError::RasterizationFailed(E_FAIL))
+            }
+
+            let left = black_box.left.max(0).min(target_size.width);
+            let top = black_box.top.max(0).min(target_size.height);
+            let right = black_box.right.max(left).min(target_size.width);
+            let bottom = black_box.bottom.max(top).min(target_size.height);
+
+            Ok(DrawnGlyph {
+                pixels: bitmap_info.bmBits as *const u8,
+                stride: bitmap_info.bmWidthBytes as usize,
+                origin: Point2D::new(left as usize, top as usize),
+                dimensions: GlyphDimensions {
+                    origin: Point2D::new(left - baseline_origin.x, baseline_origin.y - bottom),
+                    size: Size2D::new((right - left) as u32, (bottom - top) as u32),
+                    advance: glyph_metrics.advanceWidth as f32 * scale,
+                },
+                render_target: render_target,
+            })
+        }
+    }
+
+    /// Returns the embedded bitmap (for example, an `sbix` or `CBDT` emoji image) for the given
+    /// glyph, if the font has one.
+    /// 
+    /// DirectWrite picks the bitmap strike that best matches the size of the font instance, and
+    /// the image is returned at the size of that strike without scaling. The image is 32 bits per
+    /// pixel RGBA, with rows stored top to bottom, and its `origin` is the offset from the pen
+    /// position to its top left corner, with Y pointing down. If the glyph has no bitmap,
+    /// `Ok(None)` is returned. Embedded bitmaps require Windows 10 Anniversary Update or later;
+    /// on older systems this fails with `FontError::Unsupported`.
+    /// 
+    /// PNG, JPEG, and TIFF strikes are decoded with the `image` crate, so this requires the
+    /// `embedded-bitmaps` feature.
+    #[cfg(feature = "embedded-bitmaps")]
+    pub fn glyph_bitmap(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                        -> Result<Option<GlyphImage>, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let mut font_face_4: *mut IDWriteFontFace4 = ptr::null_mut();
+            let result = (**font_face).QueryInterface(&IID_IDWriteFontFace4,
+                                                      &mut font_face_4 as *mut *mut _ as
+                                                      *mut *mut c_void);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::Unsupported)
+            }
+            let font_face_4 = PathfinderComPtr::new(font_face_4);
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let pixels_per_em = font_instance.size.to_f32_px().round().max(1.0) as UINT32;
+            let mut formats = 0;
+            let result = (**font_face_4).GetGlyphImageFormats(glyph_index,
+                                                              pixels_per_em,
+                                                              pixels_per_em,
+                                                              &mut formats);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            let format = match [
+                DWRITE_GLYPH_IMAGE_FORMATS_PNG,
+                DWRITE_GLYPH_IMAGE_FORMATS_PREMULTIPLIED_B8G8R8A8,
+                DWRITE_GLYPH_IMAGE_FORMATS_JPEG,
+                DWRITE_GLYPH_IMAGE_FORMATS_TIFF,
+            ].iter().find(|&&format| formats & format != 0) {
+                None => return Ok(None),
+                Some(&format) => format,
+            };
+
+            let (mut image_data, mut context) = (mem::zeroed(), ptr::null_mut());
+            let result = (**font_face_4).GetGlyphImageData(glyph_index,
+                                                           pixels_per_em,
+                                                           format,
+                                                           &mut image_data,
+                                                           &mut context);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let image_data: DWRITE_GLYPH_IMAGE_DATA = image_data;
+
+            let bytes = slice::from_raw_parts(image_data.imageData as *const u8,
+                                              image_data.imageDataSize as usize);
+            let decoded = if format == DWRITE_GLYPH_IMAGE_FORMATS_PREMULTIPLIED_B8G8R8A8 {
+                Some((image_data.pixelSize.width,
+                      image_data.pixelSize.height,
+                      unpremultiply_bgra_to_rgba(bytes)))
+            } else {
+                image::load_from_memory(bytes).ok().map(|image| {
+                    let image = image.to_rgba();
+                    (image.width(), image.height(), image.into_raw())
+                })
+            };
+            (**font_face_4).ReleaseGlyphImageData(context);
+
+            let (width, height, pixels) = match decoded {
+                None => return Err(FontError::RasterizationFailed(E_FAIL)),
+                Some(decoded) => decoded,
+            };
+
+            let mut glyph_metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index,
+                                                             1,
+                                                             &mut glyph_metrics,
+                                                             FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let advance = glyph_metrics.advanceWidth as f32 * font_instance.size.to_f32_px() /
+                font_metrics.designUnitsPerEm as f32;
+
+            Ok(Some(GlyphImage {
+                dimensions: GlyphDimensions {
+                    origin: Point2D::new(-image_data.horizontalLeftOrigin.x,
+                                         image_data.horizontalLeftOrigin.y - height as i32),
+                    size: Size2D::new(width, height),
+                    advance: advance,
+                },
+                origin: Point2D::new(-image_data.horizontalLeftOrigin.x,
+                                     -image_data.horizontalLeftOrigin.y),
+                format: PixelFormat::Rgba32,
+                stride: width as usize * 4,
+                pixels: pixels,
+            }))
+        }
+    }
+
+    pub fn load_glyph_indices_for_characters(&self,
+                                             font_instance: &FontInstance<FK>,
+                                             characters: &[u32])
+                                             -> Result<Vec<u16>, FontError> {
+        let mut cmap_cache = self.cmap_cache.borrow_mut();
+
+        let mut missing_characters: Vec<u32> = {
+            let cmap = cmap_cache.get(&font_instance.font_key);
+            characters.iter()
+                      .filter(|&c| cmap.map_or(true, |cmap| !cmap.contains_key(c)))
+                      .cloned()
+                      .collect()
+        };
+        missing_characters.sort();
+        missing_characters.dedup();
+
+        // Look the font up even if every character is cached, so that fonts that have been
+        // deleted are still reported as missing.
+        let font_face = try!(self.instance_font_face(font_instance));
+        let cmap = cmap_cache.entry(font_instance.font_key.clone()).or_insert_with(HashMap::new);
+
+        if !missing_characters.is_empty() {
+            unsafe {
+                let mut glyphs = vec![0; missing_characters.len()];
+                let result = (**font_face).GetGlyphIndices(missing_characters.as_ptr(),
+                                                           missing_characters.len() as UINT32,
+                                                           glyphs.as_mut_ptr());
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+
+                cmap.extend(missing_characters.into_iter().zip(glyphs.into_iter()));
+            }
+        }
+
+        Ok(characters.iter().map(|character| cmap[character]).collect())
+    }
+
+    /// Returns the colored layers that make up the given glyph, from bottom to top, if the font
+    /// has `COLR` data for it.
+    /// 
+    /// Colors come from the first `CPAL` palette. Render each layer by looking up the outline of
+    /// its glyph and filling it with its color. If the glyph has no color data, `Ok(None)` is
+    /// returned, and the glyph should be rendered as usual. Color fonts require Windows 8.1 or
+    /// later; on older systems this fails with `FontError::Unsupported`.
+    pub fn color_glyph_layers(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                              -> Result<Option<Vec<ColorGlyphLayer>>, FontError> {
+        unsafe {
+            let font_face = try!(self.instance_font_face(font_instance));
+
+            let mut factory_2: *mut IDWriteFactory2 = ptr::null_mut();
+            let result = (**self.dwrite_factory).QueryInterface(&IID_IDWriteFactory2,
+                                                                &mut factory_2 as *mut *mut _ as
+                                                                *mut *mut c_void);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::Unsupported)
+            }
+            let factory_2 = PathfinderComPtr::new(factory_2);
+
+            let glyph_index = glyph_key.glyph_index as UINT16;
+            let glyph_advance = 0.0;
+            let glyph_offset = DWRITE_GLYPH_OFFSET {
+                advanceOffset: 0.0,
+                ascenderOffset: 0.0,
+            };
+            let glyph_run = DWRITE_GLYPH_RUN {
+                fontFace: *font_face,
+                fontEmSize: font_instance.size.to_f32_px(),
+                glyphCount: 1,
+                glyphIndices: &glyph_index,
+                glyphAdvances: &glyph_advance,
+                glyphOffsets: &glyph_offset,
+                isSideways: FALSE,
+                bidiLevel: 0,
+            };
+
+            let mut color_layers = ptr::null_mut();
+            let result = (**factory_2).TranslateColorGlyphRun(0.0,
+                                                              0.0,
+                                                              &glyph_run,
+                                                              ptr::null(),
+                                                              DWRITE_MEASURING_MODE_NATURAL,
+                                                              ptr::null(),
+                                                              0,
+                                                              &mut color_layers);
+            if result == DWRITE_E_NOCOLOR {
+                return Ok(None)
+            }
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let color_layers = PathfinderComPtr::new(color_layers);
+
+            let mut layers = vec![];
+            loop {
+                let mut has_run = FALSE;
+                let result = (**color_layers).MoveNext(&mut has_run);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+                if has_run == FALSE {
+                    break
+                }
+
+                let mut color_run = ptr::null();
+                let result = (**color_layers).GetCurrentRun(&mut color_run);
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+
+                let color_run = &*color_run;
+                let color = if color_run.paletteIndex == FOREGROUND_PALETTE_INDEX {
+                    None
+                } else {
+                    let run_color = &color_run.runColor;
+                    Some([run_color.r, run_color.g, run_color.b, run_color.a])
+                };
+                let glyph_indices = slice::from_raw_parts(color_run.glyphRun.glyphIndices,
+                                                          color_run.glyphRun.glyphCount as usize);
+                layers.extend(glyph_indices.iter().map(|&glyph_index| {
+                    ColorGlyphLayer {
+                        glyph_index: glyph_index as u32,
+                        color: color,
+                    }
+                }))
+            }
+            Ok(Some(layers))
+        }
+    }
+
+    /// Returns the paint graph of the given glyph, if the font has `COLR` version 1 data for it.
+    /// 
+    /// Version 1 color glyphs are built from gradients, transforms, and compositing, which
+    /// `color_glyph_layers` can't express. A renderer draws one by walking the graph from its
+    /// root, fetching the outline of each `PaintNode::Glyph` and the graph of each
+    /// `PaintNode::ColrGlyph` as it goes. Colors come from the first `CPAL` palette. If the glyph
+    /// has no version 1 paint, including when the font only has version 0 layers, `Ok(None)` is
+    /// returned. Variable fonts are painted at their default instance.
+    pub fn colrv1_paint(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                        -> Result<Option<PaintGraph>, FontError> {
+        if glyph_key.glyph_index > u16::MAX as u32 {
+            return Err(FontError::GlyphNotFound)
+        }
+
+        let (font_face, font_metrics) = try!(self.instance_font_face_and_metrics(font_instance));
+        unsafe {
+            let colr_table = match try!(FontTable::new(&font_face, tables::COLR)) {
+                None => return Ok(None),
+                Some(colr_table) => colr_table,
+            };
+            let palette = match try!(FontTable::new(&font_face, tables::CPAL)) {
+                None => vec![],
+                Some(cpal_table) => {
+                    tables::cpal_palette(cpal_table.bytes(), 0).unwrap_or(vec![])
+                }
+            };
+
+            let scale = font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
+            let root = tables::colr_paint(colr_table.bytes(),
+                                          glyph_key.glyph_index as u16,
+                                          &palette,
+                                          scale);
+            Ok(root.map(|root| PaintGraph { root: root }))
+        }
+    }
+
+    /// Converts a string to positioned glyphs, applying the given OpenType features.
+    /// 
+    /// Each feature is a tag (for example, `*b"smcp"`) and a parameter: 0 disables the feature, 1
+    /// enables it, and higher values select an alternate. The features apply to the entire string,
+    /// in addition to the ones DirectWrite enables by default.
+    /// 
+    /// The string is shaped as a single run in the given script, so mixed-script text must be
+    /// split into runs first. `script` is an ISO 15924 code such as `*b"Latn"`, `*b"Cyrl"`, or
+    /// `*b"Deva"`; `*b"Zyyy"` selects DirectWrite's default, script-neutral shaping. `language`
+    /// is a locale name such as `tr-TR` or `sr-Cyrl`, which selects language-specific forms and
+    /// substitutions such as the Turkish dotted `i`; an empty string means US English. Scripts
+    /// that DirectWrite doesn't know and locale names that contain a NUL character return
+    /// `FontError::InvalidArgument`, and shaping in a script other than `Zyyy` requires Windows
+    /// 8 or later; on older systems it fails with `FontError::Unsupported`.
+    /// 
+    /// Set `is_right_to_left` for Arabic, Hebrew, and other right-to-left runs: DirectWrite then
+    /// mirrors characters such as parentheses. The glyphs are still returned in logical order,
+    /// and callers are responsible for bidirectional reordering and for advancing the pen
+    /// leftward.
+    /// 
+    /// Combining marks are positioned by DirectWrite's `mark` and `mkmk` features. If DirectWrite
+    /// leaves a glyph that the font's `GDEF` table classifies as a mark unpositioned, it's
+    /// attached to the anchor of its base glyph, or of the mark that it stacks on, using the
+    /// mark-to-base and mark-to-mark lookups of the font's `GPOS` table, unless those features
+    /// are disabled.
+    pub fn shape(&self,
+                 font_instance: &FontInstance<FK>,
+                 text: &str,
+                 script: [u8; 4],
+                 language: &str,
+                 features: &[(FeatureTag, u32)],
+                 is_right_to_left: bool)
+                 -> Result<Vec<ShapedGlyph>, FontError> {
+        let ranges = [(0..text.len(), features.to_vec())];
+        self.shape_with_ranges(font_instance, text, script, language, &ranges, is_right_to_left)
+    }
+
+    /// Converts a string to positioned glyphs, applying OpenType features to parts of it.
+    /// 
+    /// This is like `shape`, but each set of features applies only to the characters in its
+    /// byte range of `text`. This allows, for example, enabling `calt` or a stylistic set such as
+    /// `ss01` on a single word, or disabling `liga` where a ligature would be wrong. Characters
+    /// outside every range get only the features that DirectWrite enables by default.
+    /// 
+    /// The ranges must lie on character boundaries and must be sorted and not overlap; otherwise,
+    /// this returns `FontError::InvalidArgument`. Empty ranges are ignored. Since features such
+    /// as `calt` look at neighboring characters, a cluster that straddles two ranges may be
+    /// shaped with the features of either.
+    pub fn shape_with_ranges(&self,
+                             font_instance: &FontInstance<FK>,
+                             text: &str,
+                             script: [u8; 4],
+                             language: &str,
+                             ranges: &[(Range<usize>, Vec<(FeatureTag, u32)>)],
+                             is_right_to_left: bool)
+                             -> Result<Vec<ShapedGlyph>, FontError> {
+        // DirectWrite wants a block of features for every UTF-16 code unit, so fill the gaps
+        // between the ranges with empty blocks.
+        let mut dwrite_features: Vec<Vec<DWRITE_FONT_FEATURE>> = vec![];
+        let mut feature_range_lengths: Vec<UINT32> = vec![];
+        let mut text_position = 0;
+        for &(ref range, ref features) in ranges {
+            if range.start < text_position || range.end < range.start ||
+                    range.end > text.len() || !text.is_char_boundary(range.start) ||
+                    !text.is_char_boundary(range.end) {
+                return Err(FontError::InvalidArgument)
+            }
+            if range.start == range.end {
+                continue
+            }
+            if range.start > text_position {
+                dwrite_features.push(vec![]);
+                feature_range_lengths.push(utf16_length(&text[text_position..range.start]));
+            }
+            dwrite_features.push(features.iter().map(|&(tag, parameter)| {
+                DWRITE_FONT_FEATURE {
+                    nameTag: DWRITE_FONT_FEATURE_TAG(dwrite_tag(tag)),
+                    parameter: parameter,
+                }
+            }).collect());
+            feature_range_lengths.push(utf16_length(&text[range.clone()]));
+            text_position = range.end
+        }
+        if text_position < text.len() {
+            dwrite_features.push(vec![]);
+            feature_range_lengths.push(utf16_length(&text[text_position..]));
+        }
+
+        let text_utf16: Vec<u16> = text.encode_utf16().collect();
+        if text_utf16.is_empty() {
+            return Ok(vec![])
+        }
+
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.instance_font_face_and_metrics(font_instance));
+
+            let mut text_analyzer = ptr::null_mut();
+            let result = (**self.dwrite_factory).CreateTextAnalyzer(&mut text_analyzer);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let text_analyzer = PathfinderComPtr::new(text_analyzer);
+
+            let script_analysis = DWRITE_SCRIPT_ANALYSIS {
+                script: try!(dwrite_script(&text_analyzer, script)),
+                shapes: DWRITE_SCRIPT_SHAPES_DEFAULT,
+            };
+            let language = if language.is_empty() { DEFAULT_LOCALE_NAME } else { language };
+            let locale_name = match WideCString::from_str(language) {
+                Ok(locale_name) => locale_name,
+                Err(_) => return Err(FontError::InvalidArgument),
+            };
+
+            let typographic_features: Vec<_> = dwrite_features.iter_mut().map(|features| {
+                DWRITE_TYPOGRAPHIC_FEATURES {
+                    features: features.as_mut_ptr(),
+                    featureCount: features.len() as UINT32,
+                }
+            }).collect();
+            let mut feature_ranges: Vec<_> = typographic_features.iter().map(|features| {
+                features as *const DWRITE_TYPOGRAPHIC_FEATURES
+            }).collect();
+            let has_features = dwrite_features.iter().any(|features| !features.is_empty());
+            let feature_range_count = if has_features { feature_ranges.len() as UINT32 } else { 0 };
+
+            let is_right_to_left = if is_right_to_left { TRUE } else { FALSE };
+
+            let text_length = text_utf16.len();
+            let mut cluster_map = vec![0; text_length];
+            let mut text_props: Vec<DWRITE_SHAPING_TEXT_PROPERTIES> =
+                vec![mem::zeroed(); text_length];
+
+            // Ligatures and decompositions mean that the number of glyphs isn't known in advance,
+            // so grow the buffers until they're big enough.
+            let mut max_glyph_count = text_length * 3 / 2 + 16;
+            let (mut glyph_indices, mut glyph_props, mut glyph_count);
+            loop {
+                glyph_indices = vec![0; max_glyph_count];
+                glyph_props = vec![mem::zeroed(); max_glyph_count];
+                glyph_count = 0;
+                let result = (**text_analyzer).GetGlyphs(text_utf16.as_ptr(),
+                                                         text_length as UINT32,
+                                                         *font_face,
+                                                         FALSE,
+                                                         is_right_to_left,
+                                                         &script_analysis,
+                                                         locale_name.as_ptr(),
+                                                         ptr::null_mut(),
+                                                         feature_ranges.as_mut_ptr(),
+                                                         feature_range_lengths.as_ptr(),
+                                                         feature_range_count,
+                                                         max_glyph_count as UINT32,
+                                                         cluster_map.as_mut_ptr(),
+                                                         text_props.as_mut_ptr(),
+                                                         glyph_indices.as_mut_ptr(),
+                                                         glyph_props.as_mut_ptr(),
+                                                         &mut glyph_count);
+                if result == E_NOT_SUFFICIENT_BUFFER {
+                    max_glyph_count *= 2;
+                    continue
+                }
+                if !winerror::SUCCEEDED(result) {
+                    return Err(FontError::QueryFailed(result))
+                }
+                break
+            }
+            let glyph_count = glyph_count as usize;
+            let glyph_props: Vec<DWRITE_SHAPING_GLYPH_PROPERTIES> = glyph_props;
+
+            let mut glyph_advances = vec![0.0; glyph_count];
+            let mut glyph_offsets = vec![mem::zeroed(); glyph_count];
+            let result = (**text_analyzer).GetGlyphPlacements(text_utf16.as_ptr(),
+                                                              cluster_map.as_ptr(),
+                                                              text_props.as_mut_ptr(),
+                                                              text_length as UINT32,
+                                                              glyph_indices.as_ptr(),
+                                                              glyph_props.as_ptr(),
+                                                              glyph_count as UINT32,
+                                                              *font_face,
+                                                              font_instance.size.to_f32_px(),
+                                                              FALSE,
+                                                              is_right_to_left,
+                                                              &script_analysis,
+                                                              locale_name.as_ptr(),
+                                                              feature_ranges.as_mut_ptr(),
+                                                              feature_range_lengths.as_ptr(),
+                                                              feature_range_count,
+                                                              glyph_advances.as_mut_ptr(),
+                                                              glyph_offsets.as_mut_ptr());
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let glyph_offsets: Vec<DWRITE_GLYPH_OFFSET> = glyph_offsets;
+            let mut glyph_offsets: Vec<_> = glyph_offsets.iter().map(|glyph_offset| {
+                Vector2D::new(glyph_offset.advanceOffset, glyph_offset.ascenderOffset)
+            }).collect();
+
+            // Some fonts' mark lookups aren't registered for the script that DirectWrite shapes
+            // with, which leaves their marks at the pen position. Attach those marks to their
+            // anchors ourselves, unless the caller turned mark positioning off.
+            let marks_disabled = ranges.iter().any(|&(_, ref features)| {
+                features.iter().any(|&(tag, parameter)| {
+                    (tag == *b"mark" || tag == *b"mkmk") && parameter == 0
+                })
+            });
+            if !marks_disabled {
+                let gpos_table = try!(FontTable::new(&font_face, tables::GPOS));
+                let gdef_table = try!(FontTable::new(&font_face, tables::GDEF));
+                if let (Some(gpos_table), Some(gdef_table)) = (gpos_table, gdef_table) {
+                    let scale = font_instance.size.to_f32_px() /
+                        font_metrics.designUnitsPerEm as f32;
+                    position_marks(gpos_table.bytes(),
+                                   gdef_table.bytes(),
+                                   &glyph_indices[0..glyph_count],
+                                   &glyph_advances,
+                                   &mut glyph_offsets,
+                                   scale,
+                                   is_right_to_left != FALSE);
+                }
+            }
+
+            // The cluster map gives the first glyph of the cluster that each UTF-16 code unit
+            // belongs to. Invert it to find the first code unit, and then the byte offset, of the
+            // cluster that each glyph belongs to.
+            let utf16_to_byte_offsets: Vec<usize> = text.char_indices().flat_map(|(index, ch)| {
+                ::std::iter::repeat(index).take(ch.len_utf16())
+            }).collect();
+            let mut glyph_clusters = vec![0; glyph_count];
+            for (text_position, &first_glyph) in cluster_map.iter().enumerate() {
+                if text_position > 0 && cluster_map[text_position - 1] == first_glyph {
+                    continue
+                }
+                for glyph_cluster in &mut glyph_clusters[(first_glyph as usize)..] {
+                    *glyph_cluster = utf16_to_byte_offsets[text_position]
+                }
+            }
+
+            Ok((0..glyph_count).map(|glyph| {
+                ShapedGlyph {
+                    glyph_index: glyph_indices[glyph],
+                    cluster: glyph_clusters[glyph],
+                    advance: glyph_advances[glyph],
+                    offset: glyph_offsets[glyph],
+                }
+            }).collect())
+        }
+    }
+
+    /// Returns the kerning adjustment to apply between the given pair of glyphs, in pixels.
+    /// 
+    /// The adjustment comes from the format 0 subtables of the font's `kern` table. If the font
+    /// has no `kern` table, or the pair isn't kerned, the adjustment is zero.
+    pub fn glyph_kerning(&self, font_instance: &FontInstance<FK>, left: u16, right: u16)
+                         -> Result<f32, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.font_face_and_metrics(&font_instance.font_key));
+
+            let kern_table = match try!(FontTable::new(&font_face, tables::KERN)) {
+                None => return Ok(0.0),
+                Some(kern_table) => kern_table,
+            };
+            let kerning = tables::kerning(kern_table.bytes(), left, right).unwrap_or(0);
+            Ok(kerning as f32 * font_instance.size.to_f32_px() /
+               font_metrics.designUnitsPerEm as f32)
+        }
+    }
+
+    /// Returns the total advance width of the given text in the given font, in pixels.
+    /// 
+    /// Each character is mapped to a glyph with the font's character map, as in
+    /// `load_glyph_indices_for_characters`, and the advances of the glyphs are summed. Characters
+    /// that the font doesn't cover are measured as its `.notdef` glyph, which is what gets drawn
+    /// for them. No shaping is done, so ligatures and contextual forms aren't accounted for; use
+    /// `shape` for text that needs them. Kerning isn't applied either; see `measure_text_kerned`.
+    pub fn measure_text(&self, font_instance: &FontInstance<FK>, text: &str)
+                        -> Result<f32, FontError> {
+        self.measure_characters(font_instance, text, false)
+    }
+
+    /// Returns the total advance width of the given text in the given font, in pixels, adjusted
+    /// by the kerning between each pair of adjacent glyphs.
+    /// 
+    /// This is like `measure_text`, but it also adds the adjustments that `glyph_kerning`
+    /// returns.
+    pub fn measure_text_kerned(&self, font_instance: &FontInstance<FK>, text: &str)
+                               -> Result<f32, FontError> {
+        self.measure_characters(font_instance, text, true)
+    }
+
+    fn measure_characters(&self, font_instance: &FontInstance<FK>, text: &str, kerned: bool)
+                          -> Result<f32, FontError> {
+        if text.is_empty() {
+            return Ok(0.0)
+        }
+
+        let characters: Vec<u32> = text.chars().map(|character| character as u32).collect();
+        let glyph_indices = try!(self.load_glyph_indices_for_characters(font_instance,
+                                                                        &characters));
+        let glyph_keys: Vec<_> = glyph_indices.iter().map(|&glyph_index| {
+            GlyphKey::new(glyph_index as u32, SubpixelOffset(0))
+        }).collect();
+        let advances = try!(self.glyph_advances(font_instance, &glyph_keys));
+
+        let mut width = advances.iter().fold(0.0, |width, advance| width + advance);
+        if !kerned {
+            return Ok(width)
+        }
+
+        // Map the `kern` table once for the whole string, rather than once per pair.
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.font_face_and_metrics(&font_instance.font_key));
+            let kern_table = match try!(FontTable::new(&font_face, tables::KERN)) {
+                None => return Ok(width),
+                Some(kern_table) => kern_table,
+            };
+            let kerning = glyph_indices.windows(2).fold(0, |kerning, glyph_pair| {
+                kerning + tables::kerning(kern_table.bytes(), glyph_pair[0], glyph_pair[1])
+                    .unwrap_or(0)
+            });
+            width += kerning as f32 * font_instance.size.to_f32_px() /
+                font_metrics.designUnitsPerEm as f32;
+        }
+        Ok(width)
+    }
+
+    /// Returns the constants that control math layout in the given font, scaled to its size, or
+    /// `None` if the font has no `MATH` table.
+    pub fn math_constants(&self, font_instance: &FontInstance<FK>)
+                          -> Result<Option<MathConstants>, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.font_face_and_metrics(&font_instance.font_key));
+
+            let math_table = match try!(FontTable::new(&font_face, tables::MATH)) {
+                None => return Ok(None),
+                Some(math_table) => math_table,
+            };
+            let constants = match tables::math_constants(math_table.bytes()) {
+                None => return Ok(None),
+                Some(constants) => constants,
+            };
+
+            let scale = font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
+            Ok(Some(MathConstants {
+                script_percent_scale_down: constants[0] as i16,
+                script_script_percent_scale_down: constants[1] as i16,
+                delimited_sub_formula_min_height: constants[2] as f32 * scale,
+                display_operator_min_height: constants[3] as f32 * scale,
+                math_leading: constants[4] as f32 * scale,
+                axis_height: constants[5] as f32 * scale,
+                accent_base_height: constants[6] as f32 * scale,
+                flattened_accent_base_height: constants[7] as f32 * scale,
+                subscript_shift_down: constants[8] as f32 * scale,
+                subscript_top_max: constants[9] as f32 * scale,
+                subscript_baseline_drop_min: constants[10] as f32 * scale,
+                superscript_shift_up: constants[11] as f32 * scale,
+                superscript_shift_up_cramped: constants[12] as f32 * scale,
+                superscript_bottom_min: constants[13] as f32 * scale,
+                superscript_baseline_drop_max: constants[14] as f32 * scale,
+                sub_superscript_gap_min: constants[15] as f32 * scale,
+                superscript_bottom_max_with_subscript: constants[16] as f32 * scale,
+                space_after_script: constants[17] as f32 * scale,
+                upper_limit_gap_min: constants[18] as f32 * scale,
+                upper_limit_baseline_rise_min: constants[19] as f32 * scale,
+                lower_limit_gap_min: constants[20] as f32 * scale,
+                lower_limit_baseline_drop_min: constants[21] as f32 * scale,
+                stack_top_shift_up: constants[22] as f32 * scale,
+                stack_top_display_style_shift_up: constants[23] as f32 * scale,
+                stack_bottom_shift_down: constants[24] as f32 * scale,
+                stack_bottom_display_style_shift_down: constants[25] as f32 * scale,
+                stack_gap_min: constants[26] as f32 * scale,
+                stack_display_style_gap_min: constants[27] as f32 * scale,
+                stretch_stack_top_shift_up: constants[28] as f32 * scale,
+                stretch_stack_bottom_shift_down: constants[29] as f32 * scale,
+                stretch_stack_gap_above_min: constants[30] as f32 * scale,
+                stretch_stack_gap_below_min: constants[31] as f32 * scale,
+                fraction_numerator_shift_up: constants[32] as f32 * scale,
+                fraction_numerator_display_style_shift_up: constants[33] as f32 * scale,
+                fraction_denominator_shift_down: constants[34] as f32 * scale,
+                fraction_denominator_display_style_shift_down: constants[35] as f32 * scale,
+                fraction_numerator_gap_min: constants[36] as f32 * scale,
+                fraction_num_display_style_gap_min: constants[37] as f32 * scale,
+                fraction_rule_thickness: constants[38] as f32 * scale,
+                fraction_denominator_gap_min: constants[39] as f32 * scale,
+                fraction_denom_display_style_gap_min: constants[40] as f32 * scale,
+                skewed_fraction_horizontal_gap: constants[41] as f32 * scale,
+                skewed_fraction_vertical_gap: constants[42] as f32 * scale,
+                overbar_vertical_gap: constants[43] as f32 * scale,
+                overbar_rule_thickness: constants[44] as f32 * scale,
+                overbar_extra_ascender: constants[45] as f32 * scale,
+                underbar_vertical_gap: constants[46] as f32 * scale,
+                underbar_rule_thickness: constants[47] as f32 * scale,
+                underbar_extra_descender: constants[48] as f32 * scale,
+                radical_vertical_gap: constants[49] as f32 * scale,
+                radical_display_style_vertical_gap: constants[50] as f32 * scale,
+                radical_rule_thickness: constants[51] as f32 * scale,
+                radical_extra_ascender: constants[52] as f32 * scale,
+                radical_kern_before_degree: constants[53] as f32 * scale,
+                radical_kern_after_degree: constants[54] as f32 * scale,
+                radical_degree_bottom_raise_percent: constants[55] as i16,
+            }))
+        }
+    }
+
+    /// Returns the italic correction of the given glyph from the font's `MATH` table, in pixels.
+    /// 
+    /// This is the extra space to leave after a slanted glyph when it's followed by an upright
+    /// one, and it's also used to position the limits of integral signs. It's zero if the font
+    /// has no `MATH` table or the glyph has no italic correction.
+    pub fn math_italic_correction(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
+                                  -> Result<f32, FontError> {
+        unsafe {
+            let (font_face, font_metrics) =
+                try!(self.font_face_and_metrics(&font_instance.font_key));
+
+            let math_table = match try!(FontTable::new(&font_face, tables::MATH)) {
+                None => return Ok(0.0),
+                Some(math_table) => math_table,
+            };
+            let glyph_index = glyph_key.glyph_index as u16;
+            let italic_correction = tables::math_italic_correction(math_table.bytes(),
+                                                                   glyph_index).unwrap_or(0);
+            Ok(italic_correction as f32 * font_instance.size.to_f32_px() /
+               font_metrics.designUnitsPerEm as f32)
+        }
+    }
+
+    /// Returns the position of the given baseline (for example, `*b"ideo"` or `*b"hang"`) in
+    /// horizontal text, relative to the baseline that the glyphs are designed on, scaled to the
+    /// size of the given font instance, with Y pointing up.
+    /// 
+    /// `script` is an OpenType script tag, such as `*b"hani"` or `*b"deva"`. If the font has no
+    /// `BASE` table, or the table doesn't give the baseline for that script, returns `None`.
+    pub fn baseline_offset(&self,
+                           font_instance: &FontInstance<FK>,
+                           baseline_tag: [u8; 4],
+                           script: [u8; 4])
+                           -> Result<Option<f32>, FontError> {
+        let base_table = match try!(self.font_table(&font_instance.font_key, tables::BASE)) {
+            None => return Ok(None),
+            Some(base_table) => base_table,
+        };
+        let scale = try!(self.scale_factor(font_instance));
+        Ok(tables::baseline(&base_table, baseline_tag, script).map(|offset| offset as f32 * scale))
+    }
+
+    /// Returns the font-wide metrics of the given font, scaled to its size.
+    pub fn font_metrics(&self, font_instance: &FontInstance<FK>)
+                        -> Result<FontMetrics, FontError> {
+        let metrics = try!(self.dwrite_font_metrics(font_instance));
+        let scale = font_instance.size.to_f32_px() / metrics.designUnitsPerEm as f32;
+        Ok(FontMetrics {
+            ascent: metrics.ascent as f32 * scale,
+            descent: metrics.descent as f32 * scale,
+            line_gap: metrics.lineGap as f32 * scale,
+            underline_position: metrics.underlinePosition as f32 * scale,
+            underline_thickness: metrics.underlineThickness as f32 * scale,
+            strikeout_position: metrics.strikethroughPosition as f32 * scale,
+            strikeout_thickness: metrics.strikethroughThickness as f32 * scale,
+        })
+    }
+
+    /// Returns the advance width of the given glyph in font design units, exactly as stored in
+    /// the `hmtx` table of the font.
+    /// 
+    /// This is read from the default instance of variable fonts. If the font's `hhea` or `hmtx`
+    /// table can't be read, the design advance that DirectWrite reports is returned instead.
+    pub fn raw_advance(&self, font_key: &FK, glyph_index: u16) -> Result<u16, FontError> {
+        let font_face = try!(self.font_face(font_key));
+        unsafe {
+            if glyph_index >= (**font_face).GetGlyphCount() {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            let hhea_table = try!(FontTable::new(&font_face, tables::HHEA));
+            let hmtx_table = try!(FontTable::new(&font_face, tables::HMTX));
+            if let (Some(hhea_table), Some(hmtx_table)) = (hhea_table, hmtx_table) {
+                if let Some(advance) = tables::advance_width(hhea_table.bytes(),
+                                                             hmtx_table.bytes(),
+                                                             glyph_index) {
+                    return Ok(advance)
+                }
+            }
+
+            let mut glyph_metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index,
+                                                             1,
+                                                             &mut glyph_metrics,
+                                                             FALSE);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            Ok(glyph_metrics.advanceWidth as u16)
+        }
+    }
+
+    /// Returns true if the given glyph is a composite glyph, which references other glyphs, or
+    /// false if it's a simple outline.
+    /// 
+    /// This is read from the `glyf` table, so it's only defined for TrueType-flavored fonts; for
+    /// CFF-flavored fonts, which have no `glyf` table, `FontError::Unsupported` is returned. If
+    /// the glyph's entry can't be read, `FontError::CorruptFont` is returned.
+    pub fn glyph_is_composite(&self, font_key: &FK, glyph_index: u16)
+                              -> Result<bool, FontError> {
+        let font_face = try!(self.font_face(font_key));
+        unsafe {
+            if glyph_index >= (**font_face).GetGlyphCount() {
+                return Err(FontError::GlyphNotFound)
+            }
+
+            let glyf_table = match try!(FontTable::new(&font_face, tables::GLYF)) {
+                None => return Err(FontError::Unsupported),
+                Some(glyf_table) => glyf_table,
+            };
+            let head_table = match try!(FontTable::new(&font_face, tables::HEAD)) {
+                None => return Err(FontError::CorruptFont(Some(tables::HEAD))),
+                Some(head_table) => head_table,
+            };
+            let loca_table = match try!(FontTable::new(&font_face, tables::LOCA)) {
+                None => return Err(FontError::CorruptFont(Some(tables::LOCA))),
+                Some(loca_table) => loca_table,
+            };
+            match tables::is_composite_glyph(head_table.bytes(),
+                                             loca_table.bytes(),
+                                             glyf_table.bytes(),
+                                             glyph_index) {
+                None => Err(FontError::CorruptFont(Some(tables::GLYF))),
+                Some(is_composite) => Ok(is_composite),
+            }
+        }
+    }
+
+    /// Returns the format in which the given font stores its glyph outlines.
+    /// 
+    /// `glyph_outline` returns outlines of any flavor, converting them to the same kind of path,
+    /// but this tells whether they started out quadratic or cubic, and how they are hinted. The
+    /// flavor is determined by which of the `glyf`, `CFF2`, and `CFF ` tables the font contains,
+    /// in that order of preference. If it has none of them, as in a bitmap-only font,
+    /// `FontError::Unsupported` is returned.
+    pub fn outline_flavor(&self, font_key: &FK) -> Result<OutlineFlavor, FontError> {
+        let font_face = try!(self.font_face(font_key));
+        let flavors = [
+            (tables::GLYF, OutlineFlavor::TrueType),
+            (tables::CFF2, OutlineFlavor::Cff2),
+            (tables::CFF, OutlineFlavor::Cff),
+        ];
+        unsafe {
+            for &(tag, flavor) in &flavors {
+                if try!(FontTable::new(&font_face, tag)).is_some() {
+                    return Ok(flavor)
+                }
+            }
+        }
+        Err(FontError::Unsupported)
+    }
+
+    /// Returns the SVG document that the `SVG ` table of the given font provides for the given
+    /// glyph, decompressed if the font stores it gzip-compressed.
+    /// 
+    /// A single document may contain several glyphs; the one for `glyph_index` is the element
+    /// with the ID `glyph` followed by the index, such as `glyph42`. If the font has no SVG
+    /// document for the glyph, `Ok(None)` is returned. If the document can't be decompressed,
+    /// `FontError::QueryFailed` is returned.
+    pub fn glyph_svg_document(&self, font_key: &FK, glyph_index: u16)
+                              -> Result<Option<Vec<u8>>, FontError> {
+        let svg_table = match try!(self.font_table(font_key, tables::SVG)) {
+            None => return Ok(None),
+            Some(svg_table) => svg_table,
+        };
+        let document = match tables::svg_document(&svg_table, glyph_index) {
+            None => return Ok(None),
+            Some(document) => document,
+        };
+
+        if !document.starts_with(&[0x1f, 0x8b]) {
+            return Ok(Some(document.to_vec()))
+        }
+        let mut decompressed_document = vec![];
+        match GzDecoder::new(document).read_to_end(&mut decompressed_document) {
+            Ok(_) => Ok(Some(decompressed_document)),
+            Err(_) => Err(FontError::QueryFailed(E_FAIL)),
+        }
+    }
+
+    /// Returns the line spacing metrics that the given font declares in its `hhea` and `OS/2`
+    /// tables, scaled to its size.
+    /// 
+    /// The values are read from the default instance of variable fonts. If the font lacks either
+    /// table, the metrics that the table would have provided are the ascent, descent, and line
+    /// gap that DirectWrite reports, and `use_typo_metrics` is false.
+    pub fn line_metrics(&self, font_instance: &FontInstance<FK>)
+                        -> Result<LineMetrics, FontError> {
+        let metrics = try!(self.dwrite_font_metrics(font_instance));
+        let scale = font_instance.size.to_f32_px() / metrics.designUnitsPerEm as f32;
+        let font_key = &font_instance.font_key;
+
+        let hhea_table = try!(self.font_table(font_key, tables::HHEA));
+        let hhea_metrics = hhea_table.as_ref().and_then(|hhea_table| {
+            tables::hhea_line_metrics(hhea_table)
+        });
+        let (hhea_ascent, hhea_descent, hhea_line_gap) = match hhea_metrics {
+            Some((ascender, descender, line_gap)) => {
+                (ascender as f32, -(descender as f32), line_gap as f32)
+            }
+            None => (metrics.ascent as f32, metrics.descent as f32, metrics.lineGap as f32),
+        };
+
+        let os_2_table = try!(self.font_table(font_key, tables::OS_2));
+        let os_2_metrics = os_2_table.as_ref().and_then(|os_2_table| {
+            tables::os_2_line_metrics(os_2_table)
+        });
+        let (typo_ascent, typo_descent, typo_line_gap, win_ascent, win_descent) =
+                match os_2_metrics {
+            Some((typo_ascender, typo_descender, typo_line_gap, win_ascent, win_descent)) => {
+                (typo_ascender as f32,
+                 -(typo_descender as f32),
+                 typo_line_gap as f32,
+                 win_ascent as f32,
+                 win_descent as f32)
+            }
+            None => {
+                (metrics.ascent as f32,
+                 metrics.descent as f32,
+                 metrics.lineGap as f32,
+                 metrics.ascent as f32,
+                 metrics.descent as f32)
+            }
+        };
+        let use_typo_metrics = os_2_metrics.is_some() && match os_2_table {
+            Some(ref os_2_table) => {
+                tables::os_2_style(os_2_table).map_or(false, |(_, _, fs_selection)| {
+                    fs_selection & tables::OS_2_FS_SELECTION_USE_TYPO_METRICS != 0
+                })
+            }
+            None => false,
+        };
+
+        Ok(LineMetrics {
+            hhea_ascent: hhea_ascent * scale,
+            hhea_descent: hhea_descent * scale,
+            hhea_line_gap: hhea_line_gap * scale,
+            typo_ascent: typo_ascent * scale,
+            typo_descent: typo_descent * scale,
+            typo_line_gap: typo_line_gap * scale,
+            win_ascent: win_ascent * scale,
+            win_descent: win_descent * scale,
+            use_typo_metrics: use_typo_metrics,
+        })
+    }
+
+    // Records the in-memory font file, if any, that now backs the font with the given key, and
+    // frees the one that backed it before.
+    fn replace_font_file_key(&mut self, font_key: FK, new_font_file_key: Option<u64>) {
+        let old_font_file_key = match new_font_file_key {
+            None => self.font_file_keys.remove(&font_key),
+            Some(new_font_file_key) => self.font_file_keys.insert(font_key, new_font_file_key),
+        };
+        if let Some(old_font_file_key) = old_font_file_key {
+            unsafe {
+                (**self.font_file_loader.loader).buffers.lock().unwrap().remove(&old_font_file_key);
+            }
+        }
+    }
+
+    /// Returns the DirectWrite font face behind the given font, or `None` if no font with that key
+    /// is loaded.
+    ///
+    /// This is an escape hatch for calling DirectWrite APIs that this crate doesn't wrap. The
+    /// returned pointer holds its own reference, so it stays valid even after the font is deleted
+    /// from this context. For variable fonts, this is the default instance.
+    #[inline]
+    pub fn raw_font_face(&self, font_key: &FK) -> Option<PathfinderComPtr<IDWriteFontFace>> {
+        self.font_face(font_key).ok()
+    }
+
+    fn font_face(&self, font_key: &FK) -> Result<PathfinderComPtr<IDWriteFontFace>, FontError> {
+        self.loaded_font_face(font_key).map(|loaded_font_face| {
+            loaded_font_face.font_face.clone()
+        })
+    }
+
+    fn loaded_font_face(&self, font_key: &FK) -> Result<&LoadedFontFace, FontError> {
+        match self.dwrite_font_faces.get(font_key) {
+            None => Err(FontError::FontNotLoaded),
+            Some(loaded_font_face) => Ok(loaded_font_face),
+        }
+    }
+
+    /// Returns a copy of the raw contents of the OpenType table with the given tag (for example,
+    /// `*b"GSUB"`), or `None` if the font has no such table.
+    pub fn font_table(&self, font_key: &FK, tag: [u8; 4]) -> Result<Option<Vec<u8>>, FontError> {
+        let font_face = try!(self.font_face(font_key));
+        unsafe {
+            let font_table = try!(FontTable::new(&font_face, tag));
+            Ok(font_table.map(|font_table| font_table.bytes().to_vec()))
+        }
+    }
+
+    /// Returns the glyph that the given font uses for a base character followed by a variation
+    /// selector, such as an emoji presentation selector (U+FE0E/U+FE0F) or an ideographic
+    /// variation selector.
+    /// 
+    /// This consults the format 14 subtable of the `cmap` table. `Ok(None)` means that the font
+    /// has no special glyph for the sequence, and the default mapping of the base character, as
+    /// returned by `load_glyph_indices_for_characters`, should be used.
+    pub fn glyph_for_variation_sequence(&self, font_key: &FK, base: u32, selector: u32)
+                                        -> Result<Option<u16>, FontError> {
+        let cmap_table = match try!(self.font_table(font_key, tables::CMAP)) {
+            None => return Ok(None),
+            Some(cmap_table) => cmap_table,
+        };
+        Ok(tables::variation_sequence_glyph(&cmap_table, base, selector))
+    }
+
+    /// Returns the weight, stretch, and style of the given font, in the same terms as the
+    /// arguments of `add_system_font_styled`.
+    /// 
+    /// For fonts loaded from a collection, these are the values that DirectWrite resolved when it
+    /// matched the font. For fonts loaded from a file, they're read from the `OS/2` table.
+    pub fn font_style_attributes(&self, font_key: &FK)
+                                 -> Result<(u16, u8, FontStyle), FontError> {
+        let loaded_font_face = try!(self.loaded_font_face(font_key));
+        unsafe {
+            if let Some(ref font) = loaded_font_face.font {
+                return Ok(((**font).GetWeight().0 as u16,
+                           (**font).GetStretch().0 as u8,
+                           font_style((**font).GetStyle())))
+            }
+
+            let os_2_table = try!(FontTable::new(&loaded_font_face.font_face, tables::OS_2));
+            let os_2_style = os_2_table.and_then(|os_2_table| {
+                tables::os_2_style(os_2_table.bytes())
+            });
+            match os_2_style {
+                None => Ok((400, 5, FontStyle::Normal)),
+                Some((weight, stretch, selection)) => {
+                    let style = if selection & tables::OS_2_FS_SELECTION_OBLIQUE != 0 {
+                        FontStyle::Oblique
+                    } else if selection & tables::OS_2_FS_SELECTION_ITALIC != 0 {
+                        FontStyle::Italic
+                    } else {
+                        FontStyle::Normal
+                    };
+                    Ok((weight, stretch as u8, style))
+                }
+            }
+        }
+    }
+
+    /// Returns the class of the given glyph, read from the font's `GDEF` table.
+    /// 
+    /// If the font has no `GDEF` table, or the table doesn't classify the glyph,
+    /// `GlyphClass::Unknown` is returned.
+    pub fn glyph_class(&self, font_key: &FK, glyph_index: u16) -> Result<GlyphClass, FontError> {
+        let gdef_table = match try!(self.font_table(font_key, tables::GDEF)) {
+            None => return Ok(GlyphClass::Unknown),
+            Some(gdef_table) => gdef_table,
+        };
+        Ok(match tables::glyph_class(&gdef_table, glyph_index) {
+            Some(tables::GDEF_CLASS_BASE) => GlyphClass::Base,
+            Some(tables::GDEF_CLASS_LIGATURE) => GlyphClass::Ligature,
+            Some(tables::GDEF_CLASS_MARK) => GlyphClass::Mark,
+            Some(tables::GDEF_CLASS_COMPONENT) => GlyphClass::Component,
+            _ => GlyphClass::Unknown,
+        })
+    }
+
+    /// Returns the positions at which to place the text cursor inside the given ligature glyph,
+    /// such as the two positions that divide `ffi` into its three letters.
+    /// 
+    /// The positions are read from the ligature caret list of the font's `GDEF` table and are in
+    /// pixels from the pen position, in the order the font lists them. If the font doesn't
+    /// define carets for the glyph, the list is empty, and callers typically divide the advance
+    /// evenly instead.
+    pub fn ligature_carets(&self, font_instance: &FontInstance<FK>, glyph_index: u16)
+                           -> Result<Vec<f32>, FontError> {
+        let (font_face, font_metrics) = try!(self.instance_font_face_and_metrics(font_instance));
+        unsafe {
+            let gdef_table = match try!(FontTable::new(&font_face, tables::GDEF)) {
+                None => return Ok(vec![]),
+                Some(gdef_table) => gdef_table,
+            };
+            let carets = tables::ligature_carets(gdef_table.bytes(), glyph_index).unwrap_or(vec![]);
+            let scale = font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
+            Ok(carets.into_iter().map(|caret| caret as f32 * scale).collect())
+        }
+    }
+
+    /// Returns the names of the given font, read from its `name` table.
+    /// 
+    /// For fonts loaded from a collection, names are picked for `locale`, such as `ja-JP`, in the
+    /// same way as in `system_font_families`. For fonts loaded from a file, US English names are
+    /// preferred when the font localizes them. Names that the font doesn't contain are `None`.
+    pub fn font_names(&self, font_key: &FK, locale: &str) -> Result<FontNames, FontError> {
+        let loaded_font_face = try!(self.loaded_font_face(font_key));
+        if let Some(ref font) = loaded_font_face.font {
+            unsafe {
+                let postscript_name =
+                    try!(informational_string(font,
+                                              DWRITE_INFORMATIONAL_STRING_POSTSCRIPT_NAME,
+                                              locale));
+                let full_name = try!(informational_string(font,
+                                                          DWRITE_INFORMATIONAL_STRING_FULL_NAME,
+                                                          locale));
+                let family_name = match try!(informational_string(
+                        font,
+                        DWRITE_INFORMATIONAL_STRING_PREFERRED_FAMILY_NAMES,
+                        locale)) {
+                    Some(family_name) => Some(family_name),
+                    None => {
+                        try!(informational_string(font,
+                                                  DWRITE_INFORMATIONAL_STRING_WIN32_FAMILY_NAMES,
+                                                  locale))
+                    }
+                };
+                return Ok(FontNames {
+                    postscript_name: postscript_name,
+                    full_name: full_name,
+                    family_name: family_name,
+                })
+            }
+        }
+
+        let name_table = match try!(self.font_table(font_key, tables::NAME)) {
+            None => {
+                return Ok(FontNames {
+                    postscript_name: None,
+                    full_name: None,
+                    family_name: None,
+                })
+            }
+            Some(name_table) => name_table,
+        };
+
+        // Prefer the typographic family name, which groups more than four styles together, like
+        // DirectWrite does.
+        let family_name = tables::name(&name_table, tables::NAME_ID_TYPOGRAPHIC_FAMILY).or_else(|| {
+            tables::name(&name_table, tables::NAME_ID_FAMILY)
+        });
+        Ok(FontNames {
+            postscript_name: tables::name(&name_table, tables::NAME_ID_POSTSCRIPT_NAME),
+            full_name: tables::name(&name_table, tables::NAME_ID_FULL_NAME),
+            family_name: family_name,
+        })
+    }
+
+    /// Returns every character that the font maps to the given glyph, in ascending order.
+    /// 
+    /// This is the inverse of `load_glyph_indices_for_characters`, computed from the font's
+    /// `cmap` table. Several characters can map to the same glyph; if none do (for example,
+    /// because the glyph is only reachable through ligature substitution), the list is empty.
+    pub fn characters_for_glyph(&self, font_key: &FK, glyph_index: u16)
+                                -> Result<Vec<u32>, FontError> {
+        match try!(self.font_table(font_key, tables::CMAP)) {
+            None => Ok(vec![]),
+            Some(cmap_table) => {
+                Ok(tables::characters_for_glyph(&cmap_table, glyph_index).unwrap_or(vec![]))
+            }
+        }
+    }
+
+    /// Returns the axes of the given variable font.
+    /// 
+    /// If the font isn't variable, the list is empty. If the system doesn't support variable
+    /// fonts (prior to Windows 10 Creators Update), `FontError::Unsupported` is returned.
+    pub fn variation_axes(&self, font_key: &FK) -> Result<Vec<VariationAxis>, FontError> {
+        unsafe {
+            let font_face = try!(self.font_face(font_key));
+            let font_resource = try!(font_resource(&font_face));
+
+            let axis_count = (**font_resource).GetFontAxisCount();
+            let mut default_values: Vec<DWRITE_FONT_AXIS_VALUE> =
+                vec![mem::zeroed(); axis_count as usize];
+            let result = (**font_resource).GetDefaultFontAxisValues(default_values.as_mut_ptr(),
+                                                                    axis_count);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let mut ranges: Vec<DWRITE_FONT_AXIS_RANGE> = vec![mem::zeroed(); axis_count as usize];
+            let result = (**font_resource).GetFontAxisRanges(ranges.as_mut_ptr(), axis_count);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+
+            Ok(default_values.iter().zip(ranges.iter()).map(|(default_value, range)| {
+                VariationAxis {
+                    tag: font_tag(range.axisTag),
+                    min_value: range.minValue,
+                    default_value: default_value.value,
+                    max_value: range.maxValue,
+                }
+            }).collect())
+        }
+    }
+
+    /// Returns the named instances that the designer of the given variable font declared in its
+    /// `fvar` table.
+    /// 
+    /// If the font isn't variable, the list is empty. If the system doesn't support variable
+    /// fonts (prior to Windows 10 Creators Update), `FontError::Unsupported` is returned.
+    pub fn named_instances(&self, font_key: &FK) -> Result<Vec<NamedInstance>, FontError> {
+        unsafe {
+            let font_face = try!(self.font_face(font_key));
+
+            // Make sure that the system can actually instantiate what we return.
+            try!(font_resource(&font_face));
+
+            // DirectWrite only exposes named instances through font sets, which are tied to
+            // system font collections, so read them straight out of `fvar`.
+            let fvar_table = match try!(FontTable::new(&font_face, tables::FVAR)) {
+                None => return Ok(vec![]),
+                Some(fvar_table) => fvar_table,
+            };
+            let name_table = try!(FontTable::new(&font_face, tables::NAME));
+
+            let tags = tables::fvar_axis_tags(fvar_table.bytes()).unwrap_or(vec![]);
+            let instances = tables::fvar_instances(fvar_table.bytes()).unwrap_or(vec![]);
+            Ok(instances.into_iter().map(|instance| {
+                let name = name_table.as_ref().and_then(|name_table| {
+                    tables::name(name_table.bytes(), instance.subfamily_name_id)
+                });
+                NamedInstance {
+                    name: name.unwrap_or(String::new()),
+                    coordinates: tags.iter().zip(instance.coordinates.iter()).map(|(&tag, &value)| {
+                        FontVariation {
+                            tag: tag,
+                            value: value,
+                        }
+                    }).collect(),
+                }
+            }).collect())
+        }
+    }
+
+    fn font_face_and_metrics(&self, font_key: &FK)
+                             -> Result<(PathfinderComPtr<IDWriteFontFace>, DWRITE_FONT_METRICS),
+                                       FontError> {
+        self.loaded_font_face(font_key).map(|loaded_font_face| {
+            (loaded_font_face.font_face.clone(), loaded_font_face.font_metrics)
+        })
+    }
+
+    fn instance_font_face(&self, font_instance: &FontInstance<FK>)
+                          -> Result<PathfinderComPtr<IDWriteFontFace>, FontError> {
+        self.loaded_instance_font_face(font_instance).map(|loaded_font_face| {
+            loaded_font_face.font_face
+        })
+    }
+
+    fn instance_font_face_and_metrics(&self, font_instance: &FontInstance<FK>)
+                                      -> Result<(PathfinderComPtr<IDWriteFontFace>,
+                                                 DWRITE_FONT_METRICS),
+                                                FontError> {
+        self.loaded_instance_font_face(font_instance).map(|loaded_font_face| {
+            (loaded_font_face.font_face, loaded_font_face.font_metrics)
+        })
+    }
+
+    // Returns the face for the given instance, creating it first if the instance selects a
+    // position along the axes of a variable font.
+    fn loaded_instance_font_face(&self, font_instance: &FontInstance<FK>)
+                                 -> Result<LoadedFontFace, FontError> {
+        let loaded_font_face = try!(self.loaded_font_face(&font_instance.font_key));
+        if font_instance.variations.is_empty() {
+            return Ok((*loaded_font_face).clone())
+        }
+
+        let cache_key = (font_instance.font_key.clone(), font_instance.variations.clone());
+        if let Some(variable_font_face) = self.dwrite_variable_font_faces.borrow().get(&cache_key) {
+            return Ok((*variable_font_face).clone())
+        }
+
+        unsafe {
+            let font_resource = try!(font_resource(&loaded_font_face.font_face));
+
+            // Axes that the instance doesn't mention keep the values of the face it was loaded
+            // from, rather than snapping back to the defaults of the font resource. Otherwise,
+            // for example, varying only `wdth` on a system face loaded as bold would lose the
+            // weight, and the advances that DirectWrite interpolates from `HVAR` and `VVAR` would
+            // be those of the regular weight.
+            let mut axis_values = try!(font_axis_values(&loaded_font_face.font_face));
+            for variation in &font_instance.variations {
+                let axis_tag = dwrite_tag(variation.tag);
+                match axis_values.iter_mut().find(|axis_value| axis_value.axisTag == axis_tag) {
+                    Some(axis_value) => axis_value.value = variation.to_f32(),
+                    None => {
+                        axis_values.push(DWRITE_FONT_AXIS_VALUE {
+                            axisTag: axis_tag,
+                            value: variation.to_f32(),
+                        })
+                    }
+                }
+            }
+
+            let mut variable_font_face = ptr::null_mut();
+            let result = (**font_resource).CreateFontFace(DWRITE_FONT_SIMULATIONS_NONE,
+                                                          axis_values.as_ptr(),
+                                                          axis_values.len() as UINT32,
+                                                          &mut variable_font_face);
+            if !winerror::SUCCEEDED(result) {
+                return Err(FontError::QueryFailed(result))
+            }
+            let variable_font_face =
+                PathfinderComPtr::new(variable_font_face as *mut IDWriteFontFace);
+
+            // Variation can change the font-wide metrics, so measure the new face.
+            let variable_font_face = LoadedFontFace::new(variable_font_face,
+                                                         loaded_font_face.font.clone());
+            self.dwrite_variable_font_faces.borrow_mut().insert(cache_key,
+                                                                variable_font_face.clone());
+            Ok(variable_font_face)
+        }
+    }
+
+    fn dwrite_font_metrics(&self, font_instance: &FontInstance<FK>)
+                           -> Result<DWRITE_FONT_METRICS, FontError> {
+        self.loaded_instance_font_face(font_instance).map(|loaded_font_face| {
+            loaded_font_face.font_metrics
+        })
+    }
+
+    /// Returns the number of font units per em in the given font.
+    pub fn units_per_em(&self, font_key: &FK) -> Result<u16, FontError> {
+        self.font_face_and_metrics(font_key).map(|(_, font_metrics)| {
+            font_metrics.designUnitsPerEm
+        })
+    }
+
+    /// Returns the number of pixels per font unit at the size of the given font instance.
+    /// 
+    /// Multiply a value in font units by this to get the value in pixels.
+    pub fn scale_factor(&self, font_instance: &FontInstance<FK>) -> Result<f32, FontError> {
+        self.units_per_em(&font_instance.font_key).map(|units_per_em| {
+            font_instance.size.to_f32_px() / units_per_em as f32
+        })
+    }
+
+    /// Returns the number of font units per em, despite the name.
+    #[deprecated(note = "returns units per em; use `units_per_em` or `scale_factor` instead")]
+    #[inline]
+    pub fn pixels_per_unit(&self, font_instance: &FontInstance<FK>) -> Result<f32, FontError> {
+        self.units_per_em(&font_instance.font_key).map(|units_per_em| units_per_em as f32)
+    }
+
+    pub fn x_height(&self, font_instance: &FontInstance<FK>) -> Result<u32, FontError> {
+        self.dwrite_font_metrics(font_instance).map(|m| m.xHeight as u32)
+    }
+    pub fn cap_height(&self, font_instance: &FontInstance<FK>) -> Result<u32, FontError> {
+        self.dwrite_font_metrics(font_instance).map(|m| m.capHeight as u32)
+    }
+}
+
+// A font face that can be shared between the threads of the Rayon pool. As with `FontContext`,
+// this is sound because DirectWrite font faces are free-threaded.
+#[cfg(feature = "parallel")]
+struct SharedFontFace(PathfinderComPtr<IDWriteFontFace>);
+
+#[cfg(feature = "parallel")]
+unsafe impl Send for SharedFontFace {}
+#[cfg(feature = "parallel")]
+unsafe impl Sync for SharedFontFace {}
+
+// Registers a font file loader with a factory, and unregisters it again when dropped.
+struct FontFileLoaderRegistration {
+    factory: PathfinderComPtr<IDWriteFactory>,
+    loader: PathfinderComPtr<PathfinderFontFileLoader>,
+}
+
+impl FontFileLoaderRegistration {
+    unsafe fn new(factory: &PathfinderComPtr<IDWriteFactory>,
+                  loader: PathfinderComPtr<PathfinderFontFileLoader>)
+                  -> Result<FontFileLoaderRegistration, HRESULT> {
+        let result = (**factory).RegisterFontFileLoader(*loader as *mut IDWriteFontFileLoader);
+        if !winerror::SUCCEEDED(result) {
+            return Err(result)
+        }
+        Ok(FontFileLoaderRegistration {
+            factory: factory.clone(),
+            loader: loader,
+        })
+    }
+}
+
+impl Drop for FontFileLoaderRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            (**self.factory).UnregisterFontFileLoader(*self.loader as *mut IDWriteFontFileLoader);
+        }
+    }
+}
+
+// Registers a font collection loader with a factory, and unregisters it again when dropped.
+struct FontCollectionLoaderRegistration {
+    factory: PathfinderComPtr<IDWriteFactory>,
+    loader: PathfinderComPtr<PathfinderFontCollectionLoader>,
+}
+
+impl FontCollectionLoaderRegistration {
+    unsafe fn new(factory: &PathfinderComPtr<IDWriteFactory>,
+                  loader: PathfinderComPtr<PathfinderFontCollectionLoader>)
+                  -> Result<FontCollectionLoaderRegistration, HRESULT> {
+        let result = (**factory).RegisterFontCollectionLoader(
+            *loader as *mut IDWriteFontCollectionLoader);
+        if !winerror::SUCCEEDED(result) {
+            return Err(result)
+        }
+        Ok(FontCollectionLoaderRegistration {
+            factory: factory.clone(),
+            loader: loader,
+        })
+    }
+}
+
+impl Drop for FontCollectionLoaderRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            (**self.factory).UnregisterFontCollectionLoader(
+                *self.loader as *mut IDWriteFontCollectionLoader);
+        }
+    }
+}
+
+// A view of one of the OpenType tables of a font face. DirectWrite keeps the table mapped until
+// it's released again when this object is dropped.
+struct FontTable {
+    font_face: PathfinderComPtr<IDWriteFontFace>,
+    data: *const u8,
+    size: usize,
+    context: *mut c_void,
+}
+
+impl FontTable {
+    // Returns `None` if the font has no table with the given tag.
+    unsafe fn new(font_face: &PathfinderComPtr<IDWriteFontFace>, tag: [u8; 4])
+                  -> Result<Option<FontTable>, FontError> {
+        let (mut data, mut size, mut context, mut exists) = (ptr::null(), 0, ptr::null_mut(), 0);
+        let result = (**font_face).TryGetFontTable(dwrite_tag(tag),
+                                                   &mut data,
+                                                   &mut size,
+                                                   &mut context,
+                                                   &mut exists);
+        if !winerror::SUCCEEDED(result) {
+            return Err(FontError::QueryFailed(result))
+        }
+        if exists == FALSE {
+            return Ok(None)
+        }
+
+        Ok(Some(FontTable {
+            font_face: (*font_face).clone(),
+            data: data as *const u8,
+            size: size as usize,
+            context: context,
+        }))
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.data, self.size)
+        }
+    }
+}
+
+impl Drop for FontTable {
+    fn drop(&mut self) {
+        unsafe {
+            (**self.font_face).ReleaseFontTable(self.context)
+        }
+    }
+}
+
+// A glyph that has been drawn in white onto a bitmap render target, along with the black box it
+// was cropped to. The pixels stay valid for as long as the render target is alive.
+struct DrawnGlyph {
+    render_target: PathfinderComPtr<IDWriteBitmapRenderTarget>,
+    // The top-down BGRX pixels of the whole render target.
+    pixels: *const u8,
+    stride: usize,
+    // The top left corner of the black box within the render target.
+    origin: Point2D<usize>,
+    dimensions: GlyphDimensions,
+}
+
+impl DrawnGlyph {
+    // Converts row `y` of the black box to coverage in the format for the given mode. `dest` must
+    // be exactly one row long.
+    fn copy_row(&self, y: usize, mode: RasterizationMode, dest: &mut [u8]) {
+        let width = self.dimensions.size.width as usize;
+        let src_row = unsafe {
+            slice::from_raw_parts(self.pixels.offset(((self.origin.y + y) * self.stride +
+                                                      self.origin.x * 4) as isize),
+                                  width * 4)
+        };
+        match mode {
+            RasterizationMode::Aliased | RasterizationMode::Grayscale => {
+                for (dest, src) in dest.iter_mut().zip(src_row.chunks(4)) {
+                    *dest = ((src[0] as u32 + src[1] as u32 + src[2] as u32) / 3) as u8
+                }
+            }
+            RasterizationMode::Subpixel => {
+                for (dest, src) in dest.chunks_mut(3).zip(src_row.chunks(4)) {
+                    dest[0] = src[2];
+                    dest[1] = src[1];
+                    dest[2] = src[0];
+                }
+            }
+        }
+    }
+}
+
+// Finds the DirectWrite script ID for the given ISO 15924 script code.
+// 
+// DirectWrite numbers scripts consecutively and only maps them to codes through their
+// properties, so this searches the IDs until it finds a match or runs out of scripts.
+unsafe fn dwrite_script(text_analyzer: &PathfinderComPtr<IDWriteTextAnalyzer>, script: [u8; 4])
+                        -> Result<UINT16, FontError> {
+    if script == *b"Zyyy" {
+        return Ok(0)
+    }
+
+    let mut text_analyzer_1: *mut IDWriteTextAnalyzer1 = ptr::null_mut();
+    let result = (**text_analyzer).QueryInterface(&IID_IDWriteTextAnalyzer1,
+                                                  &mut text_analyzer_1 as *mut *mut _ as
+                                                  *mut *mut c_void);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::Unsupported)
+    }
+    let text_analyzer_1 = PathfinderComPtr::new(text_analyzer_1);
+
+    let iso_script_code = dwrite_tag(script);
+    for script_id in 0..u16::MAX {
+        let script_analysis = DWRITE_SCRIPT_ANALYSIS {
+            script: script_id,
+            shapes: DWRITE_SCRIPT_SHAPES_DEFAULT,
+        };
+        let mut script_properties: DWRITE_SCRIPT_PROPERTIES = mem::zeroed();
+        let result = (**text_analyzer_1).GetScriptProperties(script_analysis,
+                                                             &mut script_properties);
+        if !winerror::SUCCEEDED(result) {
+            break
+        }
+        if script_properties.isoScriptCode == iso_script_code {
+            return Ok(script_id)
+        }
+    }
+    Err(FontError::InvalidArgument)
+}
+
+// Converts the style of a DirectWrite font to ours.
+#[inline]
+fn font_style(style: DWRITE_FONT_STYLE) -> FontStyle {
+    if style == DWRITE_FONT_STYLE_ITALIC {
+        FontStyle::Italic
+    } else if style == DWRITE_FONT_STYLE_OBLIQUE {
+        FontStyle::Oblique
+    } else {
+        FontStyle::Normal
+    }
+}
+
+// The format of the coverage that the native rasterizer produces in the given mode.
+#[inline]
+fn rasterization_pixel_format(mode: RasterizationMode) -> PixelFormat {
+    match mode {
+        RasterizationMode::Aliased | RasterizationMode::Grayscale => PixelFormat::A8,
+        RasterizationMode::Subpixel => PixelFormat::Rgb24,
+    }
+}
+
+// Converts a table or axis tag as it appears in the font file to the little-endian form that
+// DirectWrite uses (`DWRITE_MAKE_OPENTYPE_TAG`).
+#[inline]
+fn dwrite_tag(tag: [u8; 4]) -> UINT32 {
+    ((tag[3] as UINT32) << 24) | ((tag[2] as UINT32) << 16) | ((tag[1] as UINT32) << 8) |
+        (tag[0] as UINT32)
+}
+
+// Returns the number of UTF-16 code units needed to encode the given string.
+fn utf16_length(text: &str) -> UINT32 {
+    text.chars().map(|ch| ch.len_utf16() as UINT32).sum()
+}
+
+// Converts the key of an in-memory font file to the bytes that identify it to DirectWrite. The key
+// of a collection is the concatenation of the keys of the files it contains.
+#[inline]
+fn font_file_key_bytes(font_file_key: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = (font_file_key >> (index * 8)) as u8
+    }
+    bytes
+}
+
+// Splits the key of a collection into the keys of the in-memory font files that it contains.
+fn font_file_keys_from_collection_key(collection_key: &[u8]) -> Option<Vec<u64>> {
+    if collection_key.is_empty() || collection_key.len() % 8 != 0 {
+        return None
+    }
+    collection_key.chunks(8).map(font_file_key_from_bytes).collect()
+}
+
+// The inverse of `font_file_key_bytes`.
+#[inline]
+fn font_file_key_from_bytes(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() != 8 {
+        return None
+    }
+    Some(bytes.iter().enumerate().fold(0, |key, (index, &byte)| {
+        key | ((byte as u64) << (index * 8))
+    }))
+}
+
+// Returns the string for the given locale from the given set of localized strings. If there's no
+// string for that locale, this falls back to a string for another region of the same language,
+// then to the US English string, and then to the first string.
+unsafe fn localized_string(strings: &PathfinderComPtr<IDWriteLocalizedStrings>, locale: &str)
+                           -> Result<String, FontError> {
+    let count = (**strings).GetCount();
+    let language = locale.split('-').next().unwrap_or("");
+    let index = match find_locale_name(strings, locale) {
+        Some(index) => index,
+        None => {
+            match (0..count).find(|&index| {
+                match localized_string_locale(strings, index) {
+                    Some(string_locale) => {
+                        string_locale.split('-').next().unwrap_or("")
+                                     .eq_ignore_ascii_case(language)
+                    }
+                    None => false,
+                }
+            }) {
+                Some(index) => index,
+                None => find_locale_name(strings, DEFAULT_LOCALE_NAME).unwrap_or(0),
+            }
+        }
+    };
+    if index >= count {
+        return Ok(String::new())
+    }
+
+    let mut length = 0;
+    let result = (**strings).GetStringLength(index, &mut length);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::QueryFailed(result))
+    }
+    let mut buffer = vec![0; length as usize + 1];
+    let result = (**strings).GetString(index, buffer.as_mut_ptr(), buffer.len() as UINT32);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::QueryFailed(result))
+    }
+    Ok(String::from_utf16_lossy(&buffer[..(length as usize)]))
+}
+
+// Returns the index of the string for exactly the given locale, ignoring case.
+unsafe fn find_locale_name(strings: &PathfinderComPtr<IDWriteLocalizedStrings>, locale: &str)
+                           -> Option<UINT32> {
+    let locale_name = match WideCString::from_str(locale) {
+        Ok(locale_name) => locale_name,
+        Err(_) => return None,
+    };
+    let (mut index, mut exists) = (0, FALSE);
+    let result = (**strings).FindLocaleName(locale_name.as_ptr(), &mut index, &mut exists);
+    if !winerror::SUCCEEDED(result) || exists == FALSE {
+        return None
+    }
+    Some(index)
+}
+
+// Returns the locale name of the string at the given index.
+unsafe fn localized_string_locale(strings: &PathfinderComPtr<IDWriteLocalizedStrings>,
+                                  index: UINT32)
+                                  -> Option<String> {
+    let mut length = 0;
+    if !winerror::SUCCEEDED((**strings).GetLocaleNameLength(index, &mut length)) {
+        return None
+    }
+    let mut buffer = vec![0; length as usize + 1];
+    let result = (**strings).GetLocaleName(index, buffer.as_mut_ptr(), buffer.len() as UINT32);
+    if !winerror::SUCCEEDED(result) {
+        return None
+    }
+    Some(String::from_utf16_lossy(&buffer[..(length as usize)]))
+}
+
+// Returns the given informational string of the font in the given locale, as `localized_string`
+// picks it, or `None` if the font doesn't have that string.
+unsafe fn informational_string(font: &PathfinderComPtr<IDWriteFont>,
+                               string_id: DWRITE_INFORMATIONAL_STRING_ID,
+                               locale: &str)
+                               -> Result<Option<String>, FontError> {
+    let (mut strings, mut exists) = (ptr::null_mut(), FALSE);
+    let result = (**font).GetInformationalStrings(string_id, &mut strings, &mut exists);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::QueryFailed(result))
+    }
+    if exists == FALSE || strings.is_null() {
+        return Ok(None)
+    }
+    let strings = PathfinderComPtr::new(strings);
+    localized_string(&strings, locale).map(Some)
+}
+
+#[cfg(feature = "embedded-bitmaps")]
+// Converts premultiplied BGRA pixels to RGBA pixels with straight alpha.
+fn unpremultiply_bgra_to_rgba(bgra: &[u8]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(bgra.len());
+    for pixel in bgra.chunks(4) {
+        if pixel.len() < 4 {
+            break
+        }
+        let (b, g, r, a) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32, pixel[3] as u32);
+        if a == 0 {
+            rgba.extend_from_slice(&[0, 0, 0, 0]);
+            continue
+        }
+        rgba.extend_from_slice(&[
+            (r * 255 / a).min(255) as u8,
+            (g * 255 / a).min(255) as u8,
+            (b * 255 / a).min(255) as u8,
+            a as u8,
+        ]);
+    }
+    rgba
+}
+
+// Converts a tag in DirectWrite's little-endian form back to the form that appears in the font
+// file.
+#[inline]
+fn font_tag(tag: UINT32) -> [u8; 4] {
+    [tag as u8, (tag >> 8) as u8, (tag >> 16) as u8, (tag >> 24) as u8]
+}
+
+// Returns the resource that variable instances of the given face can be created from.
+//
+// This requires `IDWriteFontFace5`, which is only available on Windows 10 Creators Update and
+// later; on older systems this fails with `FontError::Unsupported`.
+unsafe fn font_resource(font_face: &PathfinderComPtr<IDWriteFontFace>)
+                        -> Result<PathfinderComPtr<IDWriteFontResource>, FontError> {
+    let mut font_face_5: *mut IDWriteFontFace5 = ptr::null_mut();
+    let result = (**font_face).QueryInterface(&IID_IDWriteFontFace5,
+                                              &mut font_face_5 as *mut *mut _ as *mut *mut c_void);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::Unsupported)
+    }
+    let font_face_5 = PathfinderComPtr::new(font_face_5);
+
+    let mut font_resource = ptr::null_mut();
+    let result = (**font_face_5).GetFontResource(&mut font_resource);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::QueryFailed(result))
+    }
+    Ok(PathfinderComPtr::new(font_resource))
+}
+
+// Returns the position of the given face along each axis of its font resource.
+//
+// Like `font_resource`, this requires `IDWriteFontFace5`.
+unsafe fn font_axis_values(font_face: &PathfinderComPtr<IDWriteFontFace>)
+                           -> Result<Vec<DWRITE_FONT_AXIS_VALUE>, FontError> {
+    let mut font_face_5: *mut IDWriteFontFace5 = ptr::null_mut();
+    let result = (**font_face).QueryInterface(&IID_IDWriteFontFace5,
+                                              &mut font_face_5 as *mut *mut _ as *mut *mut c_void);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::Unsupported)
+    }
+    let font_face_5 = PathfinderComPtr::new(font_face_5);
+
+    let axis_count = (**font_face_5).GetFontAxisValueCount();
+    let mut axis_values: Vec<DWRITE_FONT_AXIS_VALUE> = vec![mem::zeroed(); axis_count as usize];
+    let result = (**font_face_5).GetFontAxisValues(axis_values.as_mut_ptr(), axis_count);
+    if !winerror::SUCCEEDED(result) {
+        return Err(FontError::QueryFailed(result))
+    }
+    Ok(axis_values)
+}
+
+// Converts a rendering mode other than `RenderingMode::Recommended`, which has to be looked up
+// for the font face, to the DirectWrite equivalent.
+fn dwrite_rendering_mode(rendering_mode: RenderingMode) -> DWRITE_RENDERING_MODE {
+    match rendering_mode {
+        RenderingMode::Recommended => DWRITE_RENDERING_MODE_DEFAULT,
+        RenderingMode::GdiClassic => DWRITE_RENDERING_MODE_GDI_CLASSIC,
+        RenderingMode::GdiNatural => DWRITE_RENDERING_MODE_GDI_NATURAL,
+        RenderingMode::Natural => DWRITE_RENDERING_MODE_NATURAL,
+        RenderingMode::NaturalSymmetric => DWRITE_RENDERING_MODE_NATURAL_SYMMETRIC,
+        RenderingMode::Outline => DWRITE_RENDERING_MODE_OUTLINE,
+    }
+}
+
+// Finds the face whose index within its font file matches `font_index`.
+//
+// DirectWrite groups the faces of a collection into families and sorts each family by style, so
+// the position of a face in the family list doesn't necessarily match its index in the `.ttc`.
+// Instead, we walk every family and compare against the index that the face itself reports.
+// Simulated (synthetic bold/oblique) fonts are skipped, since they don't correspond to a real
+// face in the file.
+unsafe fn find_font_face_in_collection(font_collection: &PathfinderComPtr<IDWriteFontCollection>,
+                                       font_index: u32)
+                                       -> Result<(PathfinderComPtr<IDWriteFont>,
+                                                  PathfinderComPtr<IDWriteFontFace>),
+                                                 FontError> {
+    let fonts_and_faces = try!(fonts_and_faces_in_collection(font_collection));
+    match fonts_and_faces.into_iter().find(|&(_, ref font_face)| {
+        (**font_face).GetIndex() == font_index
+    }) {
+        Some(font_and_face) => Ok(font_and_face),
+        None => Err(FontError::FontIndexOutOfRange(font_index)),
+    }
+}
+
+// Returns every font in every family of the collection, along with its face, skipping simulated
+// fonts.
+unsafe fn fonts_and_faces_in_collection(font_collection: &PathfinderComPtr<IDWriteFontCollection>)
+                                        -> Result<Vec<(PathfinderComPtr<IDWriteFont>,
+                                                       PathfinderComPtr<IDWriteFontFace>)>,
+                                                  FontError> {
+    let mut fonts_and_faces = vec![];
+    let family_count = (**font_collection).GetFontFamilyCount();
+    for family_index in 0..family_count {
+        let mut font_family = ptr::null_mut();
+        let result = (**font_collection).GetFontFamily(family_index, &mut font_family);
+        if !winerror::SUCCEEDED(result) {
+            return Err(FontError::FontFileLoadFailed(result))
+        }
+        let font_family = PathfinderComPtr::new(font_family);
 
+        for font_index_in_family in 0..(**font_family).GetFontCount() {
             let mut font = ptr::null_mut();
-            // let result = (**font_family).GetFont(0, &mut font);
-            let result = (**font_family).GetFirstMatchingFont(
-                DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL,
-                &mut font);
+            let result = (**font_family).GetFont(font_index_in_family, &mut font);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
             let font = PathfinderComPtr::new(font);
+            if (**font).GetSimulations() != DWRITE_FONT_SIMULATIONS_NONE {
+                continue
+            }
 
             let mut font_face = ptr::null_mut();
             let result = (**font).CreateFontFace(&mut font_face);
             if !winerror::SUCCEEDED(result) {
-                return Err(())
+                return Err(FontError::FontFileLoadFailed(result))
             }
-            let font_face = PathfinderComPtr::new(font_face);
-
-            self.dwrite_font_faces.insert((*font_key).clone(), font_face);
-            Ok(())
+            fonts_and_faces.push((font, PathfinderComPtr::new(font_face)))
         }
     }
+    Ok(fonts_and_faces)
+}
 
-    /// Unloads the font with the given font key from memory.
-    /// 
-    /// If the font isn't loaded, does nothing.
-    #[inline]
-    pub fn delete_font(&mut self, font_key: &FK) {
-        self.dwrite_font_faces.remove(font_key);
+// Returns the key of the in-memory font file that backs the given face, if it's backed by exactly
+// one file from our loader.
+unsafe fn font_face_file_key(font_face: &PathfinderComPtr<IDWriteFontFace>) -> Option<u64> {
+    let mut file_count = 0;
+    let result = (**font_face).GetFiles(&mut file_count, ptr::null_mut());
+    if !winerror::SUCCEEDED(result) || file_count != 1 {
+        return None
     }
+    let mut font_file = ptr::null_mut();
+    let result = (**font_face).GetFiles(&mut file_count, &mut font_file);
+    if !winerror::SUCCEEDED(result) {
+        return None
+    }
+    let font_file = PathfinderComPtr::new(font_file);
 
-    /// Returns the dimensions of the given glyph in the given font.
-    /// 
-    /// If `exact` is true, then the raw outline extents as specified by the font designer are
-    /// returned. These may differ from the extents when rendered on screen, because some font
-    /// libraries (including Pathfinder) apply modifications to the outlines: for example, to
-    /// dilate them for easier reading. To retrieve extents that account for these modifications,
-    /// set `exact` to false.
-    pub fn glyph_dimensions(&self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey, _exact: bool)
-                            -> Option<GlyphDimensions> {
-        unsafe {
-            let font_face = match self.dwrite_font_faces.get(&font_instance.font_key) {
-                None => return None,
-                Some(font_face) => (*font_face).clone(),
-            };
+    let (mut reference_key, mut reference_key_size) = (ptr::null(), 0);
+    let result = (**font_file).GetReferenceKey(&mut reference_key, &mut reference_key_size);
+    if !winerror::SUCCEEDED(result) {
+        return None
+    }
+    font_file_key_from_bytes(slice::from_raw_parts(reference_key as *const u8,
+                                                   reference_key_size as usize))
+}
 
-            let mut font_metrics: DWRITE_FONT_METRICS = mem::zeroed();
-            (**font_face).GetMetrics(&mut font_metrics);
+// Attaches each glyph that the `GDEF` table classifies as a mark, and that DirectWrite didn't
+// offset, to the preceding mark or base glyph. As in DirectWrite, the X component of each offset
+// points in the reading direction.
+fn position_marks(gpos: &[u8],
+                  gdef: &[u8],
+                  glyph_indices: &[UINT16],
+                  glyph_advances: &[f32],
+                  glyph_offsets: &mut [Vector2D<f32>],
+                  scale: f32,
+                  is_right_to_left: bool) {
+    let direction = if is_right_to_left { -1.0 } else { 1.0 };
 
-            let glyph_index = glyph_key.glyph_index as UINT16;
-            let mut metrics: DWRITE_GLYPH_METRICS = mem::zeroed();
+    // Find the origin of each glyph relative to the start of the run, with X pointing right.
+    let mut pen = 0.0;
+    let origins: Vec<f32> = glyph_advances.iter().map(|&advance| {
+        let origin = if is_right_to_left { pen - advance } else { pen };
+        pen += advance * direction;
+        origin
+    }).collect();
+    let position = |glyph: usize, glyph_offsets: &[Vector2D<f32>]| {
+        Point2D::new(origins[glyph] + glyph_offsets[glyph].x * direction, glyph_offsets[glyph].y)
+    };
 
-            let result = (**font_face).GetDesignGlyphMetrics(&glyph_index, 1, &mut metrics, FALSE);
-            if !winerror::SUCCEEDED(result) {
-                return None
-            }
+    let (mut base, mut previous_mark) = (None, None);
+    for glyph in 0..glyph_indices.len() {
+        let glyph_index = glyph_indices[glyph];
+        if tables::glyph_class(gdef, glyph_index) != Some(tables::GDEF_CLASS_MARK) {
+            base = Some(glyph);
+            previous_mark = None;
+            continue
+        }
+        if glyph_offsets[glyph] != Vector2D::zero() {
+            previous_mark = Some(glyph);
+            continue
+        }
 
-            let advance = metrics.advanceWidth as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            let advance_h = metrics.advanceHeight as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            let left_side_bearing = metrics.leftSideBearing as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            let right_side_bearing = metrics.rightSideBearing as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            let bottom_side_bearing = metrics.bottomSideBearing as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            let top_side_bearing = metrics.topSideBearing as f32 * font_instance.size.to_f32_px() / font_metrics.designUnitsPerEm as f32;
-            Some(GlyphDimensions {
-                advance,
-                origin: Point2D::new(left_side_bearing as _, bottom_side_bearing as _),
-                size: Size2D::new((advance - right_side_bearing - left_side_bearing) as u32,
-                                  (advance_h - bottom_side_bearing - top_side_bearing) as u32),
+        // Prefer stacking on the previous mark, and fall back to attaching to the base.
+        let attachment = previous_mark.and_then(|mark: usize| {
+            tables::mark_attachment(gpos,
+                                    tables::GPOS_LOOKUP_MARK_TO_MARK,
+                                    glyph_indices[mark],
+                                    glyph_index).map(|anchors| (mark, anchors))
+        }).or_else(|| {
+            base.and_then(|base: usize| {
+                tables::mark_attachment(gpos,
+                                        tables::GPOS_LOOKUP_MARK_TO_BASE,
+                                        glyph_indices[base],
+                                        glyph_index).map(|anchors| (base, anchors))
             })
-        }
+        });
+        previous_mark = Some(glyph);
+
+        let (target, (target_anchor, mark_anchor)) = match attachment {
+            None => continue,
+            Some(attachment) => attachment,
+        };
+        let target_anchor = Vector2D::new(target_anchor.0 as f32, target_anchor.1 as f32) * scale;
+        let mark_anchor = Vector2D::new(mark_anchor.0 as f32, mark_anchor.1 as f32) * scale;
+        let mark_position = position(target, glyph_offsets) + target_anchor - mark_anchor;
+        let offset = mark_position - Point2D::new(origins[glyph], 0.0);
+        glyph_offsets[glyph] = Vector2D::new(offset.x * direction, offset.y);
     }
+}
 
-    /// Returns a list of path commands that represent the given glyph in the given font.
-    pub fn glyph_outline(&mut self, font_instance: &FontInstance<FK>, glyph_key: &GlyphKey)
-                         -> Result<GlyphOutline, ()> {
-        unsafe {
-            let font_face = match self.dwrite_font_faces.get(&font_instance.font_key) {
-                None => return Err(()),
-                Some(font_face) => (*font_face).clone(),
-            };
+// Computes the dimensions of a glyph from its design metrics, or from its outline if `exact` is
+// false.
+unsafe fn glyph_dimensions_from_metrics(font_face: &PathfinderComPtr<IDWriteFontFace>,
+                                        geometry_sink: &PathfinderComPtr<PathfinderGeometrySink>,
+                                        font_metrics: &DWRITE_FONT_METRICS,
+                                        metrics: &DWRITE_GLYPH_METRICS,
+                                        font_size: f32,
+                                        glyph_key: &GlyphKey,
+                                        exact: bool,
+                                        dilation: f32)
+                                        -> Result<GlyphDimensions, FontError> {
+    let advance = metrics.advanceWidth as f32 * font_size / font_metrics.designUnitsPerEm as f32;
 
-            let mut metrics: DWRITE_FONT_METRICS = mem::zeroed();
-            (**font_face).GetMetrics(&mut metrics);
+    // The design metrics don't account for anything done to the outline after it leaves
+    // the font, so measure the outline that we actually hand out instead. Round out to
+    // pixel boundaries, leaving room for the subpixel offset.
+    if !exact {
+        let outline = try!(extract_glyph_outline(font_face,
+                                                 geometry_sink,
+                                                 font_size,
+                                                 glyph_key.glyph_index as UINT16,
+                                                 false,
+                                                 false,
+                                                 true));
+        let outline = dilate_outline(outline, dilation * font_size);
+        return Ok(outline_dimensions(&outline, glyph_key, advance, true))
+    }
 
-            let geometry_sink = PathfinderGeometrySink::new();
-            let glyph_index = glyph_key.glyph_index as UINT16;
+    // Recover the ink box from the side bearings. Horizontally, the bearings are measured
+    // from the pen position and the advance; vertically, they're measured from the
+    // vertical origin and the bottom of the vertical advance. Flip to Y-up so that the
+    // result matches the outline, and round out to pixel boundaries.
+    let scale = font_size / font_metrics.designUnitsPerEm as f32;
+    let ink_left = metrics.leftSideBearing;
+    let ink_right = metrics.advanceWidth as i32 - metrics.rightSideBearing;
+    let ink_top = metrics.verticalOriginY - metrics.topSideBearing;
+    let ink_bottom = metrics.verticalOriginY - metrics.advanceHeight as i32 +
+        metrics.bottomSideBearing;
 
-            let result =
-                (**font_face).GetGlyphRunOutline(font_instance.size.to_f32_px(),    
-                                                 &glyph_index,
-                                                 ptr::null(),
-                                                 ptr::null(),
-                                                 1,
-                                                 FALSE,
-                                                 FALSE,
-                                                 *geometry_sink as *mut IDWriteGeometrySink);
-            if !winerror::SUCCEEDED(result) {
-                return Err(())
-            }
+    // Glyphs without ink, such as spaces, have an empty box.
+    if ink_right <= ink_left || ink_top <= ink_bottom {
+        return Ok(GlyphDimensions {
+            advance,
+            origin: Point2D::zero(),
+            size: Size2D::zero(),
+        })
+    }
 
-            Ok(GlyphOutline {
-                events: mem::replace(&mut (**geometry_sink).commands, vec![]),
-            })
+    let subpixel_offset: f32 = glyph_key.subpixel_offset.into();
+    let lower_left = Point2D::new((ink_left as f32 * scale).floor() as i32,
+                                  (ink_bottom as f32 * scale).floor() as i32);
+    let upper_right = Point2D::new((ink_right as f32 * scale + subpixel_offset).ceil() as i32,
+                                   (ink_top as f32 * scale).ceil() as i32);
+
+    Ok(GlyphDimensions {
+        advance,
+        origin: lower_left,
+        size: Size2D::new((upper_right.x - lower_left.x) as u32,
+                          (upper_right.y - lower_left.y) as u32),
+    })
+}
+
+// Measures the bounding box of an outline, rounded out to pixel boundaries and leaving room for
+// the subpixel offset. `y_flip` says whether the outline is Y-up; the dimensions always are.
+fn outline_dimensions(outline: &GlyphOutline, glyph_key: &GlyphKey, advance: f32, y_flip: bool)
+                      -> GlyphDimensions {
+    let subpixel_offset: f32 = glyph_key.subpixel_offset.into();
+    let (lower_left, upper_right) = match control_box(&outline.events) {
+        None => (Point2D::zero(), Point2D::zero()),
+        Some((lower_left, upper_right)) => {
+            let (bottom, top) = if y_flip {
+                (lower_left.y, upper_right.y)
+            } else {
+                (-upper_right.y, -lower_left.y)
+            };
+            (Point2D::new(lower_left.x.floor() as i32, bottom.floor() as i32),
+             Point2D::new((upper_right.x + subpixel_offset).ceil() as i32, top.ceil() as i32))
         }
+    };
+    GlyphDimensions {
+        advance,
+        origin: lower_left,
+        size: Size2D::new((upper_right.x - lower_left.x) as u32,
+                          (upper_right.y - lower_left.y) as u32),
     }
+}
 
-    pub fn rasterize_glyph_with_native_rasterizer(&self,
-                                                  _font_instance: &FontInstance<FK>,
-                                                  _glyph_key: &GlyphKey,
-                                                  _exact: bool)
-                                                  -> Result<GlyphImage, ()> {
-        // TODO(pcwalton)
-        Err(())
+// Extracts the outline of a single glyph at the given em size, rotated for vertical text if
+// `sideways` is true and positioned for a right-to-left run if `is_right_to_left` is true. If
+// `y_flip` is true, the outline is Y-up; otherwise it's Y-down, as DirectWrite produces it.
+unsafe fn extract_glyph_outline(font_face: &PathfinderComPtr<IDWriteFontFace>,
+                                geometry_sink: &PathfinderComPtr<PathfinderGeometrySink>,
+                                em_size: f32,
+                                glyph_index: UINT16,
+                                sideways: bool,
+                                is_right_to_left: bool,
+                                y_flip: bool)
+                                -> Result<GlyphOutline, FontError> {
+    extract_glyph_run_outline(font_face,
+                              geometry_sink,
+                              em_size,
+                              &[glyph_index],
+                              &[],
+                              &[],
+                              sideways,
+                              is_right_to_left,
+                              y_flip)
+}
+
+// Extracts the combined outline of a run of glyphs. If `glyph_advances` or `glyph_offsets` is
+// empty, DirectWrite uses the default advances or no offsets, respectively; otherwise, each must
+// have one entry per glyph.
+unsafe fn extract_glyph_run_outline(font_face: &PathfinderComPtr<IDWriteFontFace>,
+                                    geometry_sink: &PathfinderComPtr<PathfinderGeometrySink>,
+                                    em_size: f32,
+                                    glyph_indices: &[UINT16],
+                                    glyph_advances: &[f32],
+                                    glyph_offsets: &[DWRITE_GLYPH_OFFSET],
+                                    sideways: bool,
+                                    is_right_to_left: bool,
+                                    y_flip: bool)
+                                    -> Result<GlyphOutline, FontError> {
+    if glyph_indices.is_empty() {
+        return Ok(GlyphOutline {
+            events: Arc::new(vec![]),
+            fill_mode: FillMode::Winding,
+            error: None,
+        })
     }
 
-    pub fn load_glyph_indices_for_characters(&self, font_instance: &FontInstance<FK>, characters: &[u32])
-                                     -> Result<Vec<u16>, ()> {
-        unsafe {
-            let font_face = match self.dwrite_font_faces.get(&font_instance.font_key) {
-                None => return Err(()),
-                Some(font_face) => (*font_face).clone()
-            };
+    let glyph_advances = if glyph_advances.is_empty() {
+        ptr::null()
+    } else {
+        glyph_advances.as_ptr()
+    };
+    let glyph_offsets = if glyph_offsets.is_empty() {
+        ptr::null()
+    } else {
+        glyph_offsets.as_ptr()
+    };
+    (**geometry_sink).reset(y_flip);
+    let result = (**font_face).GetGlyphRunOutline(em_size,
+                                                  glyph_indices.as_ptr(),
+                                                  glyph_advances,
+                                                  glyph_offsets,
+                                                  glyph_indices.len() as UINT32,
+                                                  if sideways { TRUE } else { FALSE },
+                                                  if is_right_to_left { TRUE } else { FALSE },
+                                                  *geometry_sink as *mut IDWriteGeometrySink);
+    // Fonts with broken glyph data, such as a composite glyph that refers to a component that
+    // doesn't exist, can fail partway through after some contours have already been sent to the
+    // sink. Keep what we have so that the glyph degrades instead of vanishing.
+    let (events, fill_mode) = (**geometry_sink).outline();
+    let error = if winerror::SUCCEEDED(result) {
+        None
+    } else if events.is_empty() {
+        return Err(FontError::QueryFailed(result))
+    } else {
+        Some(result)
+    };
 
-            let mut glyphs = Vec::with_capacity(characters.len());
-            glyphs.set_len(characters.len());
-            let result = (**font_face).GetGlyphIndices(characters.as_ptr(), characters.len() as _, glyphs.as_mut_ptr());
-            if !winerror::SUCCEEDED(result) {
-                return Err(())
-            }
+    Ok(GlyphOutline {
+        events: Arc::new(events),
+        fill_mode: fill_mode,
+        error: error,
+    })
+}
+
+// Approximates a cubic Bézier curve with quadratic ones, subdividing it until the error is within
+// `tolerance`. This is the same approach that `pathfinder_path_utils::cubic_to_quadratic` uses;
+// see Sederberg § 2.6, "Distance Between Two Bézier Curves".
+fn push_cubic_as_quadratics(events: &mut Vec<PathEvent>,
+                            cubic: &CubicBezierSegment<f32>,
+                            tolerance: f32,
+                            depth: u8) {
+    let delta_ctrl_0 = (cubic.from - cubic.ctrl1 * 3.0) + (cubic.ctrl2 * 3.0 - cubic.to);
+    let delta_ctrl_1 = (cubic.ctrl1 * 3.0 - cubic.from) + (cubic.to - cubic.ctrl2 * 3.0);
+    let max_error = f32::max(delta_ctrl_0.length(), delta_ctrl_1.length()) / 6.0;
+    if max_error > tolerance && depth < MAX_CUBIC_SUBDIVISION_DEPTH {
+        let (cubic_a, cubic_b) = cubic.split(0.5);
+        push_cubic_as_quadratics(events, &cubic_a, tolerance, depth + 1);
+        push_cubic_as_quadratics(events, &cubic_b, tolerance, depth + 1);
+        return
+    }
 
-            Ok(glyphs)
+    let approx_ctrl_0 = (cubic.ctrl1 * 3.0 - cubic.from) * 0.5;
+    let approx_ctrl_1 = (cubic.ctrl2 * 3.0 - cubic.to) * 0.5;
+    events.push(PathEvent::QuadraticTo(approx_ctrl_0.lerp(approx_ctrl_1, 0.5).to_point(),
+                                       cubic.to))
+}
+
+// Returns the parameters in (0, 1) at which a quadratic Bézier curve reaches an extremum along
+// the X and Y axes, respectively.
+fn quadratic_extrema(from: Point2D<f32>, ctrl: Point2D<f32>, to: Point2D<f32>)
+                     -> [Option<f32>; 2] {
+    fn extremum(from: f32, ctrl: f32, to: f32) -> Option<f32> {
+        let denominator = from - 2.0 * ctrl + to;
+        if denominator == 0.0 {
+            return None
+        }
+        let t = (from - ctrl) / denominator;
+        if t > 0.0 && t < 1.0 {
+            Some(t)
+        } else {
+            None
         }
     }
 
-    fn font_metrics(&self, font_instance: &FontInstance<FK>) -> Result<DWRITE_FONT_METRICS, ()> {
-        let font_face = match self.dwrite_font_faces.get(&font_instance.font_key) {
-            None => return Err(()),
-            Some(font_face) => (*font_face).clone()
-        };
+    [extremum(from.x, ctrl.x, to.x), extremum(from.y, ctrl.y, to.y)]
+}
 
-        unsafe {
-            let mut metrics: DWRITE_FONT_METRICS = mem::zeroed();
-            (**font_face).GetMetrics(&mut metrics);
-            return Ok(metrics);
+// Returns the parameters in (0, 1) at which a cubic Bézier curve reaches an extremum along the X
+// and Y axes, two per axis.
+fn cubic_extrema(from: Point2D<f32>, ctrl1: Point2D<f32>, ctrl2: Point2D<f32>, to: Point2D<f32>)
+                 -> [Option<f32>; 4] {
+    // Solves for the roots of the derivative, which is a quadratic in `t`.
+    fn extrema(from: f32, ctrl1: f32, ctrl2: f32, to: f32) -> [Option<f32>; 2] {
+        let (a, b, c) = (ctrl1 - from, ctrl2 - ctrl1, to - ctrl2);
+        let (qa, qb, qc) = (a - 2.0 * b + c, 2.0 * (b - a), a);
+        let in_range = |t: f32| if t > 0.0 && t < 1.0 { Some(t) } else { None };
+        if qa.abs() < 1e-6 {
+            if qb == 0.0 {
+                return [None, None]
+            }
+            return [in_range(-qc / qb), None]
+        }
+        let discriminant = qb * qb - 4.0 * qa * qc;
+        if discriminant < 0.0 {
+            return [None, None]
+        }
+        let discriminant_sqrt = discriminant.sqrt();
+        [in_range((-qb + discriminant_sqrt) / (2.0 * qa)),
+         in_range((-qb - discriminant_sqrt) / (2.0 * qa))]
+    }
+
+    let x = extrema(from.x, ctrl1.x, ctrl2.x, to.x);
+    let y = extrema(from.y, ctrl1.y, ctrl2.y, to.y);
+    [x[0], x[1], y[0], y[1]]
+}
+
+// Grows the given bounds, stored as lower left and upper right corners, to enclose `point`.
+#[inline]
+fn add_point_to_bounds(bounds: &mut Option<(Point2D<f32>, Point2D<f32>)>, point: Point2D<f32>) {
+    *bounds = Some(match *bounds {
+        None => (point, point),
+        Some((lower_left, upper_right)) => (lower_left.min(point), upper_right.max(point)),
+    })
+}
+
+// Returns the lower left and upper right corners of the box enclosing every endpoint and control
+// point of the given path, or `None` if the path is empty.
+fn control_box(events: &[PathEvent]) -> Option<(Point2D<f32>, Point2D<f32>)> {
+    let mut control_box = None;
+    for event in events {
+        let points = match *event {
+            PathEvent::MoveTo(to) | PathEvent::LineTo(to) => [to, to, to],
+            PathEvent::QuadraticTo(ctrl, to) => [ctrl, to, to],
+            PathEvent::CubicTo(ctrl1, ctrl2, to) => [ctrl1, ctrl2, to],
+            PathEvent::Arc(..) | PathEvent::Close => continue,
+        };
+        for point in &points {
+            add_point_to_bounds(&mut control_box, *point)
         }
     }
+    control_box
+}
 
-    pub fn pixels_per_unit(&self, font_instance: &FontInstance<FK>) -> Result<f32, ()> {
-        self.font_metrics(font_instance).map(|m| m.designUnitsPerEm as f32)
+// Moves every point of the outline, control points included, outward along the normal of the
+// outline by the given distance.
+// 
+// Fonts wind their outer contours and their holes in opposite directions, so the winding of the
+// glyph as a whole, taken from its signed area, tells which side of each contour is outside.
+fn dilate_outline(outline: GlyphOutline, amount: f32) -> GlyphOutline {
+    if amount == 0.0 {
+        return outline
     }
-    pub fn x_height(&self, font_instance: &FontInstance<FK>) -> Result<u32, ()> {
-        self.font_metrics(font_instance).map(|m| m.xHeight as u32)
+
+    let mut contours: Vec<Vec<Point2D<f32>>> = vec![];
+    for event in outline.events.iter() {
+        let points = match *event {
+            PathEvent::MoveTo(to) => {
+                contours.push(vec![to]);
+                continue
+            }
+            PathEvent::LineTo(to) => vec![to],
+            PathEvent::QuadraticTo(ctrl, to) => vec![ctrl, to],
+            PathEvent::CubicTo(ctrl1, ctrl2, to) => vec![ctrl1, ctrl2, to],
+            PathEvent::Arc(..) | PathEvent::Close => continue,
+        };
+        if contours.is_empty() {
+            contours.push(vec![])
+        }
+        contours.last_mut().unwrap().extend(points)
     }
-    pub fn cap_height(&self, font_instance: &FontInstance<FK>) -> Result<u32, ()> {
-        self.font_metrics(font_instance).map(|m| m.capHeight as u32)
+
+    let area: f32 = contours.iter().map(|contour| {
+        (0..contour.len()).map(|index| {
+            let (from, to) = (contour[index], contour[(index + 1) % contour.len()]);
+            from.x * to.y - to.x * from.y
+        }).sum::<f32>()
+    }).sum();
+    let amount = if area >= 0.0 { amount } else { -amount };
+
+    let dilated_contours: Vec<_> = contours.iter().map(|contour| {
+        dilate_contour(contour, amount)
+    }).collect();
+    let mut points = dilated_contours.iter().flat_map(|contour| contour.iter().cloned());
+    let events = outline.events.iter().map(|event| {
+        match *event {
+            PathEvent::MoveTo(_) => PathEvent::MoveTo(points.next().unwrap()),
+            PathEvent::LineTo(_) => PathEvent::LineTo(points.next().unwrap()),
+            PathEvent::QuadraticTo(..) => {
+                let ctrl = points.next().unwrap();
+                PathEvent::QuadraticTo(ctrl, points.next().unwrap())
+            }
+            PathEvent::CubicTo(..) => {
+                let ctrl1 = points.next().unwrap();
+                let ctrl2 = points.next().unwrap();
+                PathEvent::CubicTo(ctrl1, ctrl2, points.next().unwrap())
+            }
+            event @ PathEvent::Arc(..) | event @ PathEvent::Close => event,
+        }
+    }).collect();
+
+    GlyphOutline {
+        events: Arc::new(events),
+        fill_mode: outline.fill_mode,
+        error: outline.error,
     }
 }
 
+// Moves each point of a closed contour to the right of the direction of travel by the given
+// distance, mitering the corners. Miters are limited to twice the distance so that sharp corners
+// don't produce spikes.
+fn dilate_contour(contour: &[Point2D<f32>], amount: f32) -> Vec<Point2D<f32>> {
+    let point_count = contour.len();
+    (0..point_count).map(|index| {
+        let point = contour[index];
+        let prev = (1..point_count).map(|offset| contour[(index + point_count - offset) %
+                                                        point_count])
+                                   .find(|&prev| prev != point);
+        let next = (1..point_count).map(|offset| contour[(index + offset) % point_count])
+                                   .find(|&next| next != point);
+        let (prev, next) = match (prev, next) {
+            (Some(prev), Some(next)) => (prev, next),
+            _ => return point,
+        };
+
+        let (incoming, outgoing) = ((point - prev).normalize(), (next - point).normalize());
+        let normal = Vector2D::new(incoming.y + outgoing.y, -incoming.x - outgoing.x);
+        let normal_length = normal.length();
+        if normal_length < 1e-6 {
+            return point
+        }
+        point + normal / normal_length * (amount / (normal_length * 0.5).max(0.5))
+    }).collect()
+}
+
 #[repr(C)]
 struct PathfinderFontCollectionLoader {
     object: PathfinderComObject<PathfinderFontCollectionLoader>,
-    font_file: PathfinderComPtr<IDWriteFontFile>,
+    font_file_loader: PathfinderComPtr<PathfinderFontFileLoader>,
 }
 
 static PATHFINDER_FONT_COLLECTION_LOADER_VTABLE:
@@ -401,28 +4542,43 @@ impl PathfinderCoclass for PathfinderFontCollectionLoader {
 
 impl PathfinderFontCollectionLoader {
     #[inline]
-    fn new(font_file: PathfinderComPtr<IDWriteFontFile>)
+    fn new(font_file_loader: PathfinderComPtr<PathfinderFontFileLoader>)
            -> PathfinderComPtr<PathfinderFontCollectionLoader> {
         unsafe {
             PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderFontCollectionLoader {
                 object: PathfinderComObject::construct(),
-                font_file: font_file,
+                font_file_loader: font_file_loader,
             })))
         }
     }
 
+    // The collection contains the in-memory font files whose keys make up the key of the
+    // collection.
     unsafe extern "system" fn CreateEnumeratorFromKey(
             this: *mut IDWriteFontCollectionLoader,
             factory: *mut IDWriteFactory,
-            _: *const c_void,
-            _: UINT32,
+            collection_key: *const c_void,
+            collection_key_size: UINT32,
             font_file_enumerator: *mut *mut IDWriteFontFileEnumerator)
             -> HRESULT {
         let this = this as *mut PathfinderFontCollectionLoader;
+        let collection_key = slice::from_raw_parts(collection_key as *const u8,
+                                                   collection_key_size as usize);
+        let font_file_keys = match font_file_keys_from_collection_key(collection_key) {
+            Some(font_file_keys) => font_file_keys,
+            None => {
+                *font_file_enumerator = ptr::null_mut();
+                return E_INVALIDARG
+            }
+        };
 
+        // DirectWrite doesn't add a reference to the factory for us.
+        (*(factory as *mut IUnknown)).AddRef();
         let factory = PathfinderComPtr::new(factory);
-        let font_file = (*this).font_file.clone();
-        let new_font_file_enumerator = PathfinderFontFileEnumerator::new(factory, font_file);
+        let font_file_loader = (*this).font_file_loader.clone();
+        let new_font_file_enumerator = PathfinderFontFileEnumerator::new(factory,
+                                                                         font_file_loader,
+                                                                         font_file_keys);
 
         *font_file_enumerator = new_font_file_enumerator.into_raw() as
             *mut IDWriteFontFileEnumerator;
@@ -434,8 +4590,11 @@ impl PathfinderFontCollectionLoader {
 struct PathfinderFontFileEnumerator {
     object: PathfinderComObject<PathfinderFontFileEnumerator>,
     factory: PathfinderComPtr<IDWriteFactory>,
-    font_file: PathfinderComPtr<IDWriteFontFile>,
-    state: PathfinderFontFileEnumeratorState,
+    font_file_loader: PathfinderComPtr<PathfinderFontFileLoader>,
+    font_file_keys: Vec<u64>,
+    // The index of the current file in `font_file_keys`, or `None` before the first call to
+    // `MoveNext`.
+    position: Option<usize>,
 }
 
 static PATHFINDER_FONT_FILE_ENUMERATOR_VTABLE:
@@ -449,13 +4608,6 @@ static PATHFINDER_FONT_FILE_ENUMERATOR_VTABLE:
     MoveNext: PathfinderFontFileEnumerator::MoveNext,
 };
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-enum PathfinderFontFileEnumeratorState {
-    Start,
-    AtFontFile,
-    End,
-}
-
 impl PathfinderCoclass for PathfinderFontFileEnumerator {
     type InterfaceVtable = IDWriteFontFileEnumeratorVtbl;
     fn interface_guid() -> &'static GUID { &IID_IDWriteFontFileEnumerator }
@@ -466,14 +4618,17 @@ impl PathfinderCoclass for PathfinderFontFileEnumerator {
 
 impl PathfinderFontFileEnumerator {
     #[inline]
-    fn new(factory: PathfinderComPtr<IDWriteFactory>, font_file: PathfinderComPtr<IDWriteFontFile>)
+    fn new(factory: PathfinderComPtr<IDWriteFactory>,
+           font_file_loader: PathfinderComPtr<PathfinderFontFileLoader>,
+           font_file_keys: Vec<u64>)
            -> PathfinderComPtr<PathfinderFontFileEnumerator> {
         unsafe {
             PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderFontFileEnumerator {
                 object: PathfinderComObject::construct(),
                 factory: factory,
-                font_file: font_file,
-                state: PathfinderFontFileEnumeratorState::Start,
+                font_file_loader: font_file_loader,
+                font_file_keys: font_file_keys,
+                position: None,
             })))
         }
     }
@@ -482,30 +4637,34 @@ impl PathfinderFontFileEnumerator {
                                                  font_file: *mut *mut IDWriteFontFile)
                                                  -> HRESULT {
         let this = this as *mut PathfinderFontFileEnumerator;
-        if (*this).state != PathfinderFontFileEnumeratorState::AtFontFile {
-            *font_file = ptr::null_mut();
-            return E_BOUNDS
-        }
-
-        *font_file = (*this).font_file.clone().into_raw();
-        S_OK
+        let font_file_key = match (*this).position {
+            Some(position) if position < (*this).font_file_keys.len() => {
+                font_file_key_bytes((*this).font_file_keys[position])
+            }
+            _ => {
+                *font_file = ptr::null_mut();
+                return E_BOUNDS
+            }
+        };
+        (**(*this).factory).CreateCustomFontFileReference(
+            font_file_key.as_ptr() as *const c_void,
+            font_file_key.len() as UINT32,
+            *(*this).font_file_loader as *mut IDWriteFontFileLoader,
+            font_file)
     }
 
     unsafe extern "system" fn MoveNext(this: *mut IDWriteFontFileEnumerator,
                                        has_current_file: *mut BOOL)
                                        -> HRESULT {
         let this = this as *mut PathfinderFontFileEnumerator;
-        match (*this).state {
-            PathfinderFontFileEnumeratorState::Start => {
-                (*this).state = PathfinderFontFileEnumeratorState::AtFontFile;
-                *has_current_file = TRUE;
-            }
-            PathfinderFontFileEnumeratorState::AtFontFile => {
-                (*this).state = PathfinderFontFileEnumeratorState::End;
-                *has_current_file = FALSE;
-            }
-            PathfinderFontFileEnumeratorState::End => *has_current_file = FALSE,
-        }
+        let font_file_count = (*this).font_file_keys.len();
+        let position = match (*this).position {
+            None => 0,
+            Some(position) if position < font_file_count => position + 1,
+            Some(position) => position,
+        };
+        (*this).position = Some(position);
+        *has_current_file = if position < font_file_count { TRUE } else { FALSE };
         S_OK
     }
 }
@@ -513,7 +4672,8 @@ impl PathfinderFontFileEnumerator {
 #[repr(C)]
 struct PathfinderFontFileLoader {
     object: PathfinderComObject<PathfinderFontFileLoader>,
-    buffer: Arc<Vec<u8>>,
+    // The backing of each in-memory font file, by key.
+    buffers: Mutex<BTreeMap<u64, Arc<FontFileBacking>>>,
 }
 
 static PATHFINDER_FONT_FILE_LOADER_VTABLE: IDWriteFontFileLoaderVtbl = IDWriteFontFileLoaderVtbl {
@@ -533,11 +4693,11 @@ impl PathfinderCoclass for PathfinderFontFileLoader {
 
 impl PathfinderFontFileLoader {
     #[inline]
-    fn new(buffer: Arc<Vec<u8>>) -> PathfinderComPtr<PathfinderFontFileLoader> {
+    fn new() -> PathfinderComPtr<PathfinderFontFileLoader> {
         unsafe {
             PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderFontFileLoader {
                 object: PathfinderComObject::construct(),
-                buffer: buffer,
+                buffers: Mutex::new(BTreeMap::new()),
             })))
         }
     }
@@ -551,12 +4711,18 @@ impl PathfinderFontFileLoader {
         let this = this as *mut PathfinderFontFileLoader;
         let font_file_reference = slice::from_raw_parts(font_file_reference_key as *const u8,
                                                         font_file_reference_key_size as usize);
-        if font_file_reference != PATHFINDER_FONT_FILE_KEY {
-            *font_file_stream = ptr::null_mut();
-            return E_INVALIDARG
-        }
+        let buffer = font_file_key_from_bytes(font_file_reference).and_then(|font_file_key| {
+            (*this).buffers.lock().unwrap().get(&font_file_key).cloned()
+        });
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => {
+                *font_file_stream = ptr::null_mut();
+                return E_INVALIDARG
+            }
+        };
 
-        *font_file_stream = PathfinderFontFileStream::new((*this).buffer.clone()).into_raw() as
+        *font_file_stream = PathfinderFontFileStream::new(buffer).into_raw() as
             *mut IDWriteFontFileStream;
         S_OK
     }
@@ -565,8 +4731,7 @@ impl PathfinderFontFileLoader {
 #[repr(C)]
 struct PathfinderFontFileStream {
     object: PathfinderComObject<PathfinderFontFileStream>,
-    buffer: Arc<Vec<u8>>,
-    creation_time: UINT64,
+    buffer: Arc<FontFileBacking>,
 }
 
 static PATHFINDER_FONT_FILE_STREAM_VTABLE: IDWriteFontFileStreamVtbl = IDWriteFontFileStreamVtbl {
@@ -589,19 +4754,11 @@ impl PathfinderCoclass for PathfinderFontFileStream {
 
 impl PathfinderFontFileStream {
     #[inline]
-    fn new(buffer: Arc<Vec<u8>>) -> PathfinderComPtr<PathfinderFontFileStream> {
+    fn new(buffer: Arc<FontFileBacking>) -> PathfinderComPtr<PathfinderFontFileStream> {
         unsafe {
-            let mut now = FILETIME {
-                dwLowDateTime: 0,
-                dwHighDateTime: 0,
-            };
-            kernel32::GetSystemTimeAsFileTime(&mut now);
-
             PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderFontFileStream {
                 object: PathfinderComObject::construct(),
                 buffer: buffer,
-                creation_time: ((now.dwHighDateTime as UINT64) << 32) |
-                    (now.dwLowDateTime as UINT64),
             })))
         }
     }
@@ -609,15 +4766,17 @@ impl PathfinderFontFileStream {
     unsafe extern "system" fn GetFileSize(this: *mut IDWriteFontFileStream, file_size: *mut UINT64)
                                           -> HRESULT {
         let this = this as *mut PathfinderFontFileStream;
-        *file_size = (*this).buffer.len() as UINT64;
+        *file_size = (*this).buffer.len();
         S_OK
     }
 
-    unsafe extern "system" fn GetLastWriteTime(this: *mut IDWriteFontFileStream,
+    // In-memory fonts have no write time. DirectWrite takes the time into account when caching
+    // font data, so report a fixed zero time, which keeps the metadata of identical font data
+    // identical across loads.
+    unsafe extern "system" fn GetLastWriteTime(_: *mut IDWriteFontFileStream,
                                                last_write_time: *mut UINT64)
                                                -> HRESULT {
-        let this = this as *mut PathfinderFontFileStream;
-        *last_write_time = (*this).creation_time;
+        *last_write_time = 0;
         S_OK
     }
 
@@ -628,21 +4787,42 @@ impl PathfinderFontFileStream {
                                                fragment_context: *mut *mut c_void)
                                                -> HRESULT {
         let this = this as *mut PathfinderFontFileStream;
-        let buffer_length = (*this).buffer.len() as u64;
-        if file_offset > buffer_length || file_offset + fragment_size > buffer_length {
+        let buffer_length = (*this).buffer.len();
+        if file_offset > buffer_length || fragment_size > buffer_length - file_offset {
             return E_BOUNDS
         }
 
-        let ptr = (*(*this).buffer).as_ptr().offset(file_offset as isize) as *const c_void;
-        *fragment_start = ptr;
-        *fragment_context = ptr as *mut c_void;
+        // Borrowed fragments live as long as the backing, which this stream keeps alive. Owned
+        // fragments are boxed and handed to DirectWrite as the fragment context so that they can
+        // be freed in `ReleaseFileFragment`.
+        let fragment = match (*this).buffer.read_fragment(file_offset, fragment_size) {
+            Some(fragment) => fragment,
+            None => return E_FAIL,
+        };
+        if fragment.len() as u64 != fragment_size {
+            return E_FAIL
+        }
+        match fragment {
+            Cow::Borrowed(fragment) => {
+                *fragment_start = fragment.as_ptr() as *const c_void;
+                *fragment_context = ptr::null_mut();
+            }
+            Cow::Owned(fragment) => {
+                let fragment = Box::into_raw(Box::new(fragment));
+                *fragment_start = (*fragment).as_ptr() as *const c_void;
+                *fragment_context = fragment as *mut c_void;
+            }
+        }
         (*(this as *mut IUnknown)).AddRef();
         S_OK
     }
 
     unsafe extern "system" fn ReleaseFileFragment(this: *mut IDWriteFontFileStream,
-                                                  _: *mut c_void) {
+                                                  fragment_context: *mut c_void) {
         let this = this as *mut PathfinderFontFileStream;
+        if !fragment_context.is_null() {
+            drop(Box::from_raw(fragment_context as *mut Vec<u8>));
+        }
         (*(this as *mut IUnknown)).Release();
     }
 }
@@ -650,7 +4830,15 @@ impl PathfinderFontFileStream {
 #[repr(C)]
 struct PathfinderGeometrySink {
     object: PathfinderComObject<PathfinderGeometrySink>,
+    // The context reuses one sink for every extraction, so its state is behind a lock.
+    state: Mutex<GeometrySinkState>,
+}
+
+// The outline that a `PathfinderGeometrySink` is currently collecting.
+struct GeometrySinkState {
     commands: Vec<PathEvent>,
+    fill_mode: FillMode,
+    y_flip: bool,
 }
 
 static PATHFINDER_GEOMETRY_SINK_VTABLE: ID2D1SimplifiedGeometrySinkVtbl =
@@ -677,28 +4865,47 @@ impl PathfinderCoclass for PathfinderGeometrySink {
 
 impl PathfinderGeometrySink {
     #[inline]
-    fn new() -> PathfinderComPtr<PathfinderGeometrySink> {
+    fn new(y_flip: bool) -> PathfinderComPtr<PathfinderGeometrySink> {
         unsafe {
             PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderGeometrySink {
                 object: PathfinderComObject::construct(),
-                commands: vec![],
+                state: Mutex::new(GeometrySinkState {
+                    commands: vec![],
+                    fill_mode: FillMode::Winding,
+                    y_flip: y_flip,
+                }),
             })))
         }
     }
 
+    // Readies the sink for another outline, keeping the memory allocated for the commands.
+    #[inline]
+    fn reset(&self, y_flip: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.commands.clear();
+        state.fill_mode = FillMode::Winding;
+        state.y_flip = y_flip;
+    }
+
+    // Returns a copy of the outline collected so far. The commands are copied out rather than
+    // taken, so that the sink keeps its buffer for the next extraction.
+    #[inline]
+    fn outline(&self) -> (Vec<PathEvent>, FillMode) {
+        let state = self.state.lock().unwrap();
+        (state.commands.clone(), state.fill_mode)
+    }
+
     unsafe extern "system" fn AddBeziers(this: *mut IDWriteGeometrySink,
                                          beziers: *const D2D1_BEZIER_SEGMENT,
                                          beziers_count: UINT) {
         let this = this as *mut PathfinderGeometrySink;
+        let mut state = (*this).state.lock().unwrap();
         let beziers = slice::from_raw_parts(beziers, beziers_count as usize);
         for bezier in beziers {
-            let control_point_0 =
-                PathfinderGeometrySink::d2d_point_2f_to_flipped_f32_point(&bezier.point1);
-            let control_point_1 =
-                PathfinderGeometrySink::d2d_point_2f_to_flipped_f32_point(&bezier.point2);
-            let endpoint =
-                PathfinderGeometrySink::d2d_point_2f_to_flipped_f32_point(&bezier.point3);
-            (*this).commands.push(PathEvent::CubicTo(control_point_0, control_point_1, endpoint));
+            let control_point_0 = state.d2d_point_2f_to_f32_point(&bezier.point1);
+            let control_point_1 = state.d2d_point_2f_to_f32_point(&bezier.point2);
+            let endpoint = state.d2d_point_2f_to_f32_point(&bezier.point3);
+            state.commands.push(PathEvent::CubicTo(control_point_0, control_point_1, endpoint));
         }
     }
 
@@ -706,10 +4913,11 @@ impl PathfinderGeometrySink {
                                        points: *const D2D1_POINT_2F,
                                        points_count: UINT) {
         let this = this as *mut PathfinderGeometrySink;
+        let mut state = (*this).state.lock().unwrap();
         let points = slice::from_raw_parts(points, points_count as usize);
         for point in points {
-            let point = PathfinderGeometrySink::d2d_point_2f_to_flipped_f32_point(&point);
-            (*this).commands.push(PathEvent::LineTo(point))
+            let point = state.d2d_point_2f_to_f32_point(&point);
+            state.commands.push(PathEvent::LineTo(point))
         }
     }
 
@@ -717,8 +4925,9 @@ impl PathfinderGeometrySink {
                                           start_point: D2D1_POINT_2F,
                                           _: D2D1_FIGURE_BEGIN) {
         let this = this as *mut PathfinderGeometrySink;
-        let start_point = PathfinderGeometrySink::d2d_point_2f_to_flipped_f32_point(&start_point);
-        (*this).commands.push(PathEvent::MoveTo(start_point))
+        let mut state = (*this).state.lock().unwrap();
+        let start_point = state.d2d_point_2f_to_f32_point(&start_point);
+        state.commands.push(PathEvent::MoveTo(start_point))
     }
 
     unsafe extern "system" fn Close(_: *mut IDWriteGeometrySink) -> HRESULT {
@@ -729,26 +4938,153 @@ impl PathfinderGeometrySink {
                                         figure_end: D2D1_FIGURE_END) {
         let this = this as *mut PathfinderGeometrySink;
         if figure_end == D2D1_FIGURE_END_CLOSED {
-            (*this).commands.push(PathEvent::Close)
+            (*this).state.lock().unwrap().commands.push(PathEvent::Close)
         }
     }
 
-    unsafe extern "system" fn SetFillMode(_: *mut IDWriteGeometrySink, _: D2D1_FILL_MODE) {
-        // TODO(pcwalton)
+    unsafe extern "system" fn SetFillMode(this: *mut IDWriteGeometrySink,
+                                          fill_mode: D2D1_FILL_MODE) {
+        let this = this as *mut PathfinderGeometrySink;
+        (*this).state.lock().unwrap().fill_mode = if fill_mode == D2D1_FILL_MODE_ALTERNATE {
+            FillMode::EvenOdd
+        } else {
+            FillMode::Winding
+        }
     }
 
     unsafe extern "system" fn SetSegmentFlags(_: *mut IDWriteGeometrySink, _: D2D1_PATH_SEGMENT) {
         // Should be unused.
     }
+}
+
+impl GeometrySinkState {
+    // DirectWrite outlines are Y-down, so they have to be flipped to produce Y-up outlines.
+    #[inline]
+    fn d2d_point_2f_to_f32_point(&self, point: &D2D1_POINT_2F) -> Point2D<f32> {
+        if self.y_flip {
+            Point2D::new(point.x, -point.y)
+        } else {
+            Point2D::new(point.x, point.y)
+        }
+    }
+}
+
+// Supplies a single paragraph of left-to-right text to DirectWrite's text analysis, such as font
+// fallback.
+#[repr(C)]
+struct PathfinderTextAnalysisSource {
+    object: PathfinderComObject<PathfinderTextAnalysisSource>,
+    text: Vec<u16>,
+    locale_name: WideCString,
+}
+
+static PATHFINDER_TEXT_ANALYSIS_SOURCE_VTABLE: IDWriteTextAnalysisSourceVtbl =
+        IDWriteTextAnalysisSourceVtbl {
+    parent: IUnknownVtbl {
+        AddRef: PathfinderComObject::<PathfinderTextAnalysisSource>::AddRef,
+        Release: PathfinderComObject::<PathfinderTextAnalysisSource>::Release,
+        QueryInterface: PathfinderComObject::<PathfinderTextAnalysisSource>::QueryInterface,
+    },
+    GetTextAtPosition: PathfinderTextAnalysisSource::GetTextAtPosition,
+    GetTextBeforePosition: PathfinderTextAnalysisSource::GetTextBeforePosition,
+    GetParagraphReadingDirection: PathfinderTextAnalysisSource::GetParagraphReadingDirection,
+    GetLocaleName: PathfinderTextAnalysisSource::GetLocaleName,
+    GetNumberSubstitution: PathfinderTextAnalysisSource::GetNumberSubstitution,
+};
+
+impl PathfinderCoclass for PathfinderTextAnalysisSource {
+    type InterfaceVtable = IDWriteTextAnalysisSourceVtbl;
+    fn interface_guid() -> &'static GUID { &IID_IDWriteTextAnalysisSource }
+    fn vtable() -> &'static IDWriteTextAnalysisSourceVtbl {
+        &PATHFINDER_TEXT_ANALYSIS_SOURCE_VTABLE
+    }
+}
 
+impl PathfinderTextAnalysisSource {
     #[inline]
-    fn d2d_point_2f_to_flipped_f32_point(point: &D2D1_POINT_2F) -> Point2D<f32> {
-        Point2D::new(point.x, -point.y)
+    fn new(text: Vec<u16>, locale: &str) -> PathfinderComPtr<PathfinderTextAnalysisSource> {
+        let locale_name = WideCString::from_str(locale).unwrap_or_else(|_| {
+            WideCString::from_str(DEFAULT_LOCALE_NAME).unwrap()
+        });
+        unsafe {
+            PathfinderComPtr::new(Box::into_raw(Box::new(PathfinderTextAnalysisSource {
+                object: PathfinderComObject::construct(),
+                text: text,
+                locale_name: locale_name,
+            })))
+        }
+    }
+
+    unsafe extern "system" fn GetTextAtPosition(this: *mut IDWriteTextAnalysisSource,
+                                                text_position: UINT32,
+                                                text_string: *mut *const WCHAR,
+                                                text_length: *mut UINT32)
+                                                -> HRESULT {
+        let this = this as *mut PathfinderTextAnalysisSource;
+        let text = &(*this).text;
+        if text_position as usize >= text.len() {
+            *text_string = ptr::null();
+            *text_length = 0;
+        } else {
+            *text_string = text[(text_position as usize)..].as_ptr();
+            *text_length = text.len() as UINT32 - text_position;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn GetTextBeforePosition(this: *mut IDWriteTextAnalysisSource,
+                                                    text_position: UINT32,
+                                                    text_string: *mut *const WCHAR,
+                                                    text_length: *mut UINT32)
+                                                    -> HRESULT {
+        let this = this as *mut PathfinderTextAnalysisSource;
+        let text = &(*this).text;
+        if text_position == 0 || text_position as usize > text.len() {
+            *text_string = ptr::null();
+            *text_length = 0;
+        } else {
+            *text_string = text.as_ptr();
+            *text_length = text_position;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn GetParagraphReadingDirection(_: *mut IDWriteTextAnalysisSource)
+                                                           -> DWRITE_READING_DIRECTION {
+        DWRITE_READING_DIRECTION_LEFT_TO_RIGHT
+    }
+
+    // The whole text shares one locale.
+    unsafe extern "system" fn GetLocaleName(this: *mut IDWriteTextAnalysisSource,
+                                            text_position: UINT32,
+                                            text_length: *mut UINT32,
+                                            locale_name: *mut *const WCHAR)
+                                            -> HRESULT {
+        let this = this as *mut PathfinderTextAnalysisSource;
+        *text_length = ((*this).text.len() as UINT32).saturating_sub(text_position);
+        *locale_name = (*this).locale_name.as_ptr();
+        S_OK
+    }
+
+    unsafe extern "system" fn GetNumberSubstitution(
+            this: *mut IDWriteTextAnalysisSource,
+            text_position: UINT32,
+            text_length: *mut UINT32,
+            number_substitution: *mut *mut IDWriteNumberSubstitution)
+            -> HRESULT {
+        let this = this as *mut PathfinderTextAnalysisSource;
+        *text_length = ((*this).text.len() as UINT32).saturating_sub(text_position);
+        *number_substitution = ptr::null_mut();
+        S_OK
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct GlyphOutline {
-    events: Vec<PathEvent>,
+    events: Arc<Vec<PathEvent>>,
+    fill_mode: FillMode,
+    // The failure that cut extraction short, if DirectWrite only produced part of the outline.
+    error: Option<HRESULT>,
 }
 
 impl GlyphOutline {
@@ -756,4 +5092,295 @@ impl GlyphOutline {
     pub fn iter(&self) -> Cloned<Iter<PathEvent>> {
         self.events.iter().cloned()
     }
+
+    /// Returns an iterator over the contours of this outline.
+    /// 
+    /// Each contour is the slice of path commands from one `MoveTo` up to, but not including, the
+    /// next one.
+    #[inline]
+    pub fn contours(&self) -> Contours {
+        Contours {
+            events: &self.events,
+        }
+    }
+
+    /// Returns the number of path commands in this outline.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if this outline has no path commands, as is the case for a space.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the fill rule that DirectWrite requested for this outline.
+    #[inline]
+    pub fn fill_mode(&self) -> FillMode {
+        self.fill_mode
+    }
+
+    /// Returns true if DirectWrite failed partway through extracting this outline, so that it
+    /// holds only the contours produced before the failure.
+    /// 
+    /// This happens with fonts whose glyph data is damaged. The last contour of a partial outline
+    /// may be left open. Outlines that fail before producing any contours are reported as errors
+    /// instead.
+    #[inline]
+    pub fn is_partial(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Returns the `HRESULT` that DirectWrite failed with if this outline is partial.
+    #[inline]
+    pub fn partial_error(&self) -> Option<HRESULT> {
+        self.error
+    }
+
+    /// Returns the path commands of this outline with every cubic Bézier curve approximated by
+    /// one or more quadratic Bézier curves, each within `tolerance` of the original.
+    /// 
+    /// All other path commands are returned unchanged.
+    pub fn to_quadratic(&self, tolerance: f32) -> Vec<PathEvent> {
+        let mut events = Vec::with_capacity(self.events.len());
+        let (mut subpath_start, mut last_point) = (Point2D::zero(), Point2D::zero());
+        for event in self.iter() {
+            match event {
+                PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                    let cubic = CubicBezierSegment {
+                        from: last_point,
+                        ctrl1: ctrl1,
+                        ctrl2: ctrl2,
+                        to: to,
+                    };
+                    push_cubic_as_quadratics(&mut events, &cubic, tolerance, 0);
+                    last_point = to;
+                    continue
+                }
+                PathEvent::MoveTo(to) => {
+                    subpath_start = to;
+                    last_point = to;
+                }
+                PathEvent::LineTo(to) |
+                PathEvent::QuadraticTo(_, to) |
+                PathEvent::Arc(to, ..) => last_point = to,
+                PathEvent::Close => last_point = subpath_start,
+            }
+            events.push(event)
+        }
+        events
+    }
+
+    /// Returns the contours of this outline as polylines, approximating each Bézier curve with
+    /// line segments that are within `tolerance` of it.
+    /// 
+    /// Each contour starts at its `MoveTo` point. Closed contours end with a copy of their first
+    /// point, so that the last segment is explicit.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec<Point2D<f32>>> {
+        let mut contours: Vec<Vec<Point2D<f32>>> = vec![];
+        let (mut subpath_start, mut last_point) = (Point2D::zero(), Point2D::zero());
+        for event in self.iter() {
+            // Tolerate outlines that draw without moving first.
+            if contours.is_empty() {
+                if let PathEvent::MoveTo(_) = event {} else {
+                    contours.push(vec![last_point])
+                }
+            }
+            match event {
+                PathEvent::MoveTo(to) => {
+                    subpath_start = to;
+                    contours.push(vec![to])
+                }
+                PathEvent::LineTo(to) | PathEvent::Arc(to, ..) => {
+                    contours.last_mut().unwrap().push(to)
+                }
+                PathEvent::QuadraticTo(ctrl, to) => {
+                    let quadratic = QuadraticBezierSegment {
+                        from: last_point,
+                        ctrl: ctrl,
+                        to: to,
+                    };
+                    contours.last_mut().unwrap().extend(quadratic.flattened(tolerance))
+                }
+                PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                    let cubic = CubicBezierSegment {
+                        from: last_point,
+                        ctrl1: ctrl1,
+                        ctrl2: ctrl2,
+                        to: to,
+                    };
+                    contours.last_mut().unwrap().extend(cubic.flattened(tolerance))
+                }
+                PathEvent::Close => {
+                    if last_point != subpath_start {
+                        contours.last_mut().unwrap().push(subpath_start)
+                    }
+                }
+            }
+            last_point = match event {
+                PathEvent::MoveTo(to) |
+                PathEvent::LineTo(to) |
+                PathEvent::QuadraticTo(_, to) |
+                PathEvent::CubicTo(_, _, to) |
+                PathEvent::Arc(to, ..) => to,
+                PathEvent::Close => subpath_start,
+            };
+        }
+        contours
+    }
+
+    /// Returns the tight bounding rectangle of this outline.
+    /// 
+    /// Bézier curves are bounded by their extrema rather than by their control points. If the
+    /// outline has no figures, the rectangle is empty and positioned at the origin.
+    pub fn bounds(&self) -> Rect<f32> {
+        let mut bounds = None;
+        let (mut subpath_start, mut last_point) = (Point2D::zero(), Point2D::zero());
+        for event in self.iter() {
+            match event {
+                PathEvent::MoveTo(to) => {
+                    subpath_start = to;
+                    add_point_to_bounds(&mut bounds, to)
+                }
+                PathEvent::LineTo(to) | PathEvent::Arc(to, ..) => {
+                    add_point_to_bounds(&mut bounds, to)
+                }
+                PathEvent::QuadraticTo(ctrl, to) => {
+                    let quadratic = QuadraticBezierSegment {
+                        from: last_point,
+                        ctrl: ctrl,
+                        to: to,
+                    };
+                    for &t in &quadratic_extrema(last_point, ctrl, to) {
+                        if let Some(t) = t {
+                            add_point_to_bounds(&mut bounds, quadratic.sample(t))
+                        }
+                    }
+                    add_point_to_bounds(&mut bounds, to)
+                }
+                PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                    let cubic = CubicBezierSegment {
+                        from: last_point,
+                        ctrl1: ctrl1,
+                        ctrl2: ctrl2,
+                        to: to,
+                    };
+                    for &t in &cubic_extrema(last_point, ctrl1, ctrl2, to) {
+                        if let Some(t) = t {
+                            add_point_to_bounds(&mut bounds, cubic.sample(t))
+                        }
+                    }
+                    add_point_to_bounds(&mut bounds, to)
+                }
+                PathEvent::Close => {}
+            }
+            last_point = match event {
+                PathEvent::MoveTo(to) |
+                PathEvent::LineTo(to) |
+                PathEvent::QuadraticTo(_, to) |
+                PathEvent::CubicTo(_, _, to) |
+                PathEvent::Arc(to, ..) => to,
+                PathEvent::Close => subpath_start,
+            };
+        }
+
+        match bounds {
+            None => Rect::zero(),
+            Some((lower_left, upper_right)) => {
+                Rect::new(lower_left, (upper_right - lower_left).to_size())
+            }
+        }
+    }
+
+    /// Returns this outline as SVG path data, suitable for the `d` attribute of a `<path>`
+    /// element.
+    /// 
+    /// The coordinates are used as stored, so outlines are Y-up unless `FontContext::set_y_flip`
+    /// was used to turn flipping off. Since SVG is Y-down, Y-up outlines need a `scale(1, -1)`
+    /// transform to appear upright.
+    pub fn to_svg_path_data(&self) -> String {
+        let mut path_data = String::new();
+        for event in self.iter() {
+            if !path_data.is_empty() {
+                path_data.push(' ')
+            }
+            match event {
+                PathEvent::MoveTo(to) => write!(path_data, "M {} {}", to.x, to.y),
+                PathEvent::LineTo(to) | PathEvent::Arc(to, ..) => {
+                    write!(path_data, "L {} {}", to.x, to.y)
+                }
+                PathEvent::QuadraticTo(ctrl, to) => {
+                    write!(path_data, "Q {} {} {} {}", ctrl.x, ctrl.y, to.x, to.y)
+                }
+                PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                    write!(path_data,
+                           "C {} {} {} {} {} {}",
+                           ctrl1.x,
+                           ctrl1.y,
+                           ctrl2.x,
+                           ctrl2.y,
+                           to.x,
+                           to.y)
+                }
+                PathEvent::Close => write!(path_data, "Z"),
+            }.unwrap()
+        }
+        path_data
+    }
+
+    /// Converts this outline into a Lyon path.
+    pub fn into_path(self) -> Path {
+        let mut builder = Path::builder();
+        for event in self.iter() {
+            builder.path_event(event)
+        }
+        builder.build()
+    }
+}
+
+/// An iterator over the keys of the fonts loaded into a context, returned by
+/// `FontContext::loaded_fonts`.
+pub struct LoadedFonts<'a, FK> where FK: 'a {
+    font_keys: Keys<'a, FK, LoadedFontFace>,
+}
+
+impl<'a, FK> Iterator for LoadedFonts<'a, FK> {
+    type Item = &'a FK;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a FK> {
+        self.font_keys.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.font_keys.size_hint()
+    }
+}
+
+/// An iterator over the contours of a glyph outline, returned by `GlyphOutline::contours`.
+pub struct Contours<'a> {
+    events: &'a [PathEvent],
+}
+
+impl<'a> Iterator for Contours<'a> {
+    type Item = &'a [PathEvent];
+
+    fn next(&mut self) -> Option<&'a [PathEvent]> {
+        if self.events.is_empty() {
+            return None
+        }
+
+        let length = self.events.iter().skip(1).position(|event| {
+            match *event {
+                PathEvent::MoveTo(_) => true,
+                _ => false,
+            }
+        }).map(|position| position + 1).unwrap_or(self.events.len());
+        let (contour, rest) = self.events.split_at(length);
+        self.events = rest;
+        Some(contour)
+    }
 }
@@ -0,0 +1,1451 @@
+// pathfinder/font-renderer/src/directwrite/tables.rs
+//
+// Copyright © 2017 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsers for the OpenType tables that DirectWrite doesn't interpret for us.
+//! 
+//! All of these operate on the raw, big-endian table bytes and return `None` if the table is
+//! truncated or otherwise malformed.
+
+use euclid::{Point2D, Transform2D};
+use {ColorLine, ColorStop, GradientExtend, PaintNode};
+
+pub const BASE: [u8; 4] = *b"BASE";
+pub const CFF: [u8; 4] = *b"CFF ";
+pub const CFF2: [u8; 4] = *b"CFF2";
+pub const CMAP: [u8; 4] = *b"cmap";
+pub const COLR: [u8; 4] = *b"COLR";
+pub const CPAL: [u8; 4] = *b"CPAL";
+pub const FVAR: [u8; 4] = *b"fvar";
+pub const GDEF: [u8; 4] = *b"GDEF";
+pub const GLYF: [u8; 4] = *b"glyf";
+pub const GPOS: [u8; 4] = *b"GPOS";
+pub const HEAD: [u8; 4] = *b"head";
+pub const HHEA: [u8; 4] = *b"hhea";
+pub const HMTX: [u8; 4] = *b"hmtx";
+pub const KERN: [u8; 4] = *b"kern";
+pub const LOCA: [u8; 4] = *b"loca";
+pub const MATH: [u8; 4] = *b"MATH";
+pub const NAME: [u8; 4] = *b"name";
+pub const OS_2: [u8; 4] = *b"OS/2";
+pub const SVG: [u8; 4] = *b"SVG ";
+
+// The versions at the start of an OpenType font's table directory, and the tag of a collection.
+const SFNT_VERSION_TRUETYPE: u32 = 0x00010000;
+const SFNT_VERSION_APPLE_TRUETYPE: u32 = 0x74727565;  // 'true'
+const SFNT_VERSION_CFF: u32 = 0x4f54544f;  // 'OTTO'
+const TTC_TAG: [u8; 4] = *b"ttcf";
+
+// The index of the `checkSumAdjustment` field of the `head` table, in 32-bit words.
+const HEAD_CHECKSUM_ADJUSTMENT_WORD: usize = 2;
+
+// Coverage bits of a Microsoft `kern` subtable.
+const KERN_COVERAGE_HORIZONTAL: u16 = 0x0001;
+const KERN_COVERAGE_MINIMUM: u16 = 0x0002;
+const KERN_COVERAGE_CROSS_STREAM: u16 = 0x0004;
+const KERN_COVERAGE_OVERRIDE: u16 = 0x0008;
+
+// Coverage bits of an Apple `kern` subtable.
+const APPLE_KERN_COVERAGE_VERTICAL: u16 = 0x8000;
+const APPLE_KERN_COVERAGE_CROSS_STREAM: u16 = 0x4000;
+const APPLE_KERN_COVERAGE_VARIATION: u16 = 0x2000;
+
+// Glyph classes in the `GDEF` table.
+pub const GDEF_CLASS_BASE: u16 = 1;
+pub const GDEF_CLASS_LIGATURE: u16 = 2;
+pub const GDEF_CLASS_MARK: u16 = 3;
+pub const GDEF_CLASS_COMPONENT: u16 = 4;
+
+// Types of `GPOS` lookups.
+pub const GPOS_LOOKUP_MARK_TO_BASE: u16 = 4;
+pub const GPOS_LOOKUP_MARK_TO_MARK: u16 = 6;
+const GPOS_LOOKUP_EXTENSION: u16 = 9;
+
+// Platforms and encodings of the `name` table whose strings are UTF-16BE.
+const NAME_PLATFORM_UNICODE: u16 = 0;
+const NAME_PLATFORM_WINDOWS: u16 = 3;
+const NAME_ENCODING_WINDOWS_UNICODE_BMP: u16 = 1;
+const NAME_ENCODING_WINDOWS_UNICODE_FULL: u16 = 10;
+const NAME_LANGUAGE_WINDOWS_ENGLISH_US: u16 = 0x0409;
+
+// IDs of strings in the `name` table.
+pub const NAME_ID_FAMILY: u16 = 1;
+pub const NAME_ID_FULL_NAME: u16 = 4;
+pub const NAME_ID_POSTSCRIPT_NAME: u16 = 6;
+pub const NAME_ID_TYPOGRAPHIC_FAMILY: u16 = 16;
+
+// Bits of `fsSelection` in the `OS/2` table.
+pub const OS_2_FS_SELECTION_ITALIC: u16 = 0x0001;
+pub const OS_2_FS_SELECTION_USE_TYPO_METRICS: u16 = 0x0080;
+pub const OS_2_FS_SELECTION_OBLIQUE: u16 = 0x0200;
+
+// Platforms and encodings of the `cmap` subtables that map from Unicode.
+const CMAP_PLATFORM_UNICODE: u16 = 0;
+const CMAP_ENCODING_UNICODE_VARIATION_SEQUENCES: u16 = 5;
+const CMAP_PLATFORM_WINDOWS: u16 = 3;
+const CMAP_ENCODING_WINDOWS_SYMBOL: u16 = 0;
+const CMAP_ENCODING_WINDOWS_UNICODE_BMP: u16 = 1;
+const CMAP_ENCODING_WINDOWS_UNICODE_FULL: u16 = 10;
+
+// The palette index that stands for the current text color in `COLR` paints.
+const COLR_FOREGROUND_PALETTE_INDEX: u16 = 0xffff;
+
+// How deeply `COLR` paints may nest. This guards against cycles, which malformed fonts can
+// create through `PaintColrLayers`.
+const COLR_MAX_PAINT_DEPTH: u32 = 64;
+
+// The number of values in the `MathConstants` subtable: four leading integers, 51 value records,
+// and one trailing integer.
+pub const MATH_CONSTANT_COUNT: usize = 56;
+
+/// A named instance from the `fvar` table.
+pub struct FvarInstance {
+    /// The ID of the subfamily name of this instance in the `name` table.
+    pub subfamily_name_id: u16,
+    /// The 16.16 fixed-point coordinates of this instance, one per axis in `fvar` order.
+    pub coordinates: Vec<i32>,
+}
+
+#[inline]
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    match (data.get(offset), data.get(offset + 1)) {
+        (Some(&b0), Some(&b1)) => Some(((b0 as u16) << 8) | (b1 as u16)),
+        _ => None,
+    }
+}
+
+#[inline]
+pub fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+#[inline]
+fn read_u24(data: &[u8], offset: usize) -> Option<u32> {
+    match (data.get(offset), read_u16(data, offset + 1)) {
+        (Some(&high), Some(low)) => Some(((high as u32) << 16) | (low as u32)),
+        _ => None,
+    }
+}
+
+#[inline]
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    match (read_u16(data, offset), read_u16(data, offset + 2)) {
+        (Some(high), Some(low)) => Some(((high as u32) << 16) | (low as u32)),
+        _ => None,
+    }
+}
+
+#[inline]
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_i16(data, offset).map(|value| value as f32 / 16384.0)
+}
+
+#[inline]
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536.0)
+}
+
+/// Checks the table directory of the face at the given index of an OpenType font or collection.
+/// 
+/// The directory and every table it lists must lie within `data`, and each table must match its
+/// checksum. On failure, the tag of the first table that doesn't is returned, or `None` if the
+/// header or the directory itself is truncated or unrecognized. If `data` is a collection with no
+/// face at `font_index`, only the collection header is checked.
+pub fn validate_sfnt(data: &[u8], font_index: u32) -> Result<(), Option<[u8; 4]>> {
+    let mut offset_table = 0;
+    if data.get(0..4) == Some(&TTC_TAG[..]) {
+        let font_count = read_u32(data, 8).ok_or(None)?;
+        if font_index >= font_count {
+            return Ok(())
+        }
+        offset_table = read_u32(data, 12 + font_index as usize * 4).ok_or(None)? as usize;
+    }
+
+    match read_u32(data, offset_table).ok_or(None)? {
+        SFNT_VERSION_TRUETYPE | SFNT_VERSION_APPLE_TRUETYPE | SFNT_VERSION_CFF => {}
+        _ => return Err(None),
+    }
+    let table_count = read_u16(data, offset_table + 4).ok_or(None)? as usize;
+    let directory = data.get(offset_table..(offset_table + 12 + table_count * 16)).ok_or(None)?;
+
+    for table_index in 0..table_count {
+        let record = 12 + table_index * 16;
+        let tag = [directory[record],
+                   directory[record + 1],
+                   directory[record + 2],
+                   directory[record + 3]];
+        let checksum = read_u32(directory, record + 4).ok_or(Some(tag))?;
+        let offset = read_u32(directory, record + 8).ok_or(Some(tag))? as usize;
+        let length = read_u32(directory, record + 12).ok_or(Some(tag))? as usize;
+        let table = offset.checked_add(length)
+                          .and_then(|end| data.get(offset..end))
+                          .ok_or(Some(tag))?;
+        if table_checksum(table, tag == HEAD) != checksum {
+            return Err(Some(tag))
+        }
+    }
+    Ok(())
+}
+
+/// Sums a table as big-endian 32-bit words, padding the last one with zeros, to produce the
+/// checksum recorded in the table directory. The `head` table's `checkSumAdjustment` is skipped,
+/// since it depends on the checksum of the whole font.
+pub fn table_checksum(table: &[u8], is_head: bool) -> u32 {
+    let mut checksum = 0u32;
+    for (word_index, word) in table.chunks(4).enumerate() {
+        if is_head && word_index == HEAD_CHECKSUM_ADJUSTMENT_WORD {
+            continue
+        }
+        let mut bytes = [0; 4];
+        bytes[..word.len()].copy_from_slice(word);
+        checksum = checksum.wrapping_add(read_u32(&bytes, 0).unwrap());
+    }
+    checksum
+}
+
+/// Returns true if the `cmap` table has a subtable that maps from Unicode or from the Windows
+/// symbol encoding, which are the ones DirectWrite uses.
+pub fn has_unicode_subtable(cmap: &[u8]) -> bool {
+    let table_count = match read_u16(cmap, 2) {
+        None => return false,
+        Some(table_count) => table_count as usize,
+    };
+    (0..table_count).any(|record_index| {
+        let record = 4 + record_index * 8;
+        let subtable_offset = match read_u32(cmap, record + 4) {
+            None => return false,
+            Some(subtable_offset) => subtable_offset as usize,
+        };
+        if read_u16(cmap, subtable_offset).is_none() {
+            return false
+        }
+        match (read_u16(cmap, record), read_u16(cmap, record + 2)) {
+            (Some(CMAP_PLATFORM_UNICODE), Some(_)) |
+            (Some(CMAP_PLATFORM_WINDOWS), Some(CMAP_ENCODING_WINDOWS_SYMBOL)) |
+            (Some(CMAP_PLATFORM_WINDOWS), Some(CMAP_ENCODING_WINDOWS_UNICODE_BMP)) |
+            (Some(CMAP_PLATFORM_WINDOWS), Some(CMAP_ENCODING_WINDOWS_UNICODE_FULL)) => true,
+            _ => false,
+        }
+    })
+}
+
+/// Returns every character that the Unicode subtable of the `cmap` table maps to the given glyph,
+/// in ascending order.
+/// 
+/// Subtables that cover the full Unicode range are preferred over ones that only cover the Basic
+/// Multilingual Plane. Only formats 4 and 12 are understood.
+pub fn characters_for_glyph(cmap: &[u8], glyph_index: u16) -> Option<Vec<u32>> {
+    let table_count = read_u16(cmap, 2)? as usize;
+    let mut best_subtable = None;
+    for record_index in 0..table_count {
+        let record = 4 + record_index * 8;
+        let platform_id = read_u16(cmap, record)?;
+        let encoding_id = read_u16(cmap, record + 2)?;
+        let offset = read_u32(cmap, record + 4)? as usize;
+        let priority = match (platform_id, encoding_id) {
+            (CMAP_PLATFORM_WINDOWS, CMAP_ENCODING_WINDOWS_UNICODE_FULL) => 3,
+            (CMAP_PLATFORM_UNICODE, _) => 2,
+            (CMAP_PLATFORM_WINDOWS, CMAP_ENCODING_WINDOWS_UNICODE_BMP) => 1,
+            _ => continue,
+        };
+        match read_u16(cmap, offset) {
+            Some(4) | Some(12) => {}
+            _ => continue,
+        }
+        match best_subtable {
+            Some((best_priority, _)) if best_priority >= priority => {}
+            _ => best_subtable = Some((priority, offset)),
+        }
+    }
+
+    let (_, offset) = best_subtable?;
+    let subtable = cmap.get(offset..)?;
+    match read_u16(subtable, 0)? {
+        4 => characters_for_glyph_from_format_4(subtable, glyph_index),
+        _ => characters_for_glyph_from_format_12(subtable, glyph_index),
+    }
+}
+
+// Walks every segment of a format 4 (segment mapping to delta values) subtable.
+fn characters_for_glyph_from_format_4(subtable: &[u8], glyph_index: u16) -> Option<Vec<u32>> {
+    let segment_count = read_u16(subtable, 6)? as usize / 2;
+    let end_codes = 14;
+    let start_codes = end_codes + segment_count * 2 + 2;
+    let id_deltas = start_codes + segment_count * 2;
+    let id_range_offsets = id_deltas + segment_count * 2;
+
+    let mut characters = vec![];
+    for segment in 0..segment_count {
+        let end_code = read_u16(subtable, end_codes + segment * 2)?;
+        let start_code = read_u16(subtable, start_codes + segment * 2)?;
+        let id_delta = read_u16(subtable, id_deltas + segment * 2)?;
+        let id_range_offset_position = id_range_offsets + segment * 2;
+        let id_range_offset = read_u16(subtable, id_range_offset_position)? as usize;
+        if start_code > end_code {
+            continue
+        }
+
+        for character in (start_code as u32)..(end_code as u32 + 1) {
+            // The final segment maps 0xffff to the missing glyph.
+            if character == 0xffff {
+                continue
+            }
+            let mapped_glyph_index = if id_range_offset == 0 {
+                (character as u16).wrapping_add(id_delta)
+            } else {
+                let glyph_offset = id_range_offset_position + id_range_offset +
+                    (character - start_code as u32) as usize * 2;
+                match read_u16(subtable, glyph_offset)? {
+                    0 => continue,
+                    mapped_glyph_index => mapped_glyph_index.wrapping_add(id_delta),
+                }
+            };
+            if mapped_glyph_index == glyph_index {
+                characters.push(character)
+            }
+        }
+    }
+    Some(characters)
+}
+
+// Checks every group of a format 12 (segmented coverage) subtable.
+fn characters_for_glyph_from_format_12(subtable: &[u8], glyph_index: u16) -> Option<Vec<u32>> {
+    let group_count = read_u32(subtable, 12)? as usize;
+    let mut characters = vec![];
+    for group in 0..group_count {
+        let group_offset = 16 + group * 12;
+        let start_char_code = read_u32(subtable, group_offset)?;
+        let end_char_code = read_u32(subtable, group_offset + 4)?;
+        let start_glyph_id = read_u32(subtable, group_offset + 8)?;
+        let glyph_index = glyph_index as u32;
+        if end_char_code < start_char_code || glyph_index < start_glyph_id ||
+                glyph_index - start_glyph_id > end_char_code - start_char_code {
+            continue
+        }
+        characters.push(start_char_code + (glyph_index - start_glyph_id))
+    }
+    Some(characters)
+}
+
+/// Returns the glyph that the format 14 (Unicode variation sequences) subtable of the `cmap`
+/// table maps the given base character and variation selector to.
+/// 
+/// Returns `None` if the font has no such subtable, if the sequence isn't listed, or if the
+/// sequence is listed as using the default glyph of the base character.
+pub fn variation_sequence_glyph(cmap: &[u8], base: u32, selector: u32) -> Option<u16> {
+    let table_count = read_u16(cmap, 2)? as usize;
+    let subtable_offset = (0..table_count).filter_map(|record_index| {
+        let record = 4 + record_index * 8;
+        if read_u16(cmap, record)? != CMAP_PLATFORM_UNICODE ||
+                read_u16(cmap, record + 2)? != CMAP_ENCODING_UNICODE_VARIATION_SEQUENCES {
+            return None
+        }
+        read_u32(cmap, record + 4)
+    }).next()? as usize;
+    let subtable = cmap.get(subtable_offset..)?;
+    if read_u16(subtable, 0)? != 14 {
+        return None
+    }
+
+    // Variation selector records are sorted by selector, 11 bytes each.
+    let record_count = read_u32(subtable, 6)? as usize;
+    let record = (0..record_count).map(|record_index| 10 + record_index * 11).find(|&record| {
+        read_u24(subtable, record) == Some(selector)
+    })?;
+
+    // Sequences in the default UVS table use the glyph that the base character maps to, so only
+    // the non-default UVS table, whose mappings are 5 bytes each, can give a different glyph.
+    let non_default_offset = read_u32(subtable, record + 7)? as usize;
+    if non_default_offset == 0 {
+        return None
+    }
+    let non_default = subtable.get(non_default_offset..)?;
+    let mapping_count = read_u32(non_default, 0)? as usize;
+    (0..mapping_count).map(|mapping_index| 4 + mapping_index * 5).find(|&mapping| {
+        read_u24(non_default, mapping) == Some(base)
+    }).and_then(|mapping| read_u16(non_default, mapping + 3))
+}
+
+/// Returns the SVG document in the `SVG ` table that contains the given glyph, exactly as stored,
+/// which may be gzip-compressed.
+pub fn svg_document(svg: &[u8], glyph_index: u16) -> Option<&[u8]> {
+    let document_list_offset = read_u32(svg, 2)? as usize;
+    let document_list = svg.get(document_list_offset..)?;
+    let entry_count = read_u16(document_list, 0)? as usize;
+    for entry_index in 0..entry_count {
+        let entry = 2 + entry_index * 12;
+        let start_glyph_id = read_u16(document_list, entry)?;
+        let end_glyph_id = read_u16(document_list, entry + 2)?;
+        if glyph_index < start_glyph_id || glyph_index > end_glyph_id {
+            continue
+        }
+        let document_offset = read_u32(document_list, entry + 4)? as usize;
+        let document_length = read_u32(document_list, entry + 8)? as usize;
+        return document_list.get(document_offset..(document_offset + document_length))
+    }
+    None
+}
+
+/// Returns the tags of the axes in the `fvar` table, in order.
+pub fn fvar_axis_tags(fvar: &[u8]) -> Option<Vec<[u8; 4]>> {
+    let axes_offset = read_u16(fvar, 4)? as usize;
+    let axis_count = read_u16(fvar, 8)? as usize;
+    let axis_size = read_u16(fvar, 10)? as usize;
+    (0..axis_count).map(|axis_index| {
+        let axis = fvar.get((axes_offset + axis_index * axis_size)..)?.get(0..4)?;
+        Some([axis[0], axis[1], axis[2], axis[3]])
+    }).collect()
+}
+
+/// Returns the named instances in the `fvar` table, in order.
+pub fn fvar_instances(fvar: &[u8]) -> Option<Vec<FvarInstance>> {
+    let axes_offset = read_u16(fvar, 4)? as usize;
+    let axis_count = read_u16(fvar, 8)? as usize;
+    let axis_size = read_u16(fvar, 10)? as usize;
+    let instance_count = read_u16(fvar, 12)? as usize;
+    let instance_size = read_u16(fvar, 14)? as usize;
+    let instances_offset = axes_offset + axis_count * axis_size;
+    (0..instance_count).map(|instance_index| {
+        let offset = instances_offset + instance_index * instance_size;
+        let coordinates = (0..axis_count).map(|axis_index| {
+            read_u32(fvar, offset + 4 + axis_index * 4).map(|value| value as i32)
+        }).collect::<Option<Vec<_>>>()?;
+        Some(FvarInstance {
+            subfamily_name_id: read_u16(fvar, offset)?,
+            coordinates: coordinates,
+        })
+    }).collect()
+}
+
+/// Returns the string with the given ID in the `name` table.
+/// 
+/// Only Unicode strings are considered. US English is preferred if the string is localized.
+pub fn name(name: &[u8], name_id: u16) -> Option<String> {
+    let count = read_u16(name, 2)? as usize;
+    let storage_offset = read_u16(name, 4)? as usize;
+    let mut best_record = None;
+    for record_index in 0..count {
+        let record = 6 + record_index * 12;
+        let platform_id = read_u16(name, record)?;
+        let encoding_id = read_u16(name, record + 2)?;
+        let language_id = read_u16(name, record + 4)?;
+        if read_u16(name, record + 6)? != name_id {
+            continue
+        }
+        let is_unicode = platform_id == NAME_PLATFORM_UNICODE ||
+            (platform_id == NAME_PLATFORM_WINDOWS &&
+             (encoding_id == NAME_ENCODING_WINDOWS_UNICODE_BMP ||
+              encoding_id == NAME_ENCODING_WINDOWS_UNICODE_FULL));
+        if !is_unicode {
+            continue
+        }
+        best_record = Some(record);
+        if platform_id == NAME_PLATFORM_WINDOWS && language_id == NAME_LANGUAGE_WINDOWS_ENGLISH_US {
+            break
+        }
+    }
+
+    let record = best_record?;
+    let length = read_u16(name, record + 8)? as usize;
+    let offset = storage_offset + read_u16(name, record + 10)? as usize;
+    let bytes = name.get(offset..(offset + length))?;
+    let units: Vec<u16> = bytes.chunks(2).map(|unit| {
+        ((unit[0] as u16) << 8) | (*unit.get(1).unwrap_or(&0) as u16)
+    }).collect();
+    String::from_utf16(&units).ok()
+}
+
+/// Returns the total horizontal kerning adjustment between `left` and `right`, in font units.
+/// 
+/// Only format 0 subtables are consulted. Both the Microsoft and the Apple table headers are
+/// understood.
+pub fn kerning(kern: &[u8], left: u16, right: u16) -> Option<i32> {
+    let mut kerning = 0;
+    if read_u16(kern, 0)? == 0 {
+        // Microsoft header.
+        let table_count = read_u16(kern, 2)?;
+        let mut offset = 4;
+        for _ in 0..table_count {
+            let length = read_u16(kern, offset + 2)? as usize;
+            let coverage = read_u16(kern, offset + 4)?;
+            let format = coverage >> 8;
+            if format == 0 && coverage & KERN_COVERAGE_HORIZONTAL != 0 &&
+                    coverage & (KERN_COVERAGE_MINIMUM | KERN_COVERAGE_CROSS_STREAM) == 0 {
+                let value = kerning_from_format_0(kern.get(offset + 6..)?, left, right)?;
+                if coverage & KERN_COVERAGE_OVERRIDE != 0 {
+                    kerning = value as i32
+                } else {
+                    kerning += value as i32
+                }
+            }
+            if length < 6 {
+                return None
+            }
+            offset += length
+        }
+    } else {
+        // Apple header.
+        let table_count = read_u32(kern, 4)?;
+        let mut offset = 8;
+        for _ in 0..table_count {
+            let length = read_u32(kern, offset)? as usize;
+            let coverage = read_u16(kern, offset + 4)?;
+            let format = coverage & 0xff;
+            if format == 0 && coverage & (APPLE_KERN_COVERAGE_VERTICAL |
+                                          APPLE_KERN_COVERAGE_CROSS_STREAM |
+                                          APPLE_KERN_COVERAGE_VARIATION) == 0 {
+                let value = kerning_from_format_0(kern.get(offset + 8..)?, left, right)?;
+                kerning += value as i32
+            }
+            if length < 8 {
+                return None
+            }
+            offset += length
+        }
+    }
+    Some(kerning)
+}
+
+// Binary searches the sorted pair list of a format 0 subtable, starting just past the subtable
+// header.
+fn kerning_from_format_0(subtable: &[u8], left: u16, right: u16) -> Option<i16> {
+    let pair_count = read_u16(subtable, 0)? as usize;
+    let key = ((left as u32) << 16) | (right as u32);
+    let (mut low, mut high) = (0, pair_count);
+    while low < high {
+        let mid = (low + high) / 2;
+        let pair_offset = 8 + mid * 6;
+        let pair_key = read_u32(subtable, pair_offset)?;
+        if pair_key == key {
+            return read_i16(subtable, pair_offset + 4)
+        }
+        if pair_key < key {
+            low = mid + 1
+        } else {
+            high = mid
+        }
+    }
+    Some(0)
+}
+
+/// Returns every value in the `MathConstants` subtable of the `MATH` table, in the order that the
+/// OpenType specification lists them.
+/// 
+/// Device table adjustments are ignored. The result has `MATH_CONSTANT_COUNT` entries.
+pub fn math_constants(math: &[u8]) -> Option<Vec<i32>> {
+    let constants_offset = read_u16(math, 4)? as usize;
+    let constants = math.get(constants_offset..)?;
+    let mut values = Vec::with_capacity(MATH_CONSTANT_COUNT);
+    values.push(read_i16(constants, 0)? as i32);
+    values.push(read_i16(constants, 2)? as i32);
+    values.push(read_u16(constants, 4)? as i32);
+    values.push(read_u16(constants, 6)? as i32);
+    for record_index in 0..(MATH_CONSTANT_COUNT - 5) {
+        values.push(read_i16(constants, 8 + record_index * 4)? as i32);
+    }
+    values.push(read_i16(constants, 8 + (MATH_CONSTANT_COUNT - 5) * 4)? as i32);
+    Some(values)
+}
+
+/// Returns the italic correction of the given glyph from the `MATH` table, in font units, or
+/// `None` if the glyph has none.
+pub fn math_italic_correction(math: &[u8], glyph_index: u16) -> Option<i16> {
+    let glyph_info_offset = read_u16(math, 6)? as usize;
+    let glyph_info = math.get(glyph_info_offset..)?;
+    let italics_offset = read_u16(glyph_info, 0)? as usize;
+    if italics_offset == 0 {
+        return None
+    }
+    let italics = glyph_info.get(italics_offset..)?;
+    let coverage = italics.get((read_u16(italics, 0)? as usize)..)?;
+    let coverage_index = coverage_index(coverage, glyph_index)?;
+    if coverage_index >= read_u16(italics, 2)? as usize {
+        return None
+    }
+    read_i16(italics, 4 + coverage_index * 4)
+}
+
+/// Returns the anchor points that attach `mark_glyph` to `base_glyph`, as the base anchor and
+/// then the mark anchor, in font units.
+/// 
+/// `lookup_type` is `GPOS_LOOKUP_MARK_TO_BASE` or `GPOS_LOOKUP_MARK_TO_MARK`; in the latter case,
+/// `base_glyph` is the mark that `mark_glyph` stacks on. The first lookup that covers both glyphs
+/// wins. Scripts, languages, and lookup flags aren't consulted.
+pub fn mark_attachment(gpos: &[u8], lookup_type: u16, base_glyph: u16, mark_glyph: u16)
+                       -> Option<((i16, i16), (i16, i16))> {
+    gpos_subtables(gpos, lookup_type)?.iter().filter_map(|subtable| {
+        mark_attachment_from_subtable(subtable, base_glyph, mark_glyph)
+    }).next()
+}
+
+// Returns every subtable of the `GPOS` lookups of the given type, looking through extension
+// lookups.
+fn gpos_subtables(gpos: &[u8], lookup_type: u16) -> Option<Vec<&[u8]>> {
+    let lookup_list = gpos.get((read_u16(gpos, 8)? as usize)..)?;
+    let lookup_count = read_u16(lookup_list, 0)? as usize;
+    let mut subtables = vec![];
+    for lookup_index in 0..lookup_count {
+        let lookup = lookup_list.get((read_u16(lookup_list, 2 + lookup_index * 2)? as usize)..)?;
+        let this_lookup_type = read_u16(lookup, 0)?;
+        let subtable_count = read_u16(lookup, 4)? as usize;
+        for subtable_index in 0..subtable_count {
+            let subtable = lookup.get((read_u16(lookup, 6 + subtable_index * 2)? as usize)..)?;
+            if this_lookup_type == GPOS_LOOKUP_EXTENSION {
+                if read_u16(subtable, 2)? == lookup_type {
+                    subtables.push(subtable.get((read_u32(subtable, 4)? as usize)..)?)
+                }
+            } else if this_lookup_type == lookup_type {
+                subtables.push(subtable)
+            }
+        }
+    }
+    Some(subtables)
+}
+
+// Mark-to-base and mark-to-mark subtables share a layout, so this reads either.
+fn mark_attachment_from_subtable(subtable: &[u8], base_glyph: u16, mark_glyph: u16)
+                                 -> Option<((i16, i16), (i16, i16))> {
+    if read_u16(subtable, 0)? != 1 {
+        return None
+    }
+    let mark_coverage = subtable.get((read_u16(subtable, 2)? as usize)..)?;
+    let base_coverage = subtable.get((read_u16(subtable, 4)? as usize)..)?;
+    let mark_index = coverage_index(mark_coverage, mark_glyph)?;
+    let base_index = coverage_index(base_coverage, base_glyph)?;
+    let mark_class_count = read_u16(subtable, 6)? as usize;
+    let mark_array = subtable.get((read_u16(subtable, 8)? as usize)..)?;
+    let base_array = subtable.get((read_u16(subtable, 10)? as usize)..)?;
+    if mark_index >= read_u16(mark_array, 0)? as usize ||
+            base_index >= read_u16(base_array, 0)? as usize {
+        return None
+    }
+
+    let mark_record = 2 + mark_index * 4;
+    let mark_class = read_u16(mark_array, mark_record)? as usize;
+    if mark_class >= mark_class_count {
+        return None
+    }
+    let mark_anchor_offset = read_u16(mark_array, mark_record + 2)? as usize;
+    let mark_anchor = anchor(mark_array.get(mark_anchor_offset..)?)?;
+
+    // A null offset means that the base has no anchor for this class of mark.
+    let base_record = 2 + (base_index * mark_class_count + mark_class) * 2;
+    let base_anchor_offset = read_u16(base_array, base_record)? as usize;
+    if base_anchor_offset == 0 {
+        return None
+    }
+    let base_anchor = anchor(base_array.get(base_anchor_offset..)?)?;
+
+    Some((base_anchor, mark_anchor))
+}
+
+// Reads the coordinates of an anchor table. Every format starts with them; the contour point and
+// device table adjustments of the later formats are ignored.
+fn anchor(anchor: &[u8]) -> Option<(i16, i16)> {
+    Some((read_i16(anchor, 2)?, read_i16(anchor, 4)?))
+}
+
+/// Returns the class of the given glyph from the glyph class definition table of the `GDEF`
+/// table, or `None` if the table has no glyph class definitions.
+/// 
+/// Glyphs that the definitions don't mention have class 0.
+pub fn glyph_class(gdef: &[u8], glyph_index: u16) -> Option<u16> {
+    let class_def_offset = read_u16(gdef, 4)? as usize;
+    if class_def_offset == 0 {
+        return None
+    }
+    class_def(gdef.get(class_def_offset..)?, glyph_index)
+}
+
+/// Returns the caret positions that divide the given ligature glyph into its components, from
+/// the ligature caret list of the `GDEF` table, in font units along the X axis.
+/// 
+/// Carets that are defined by a contour point of the glyph are skipped. `None` is returned if the
+/// table has no caret list or the list doesn't include the glyph.
+pub fn ligature_carets(gdef: &[u8], glyph_index: u16) -> Option<Vec<i16>> {
+    let lig_caret_list_offset = read_u16(gdef, 8)? as usize;
+    if lig_caret_list_offset == 0 {
+        return None
+    }
+    let lig_caret_list = gdef.get(lig_caret_list_offset..)?;
+    let coverage = lig_caret_list.get((read_u16(lig_caret_list, 0)? as usize)..)?;
+    let coverage_index = coverage_index(coverage, glyph_index)?;
+    if coverage_index >= read_u16(lig_caret_list, 2)? as usize {
+        return None
+    }
+    let lig_glyph_offset = read_u16(lig_caret_list, 4 + coverage_index * 2)? as usize;
+    let lig_glyph = lig_caret_list.get(lig_glyph_offset..)?;
+
+    let caret_count = read_u16(lig_glyph, 0)? as usize;
+    let mut carets = Vec::with_capacity(caret_count);
+    for caret_index in 0..caret_count {
+        let caret_value_offset = read_u16(lig_glyph, 2 + caret_index * 2)? as usize;
+        let caret_value = lig_glyph.get(caret_value_offset..)?;
+        match read_u16(caret_value, 0)? {
+            // Format 3 adds a device table, which we ignore.
+            1 | 3 => carets.push(read_i16(caret_value, 2)?),
+            _ => {}
+        }
+    }
+    Some(carets)
+}
+
+// Looks up the class of the given glyph in a class definition table.
+fn class_def(class_def: &[u8], glyph_index: u16) -> Option<u16> {
+    match read_u16(class_def, 0)? {
+        1 => {
+            let start_glyph = read_u16(class_def, 2)?;
+            let glyph_count = read_u16(class_def, 4)?;
+            if glyph_index < start_glyph || glyph_index - start_glyph >= glyph_count {
+                return Some(0)
+            }
+            read_u16(class_def, 6 + (glyph_index - start_glyph) as usize * 2)
+        }
+        2 => {
+            let range_count = read_u16(class_def, 2)? as usize;
+            let (mut low, mut high) = (0, range_count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let range = 4 + mid * 6;
+                let start_glyph = read_u16(class_def, range)?;
+                let end_glyph = read_u16(class_def, range + 2)?;
+                if glyph_index < start_glyph {
+                    high = mid
+                } else if glyph_index > end_glyph {
+                    low = mid + 1
+                } else {
+                    return read_u16(class_def, range + 4)
+                }
+            }
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+// Returns the index of the given glyph in a coverage table, or `None` if it isn't covered.
+fn coverage_index(coverage: &[u8], glyph_index: u16) -> Option<usize> {
+    match read_u16(coverage, 0)? {
+        1 => {
+            let glyph_count = read_u16(coverage, 2)? as usize;
+            let (mut low, mut high) = (0, glyph_count);
+            while low < high {
+                let mid = (low + high) / 2;
+                let glyph = read_u16(coverage, 4 + mid * 2)?;
+                if glyph == glyph_index {
+                    return Some(mid)
+                }
+                if glyph < glyph_index {
+                    low = mid + 1
+                } else {
+                    high = mid
+                }
+            }
+            None
+        }
+        2 => {
+            let range_count = read_u16(coverage, 2)? as usize;
+            for range_index in 0..range_count {
+                let range = 4 + range_index * 6;
+                let start_glyph = read_u16(coverage, range)?;
+                let end_glyph = read_u16(coverage, range + 2)?;
+                if glyph_index >= start_glyph && glyph_index <= end_glyph {
+                    let start_coverage_index = read_u16(coverage, range + 4)? as usize;
+                    return Some(start_coverage_index + (glyph_index - start_glyph) as usize)
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Returns the position of the given baseline for the given script along the horizontal axis of
+/// the `BASE` table, in font units.
+/// 
+/// If the script has no record, the `DFLT` script is consulted instead.
+pub fn baseline(base: &[u8], baseline_tag: [u8; 4], script: [u8; 4]) -> Option<i16> {
+    let axis_offset = read_u16(base, 4)? as usize;
+    if axis_offset == 0 {
+        return None
+    }
+    let axis = base.get(axis_offset..)?;
+    let tag_list = axis.get((read_u16(axis, 0)? as usize)..)?;
+    let script_list = axis.get((read_u16(axis, 2)? as usize)..)?;
+
+    let tag_count = read_u16(tag_list, 0)? as usize;
+    let baseline_index = (0..tag_count).position(|tag_index| {
+        tag_list.get((2 + tag_index * 4)..(6 + tag_index * 4)) == Some(&baseline_tag[..])
+    })?;
+
+    let script_count = read_u16(script_list, 0)? as usize;
+    let find_script = |script: [u8; 4]| {
+        (0..script_count).map(|script_index| 2 + script_index * 6).find(|&record| {
+            script_list.get(record..(record + 4)) == Some(&script[..])
+        })
+    };
+    let record = find_script(script).or_else(|| find_script(*b"DFLT"))?;
+    let base_script = script_list.get((read_u16(script_list, record + 4)? as usize)..)?;
+
+    let base_values_offset = read_u16(base_script, 0)? as usize;
+    if base_values_offset == 0 {
+        return None
+    }
+    let base_values = base_script.get(base_values_offset..)?;
+    if baseline_index >= read_u16(base_values, 2)? as usize {
+        return None
+    }
+    let base_coord_offset = read_u16(base_values, 4 + baseline_index * 2)? as usize;
+    read_i16(base_values.get(base_coord_offset..)?, 2)
+}
+
+/// Returns the `ascender`, `descender`, and `lineGap` fields of the `hhea` table.
+pub fn hhea_line_metrics(hhea: &[u8]) -> Option<(i16, i16, i16)> {
+    Some((read_i16(hhea, 4)?, read_i16(hhea, 6)?, read_i16(hhea, 8)?))
+}
+
+/// Returns the advance width of the given glyph from the `hmtx` table, which needs the
+/// `numberOfHMetrics` field of the `hhea` table to be interpreted.
+/// 
+/// Glyphs past the last full metric record share its advance, as in monospaced fonts.
+pub fn advance_width(hhea: &[u8], hmtx: &[u8], glyph_index: u16) -> Option<u16> {
+    let metric_count = read_u16(hhea, 34)?;
+    if metric_count == 0 {
+        return None
+    }
+    let metric_index = glyph_index.min(metric_count - 1) as usize;
+    read_u16(hmtx, metric_index * 4)
+}
+
+/// Returns true if the given glyph in the `glyf` table is a composite, built out of references to
+/// other glyphs, rather than a simple outline. Offsets into `glyf` are read from the `loca`
+/// table, in the format that the `indexToLocFormat` field of the `head` table selects.
+/// 
+/// Glyphs without an outline, such as the space, are simple.
+pub fn is_composite_glyph(head: &[u8], loca: &[u8], glyf: &[u8], glyph_index: u16)
+                          -> Option<bool> {
+    let glyph_index = glyph_index as usize;
+    let (start, end) = match read_i16(head, 50)? {
+        0 => {
+            (read_u16(loca, glyph_index * 2)? as usize * 2,
+             read_u16(loca, glyph_index * 2 + 2)? as usize * 2)
+        }
+        _ => {
+            (read_u32(loca, glyph_index * 4)? as usize,
+             read_u32(loca, glyph_index * 4 + 4)? as usize)
+        }
+    };
+    if start == end {
+        return Some(false)
+    }
+    let number_of_contours = read_i16(glyf.get(start..end)?, 0)?;
+    Some(number_of_contours < 0)
+}
+
+/// Returns the `sTypoAscender`, `sTypoDescender`, `sTypoLineGap`, `usWinAscent`, and
+/// `usWinDescent` fields of the `OS/2` table.
+pub fn os_2_line_metrics(os_2: &[u8]) -> Option<(i16, i16, i16, u16, u16)> {
+    Some((read_i16(os_2, 68)?,
+          read_i16(os_2, 70)?,
+          read_i16(os_2, 72)?,
+          read_u16(os_2, 74)?,
+          read_u16(os_2, 76)?))
+}
+
+/// Returns the `usWeightClass`, `usWidthClass`, and `fsSelection` fields of the `OS/2` table.
+pub fn os_2_style(os_2: &[u8]) -> Option<(u16, u16, u16)> {
+    Some((read_u16(os_2, 4)?, read_u16(os_2, 6)?, read_u16(os_2, 62)?))
+}
+
+/// Returns the colors of the given palette in the `CPAL` table, as red, green, blue, and alpha
+/// components from 0.0 to 1.0.
+pub fn cpal_palette(cpal: &[u8], palette_index: u16) -> Option<Vec<[f32; 4]>> {
+    let entry_count = read_u16(cpal, 2)? as usize;
+    if palette_index >= read_u16(cpal, 4)? {
+        return None
+    }
+    let color_records = cpal.get((read_u32(cpal, 8)? as usize)..)?;
+    let first_record = read_u16(cpal, 12 + palette_index as usize * 2)? as usize;
+    (first_record..(first_record + entry_count)).map(|record_index| {
+        let record = color_records.get((record_index * 4)..(record_index * 4 + 4))?;
+        Some([record[2] as f32 / 255.0,
+              record[1] as f32 / 255.0,
+              record[0] as f32 / 255.0,
+              record[3] as f32 / 255.0])
+    }).collect()
+}
+
+/// Returns the paint graph of the given glyph from the `COLR` table, or `None` if the table is
+/// older than version 1 or has no paint for the glyph.
+/// 
+/// Palette indices are resolved against `palette`, and positions are multiplied by `scale` to
+/// convert them from font units. Variable paints are read at the default instance of the font.
+pub fn colr_paint(colr: &[u8], glyph_index: u16, palette: &[[f32; 4]], scale: f32)
+                  -> Option<PaintNode> {
+    if read_u16(colr, 0)? < 1 {
+        return None
+    }
+    let base_glyph_list_offset = read_u32(colr, 14)? as usize;
+    if base_glyph_list_offset == 0 {
+        return None
+    }
+    let base_glyph_list = colr.get(base_glyph_list_offset..)?;
+    let layer_list = match read_u32(colr, 18)? as usize {
+        0 => None,
+        layer_list_offset => Some(colr.get(layer_list_offset..)?),
+    };
+
+    let record_count = read_u32(base_glyph_list, 0)? as usize;
+    let (mut low, mut high) = (0, record_count);
+    while low < high {
+        let mid = (low + high) / 2;
+        let record = 4 + mid * 6;
+        let record_glyph_index = read_u16(base_glyph_list, record)?;
+        if record_glyph_index < glyph_index {
+            low = mid + 1
+        } else if record_glyph_index > glyph_index {
+            high = mid
+        } else {
+            let paint_offset = read_u32(base_glyph_list, record + 2)? as usize;
+            let parser = ColrPaintParser {
+                layer_list: layer_list,
+                palette: palette,
+                scale: scale,
+            };
+            return parser.paint(base_glyph_list.get(paint_offset..)?, 0)
+        }
+    }
+    None
+}
+
+// The state shared by all the paints of one `COLR` paint graph.
+struct ColrPaintParser<'a> {
+    layer_list: Option<&'a [u8]>,
+    palette: &'a [[f32; 4]],
+    scale: f32,
+}
+
+impl<'a> ColrPaintParser<'a> {
+    // Parses the paint table at the start of `paint`. Each variable format has the same layout as
+    // the static format before it, followed by variation indices that we ignore.
+    fn paint(&self, paint: &[u8], depth: u32) -> Option<PaintNode> {
+        if depth > COLR_MAX_PAINT_DEPTH {
+            return None
+        }
+        let child = |offset_offset: usize| -> Option<Box<PaintNode>> {
+            let child_offset = read_u24(paint, offset_offset)? as usize;
+            Some(Box::new(self.paint(paint.get(child_offset..)?, depth + 1)?))
+        };
+
+        let format = *paint.get(0)?;
+        match format {
+            1 => {
+                let layer_list = self.layer_list?;
+                let layer_count = *paint.get(1)? as usize;
+                let first_layer = read_u32(paint, 2)? as usize;
+                let layers = (first_layer..(first_layer + layer_count)).map(|layer_index| {
+                    let layer_offset = read_u32(layer_list, 4 + layer_index * 4)? as usize;
+                    self.paint(layer_list.get(layer_offset..)?, depth + 1)
+                }).collect::<Option<Vec<_>>>()?;
+                Some(PaintNode::Layers(layers))
+            }
+            2 | 3 => {
+                Some(PaintNode::Solid {
+                    color: self.color(read_u16(paint, 1)?)?,
+                    alpha: read_f2dot14(paint, 3)?,
+                })
+            }
+            4 | 5 => {
+                Some(PaintNode::LinearGradient {
+                    color_line: self.color_line(paint, format == 5)?,
+                    p0: self.point(paint, 4)?,
+                    p1: self.point(paint, 8)?,
+                    p2: self.point(paint, 12)?,
+                })
+            }
+            6 | 7 => {
+                Some(PaintNode::RadialGradient {
+                    color_line: self.color_line(paint, format == 7)?,
+                    center0: self.point(paint, 4)?,
+                    radius0: read_u16(paint, 8)? as f32 * self.scale,
+                    center1: self.point(paint, 10)?,
+                    radius1: read_u16(paint, 14)? as f32 * self.scale,
+                })
+            }
+            8 | 9 => {
+                // Sweep angles are biased so that they can reach a full turn.
+                Some(PaintNode::SweepGradient {
+                    color_line: self.color_line(paint, format == 9)?,
+                    center: self.point(paint, 4)?,
+                    start_angle: (read_f2dot14(paint, 8)? + 1.0) * 180.0,
+                    end_angle: (read_f2dot14(paint, 10)? + 1.0) * 180.0,
+                })
+            }
+            10 => {
+                Some(PaintNode::Glyph {
+                    glyph_index: read_u16(paint, 4)? as u32,
+                    paint: child(1)?,
+                })
+            }
+            11 => Some(PaintNode::ColrGlyph(read_u16(paint, 1)? as u32)),
+            12 | 13 => {
+                let affine = paint.get((read_u24(paint, 4)? as usize)..)?;
+                let transform = Transform2D::row_major(read_fixed(affine, 0)?,
+                                                       read_fixed(affine, 4)?,
+                                                       read_fixed(affine, 8)?,
+                                                       read_fixed(affine, 12)?,
+                                                       read_fixed(affine, 16)?,
+                                                       read_fixed(affine, 20)?);
+                self.transform(transform, child(1)?)
+            }
+            14 | 15 => {
+                let transform = Transform2D::create_translation(read_i16(paint, 4)? as f32,
+                                                                read_i16(paint, 6)? as f32);
+                self.transform(transform, child(1)?)
+            }
+            16 | 17 | 18 | 19 => {
+                let transform = Transform2D::create_scale(read_f2dot14(paint, 4)?,
+                                                          read_f2dot14(paint, 6)?);
+                let transform = if format >= 18 {
+                    around_center(transform, paint, 8)?
+                } else {
+                    transform
+                };
+                self.transform(transform, child(1)?)
+            }
+            20 | 21 | 22 | 23 => {
+                let scale = read_f2dot14(paint, 4)?;
+                let transform = Transform2D::create_scale(scale, scale);
+                let transform = if format >= 22 {
+                    around_center(transform, paint, 6)?
+                } else {
+                    transform
+                };
+                self.transform(transform, child(1)?)
+            }
+            24 | 25 | 26 | 27 => {
+                // Angles are in half turns, counterclockwise.
+                let angle = read_f2dot14(paint, 4)? * 180.0_f32.to_radians();
+                let (sin, cos) = (angle.sin(), angle.cos());
+                let transform = Transform2D::row_major(cos, sin, -sin, cos, 0.0, 0.0);
+                let transform = if format >= 26 {
+                    around_center(transform, paint, 6)?
+                } else {
+                    transform
+                };
+                self.transform(transform, child(1)?)
+            }
+            28 | 29 | 30 | 31 => {
+                let x_skew = read_f2dot14(paint, 4)? * 180.0_f32.to_radians();
+                let y_skew = read_f2dot14(paint, 6)? * 180.0_f32.to_radians();
+                let transform = Transform2D::row_major(1.0, y_skew.tan(), -x_skew.tan(), 1.0,
+                                                       0.0, 0.0);
+                let transform = if format >= 30 {
+                    around_center(transform, paint, 8)?
+                } else {
+                    transform
+                };
+                self.transform(transform, child(1)?)
+            }
+            32 => {
+                Some(PaintNode::Composite {
+                    source: child(1)?,
+                    mode: *paint.get(4)?,
+                    backdrop: child(5)?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Reads the color line that the paint's first field points to. Variable color lines have
+    // longer stops.
+    fn color_line(&self, paint: &[u8], is_variable: bool) -> Option<ColorLine> {
+        let color_line = paint.get((read_u24(paint, 1)? as usize)..)?;
+        let extend = match *color_line.get(0)? {
+            1 => GradientExtend::Repeat,
+            2 => GradientExtend::Reflect,
+            _ => GradientExtend::Pad,
+        };
+        let stop_count = read_u16(color_line, 1)? as usize;
+        let stop_size = if is_variable { 10 } else { 6 };
+        let stops = (0..stop_count).map(|stop_index| {
+            let stop = 3 + stop_index * stop_size;
+            Some(ColorStop {
+                offset: read_f2dot14(color_line, stop)?,
+                color: self.color(read_u16(color_line, stop + 2)?)?,
+                alpha: read_f2dot14(color_line, stop + 4)?,
+            })
+        }).collect::<Option<Vec<_>>>()?;
+        Some(ColorLine {
+            extend: extend,
+            stops: stops,
+        })
+    }
+
+    // Looks up a palette entry. The outer `Option` is `None` if the index is out of range; the
+    // inner one is `None` for the text color.
+    fn color(&self, palette_index: u16) -> Option<Option<[f32; 4]>> {
+        if palette_index == COLR_FOREGROUND_PALETTE_INDEX {
+            return Some(None)
+        }
+        self.palette.get(palette_index as usize).map(|&color| Some(color))
+    }
+
+    fn point(&self, paint: &[u8], offset: usize) -> Option<Point2D<f32>> {
+        Some(Point2D::new(read_i16(paint, offset)? as f32 * self.scale,
+                          read_i16(paint, offset + 2)? as f32 * self.scale))
+    }
+
+    // Wraps a paint in a transform given in font units, scaling its translation to pixels.
+    fn transform(&self, transform: Transform2D<f32>, paint: Box<PaintNode>) -> Option<PaintNode> {
+        Some(PaintNode::Transform {
+            transform: Transform2D::row_major(transform.m11,
+                                              transform.m12,
+                                              transform.m21,
+                                              transform.m22,
+                                              transform.m31 * self.scale,
+                                              transform.m32 * self.scale),
+            paint: paint,
+        })
+    }
+}
+
+// Makes a transform apply around the center point stored at the given offset of a paint, in font
+// units.
+fn around_center(transform: Transform2D<f32>, paint: &[u8], offset: usize)
+                 -> Option<Transform2D<f32>> {
+    let center_x = read_i16(paint, offset)? as f32;
+    let center_y = read_i16(paint, offset + 2)? as f32;
+    Some(Transform2D::create_translation(-center_x, -center_y)
+                     .post_mul(&transform)
+                     .post_mul(&Transform2D::create_translation(center_x, center_y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::{Point2D, Transform2D};
+    use {ColorLine, ColorStop, GradientExtend, PaintNode};
+
+    // A version 1 `COLR` table with three base glyphs: glyph 1 is a solid red, glyph 2 is a
+    // linear gradient from red to half-transparent blue, and glyph 3 is the text color
+    // translated by (20, -10).
+    static COLR_V1: [u8; 105] = [
+        // Header: version 1, no version 0 records, a base glyph list at 34, and nothing else.
+        0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 34, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0,
+        // Base glyph list: three records, with paint offsets from the start of the list.
+        0, 0, 0, 3,
+        0, 1, 0, 0, 0, 22,
+        0, 2, 0, 0, 0, 27,
+        0, 3, 0, 0, 0, 58,
+        // PaintSolid: palette index 0, alpha 1.0.
+        2, 0, 0, 0x40, 0x00,
+        // PaintLinearGradient: color line at 16, p0 = (0, 0), p1 = (100, 0), p2 = (0, 100).
+        4, 0, 0, 16, 0, 0, 0, 0, 0, 100, 0, 0, 0, 0, 0, 100,
+        // ColorLine: repeat, two stops.
+        1, 0, 2,
+        0x00, 0x00, 0, 0, 0x40, 0x00,
+        0x40, 0x00, 0, 1, 0x20, 0x00,
+        // PaintTranslate: child at 8, dx = 20, dy = -10.
+        14, 0, 0, 8, 0, 20, 0xff, 0xf6,
+        // PaintSolid: the text color, alpha 1.0.
+        2, 0xff, 0xff, 0x40, 0x00,
+    ];
+
+    static PALETTE: [[f32; 4]; 2] = [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]];
+
+    #[test]
+    fn test_colr_paint_solid() {
+        assert_eq!(super::colr_paint(&COLR_V1, 1, &PALETTE, 0.5),
+                   Some(PaintNode::Solid {
+                       color: Some([1.0, 0.0, 0.0, 1.0]),
+                       alpha: 1.0,
+                   }));
+    }
+
+    #[test]
+    fn test_colr_paint_linear_gradient() {
+        assert_eq!(super::colr_paint(&COLR_V1, 2, &PALETTE, 0.5),
+                   Some(PaintNode::LinearGradient {
+                       color_line: ColorLine {
+                           extend: GradientExtend::Repeat,
+                           stops: vec![
+                               ColorStop {
+                                   offset: 0.0,
+                                   color: Some([1.0, 0.0, 0.0, 1.0]),
+                                   alpha: 1.0,
+                               },
+                               ColorStop {
+                                   offset: 1.0,
+                                   color: Some([0.0, 0.0, 1.0, 1.0]),
+                                   alpha: 0.5,
+                               },
+                           ],
+                       },
+                       p0: Point2D::new(0.0, 0.0),
+                       p1: Point2D::new(50.0, 0.0),
+                       p2: Point2D::new(0.0, 50.0),
+                   }));
+    }
+
+    #[test]
+    fn test_colr_paint_transform() {
+        assert_eq!(super::colr_paint(&COLR_V1, 3, &PALETTE, 0.5),
+                   Some(PaintNode::Transform {
+                       transform: Transform2D::create_translation(10.0, -5.0),
+                       paint: Box::new(PaintNode::Solid {
+                           color: None,
+                           alpha: 1.0,
+                       }),
+                   }));
+    }
+
+    #[test]
+    fn test_colr_paint_missing_glyph() {
+        assert_eq!(super::colr_paint(&COLR_V1, 4, &PALETTE, 0.5), None);
+        assert_eq!(super::colr_paint(&COLR_V1[0..40], 1, &PALETTE, 0.5), None);
+    }
+
+    // Encodes a sequence of 16-bit fields. Negative values are stored in two's complement.
+    fn words(values: &[i32]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(values.len() * 2);
+        for &value in values {
+            bytes.push((value >> 8) as u8);
+            bytes.push(value as u8);
+        }
+        bytes
+    }
+
+    // A Microsoft `kern` table with one horizontal format 0 subtable, which kerns (3, 4) by -50
+    // and (5, 6) by 20.
+    fn kern() -> Vec<u8> {
+        words(&[0, 1, 0, 26, 0x0001, 2, 12, 1, 0, 3, 4, -50, 5, 6, 20])
+    }
+
+    #[test]
+    fn test_kerning() {
+        assert_eq!(super::kerning(&kern(), 3, 4), Some(-50));
+        assert_eq!(super::kerning(&kern(), 5, 6), Some(20));
+        assert_eq!(super::kerning(&kern(), 4, 3), Some(0));
+        assert_eq!(super::kerning(&kern()[0..10], 3, 4), None);
+    }
+
+    // An `fvar` table with weight and width axes and one named instance at weight 700 and width
+    // 75.
+    fn fvar() -> Vec<u8> {
+        let mut fvar = words(&[1, 0, 16, 2, 2, 20, 1, 12]);
+        fvar.extend_from_slice(b"wght");
+        fvar.extend_from_slice(&words(&[100, 0, 400, 0, 900, 0, 0, 256]));
+        fvar.extend_from_slice(b"wdth");
+        fvar.extend_from_slice(&words(&[50, 0, 100, 0, 100, 0, 0, 257]));
+        fvar.extend_from_slice(&words(&[258, 0, 700, 0, 75, 0]));
+        fvar
+    }
+
+    #[test]
+    fn test_fvar() {
+        assert_eq!(super::fvar_axis_tags(&fvar()), Some(vec![*b"wght", *b"wdth"]));
+
+        let instances = super::fvar_instances(&fvar()).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].subfamily_name_id, 258);
+        assert_eq!(instances[0].coordinates, vec![700 << 16, 75 << 16]);
+        assert!(super::fvar_instances(&fvar()[0..60]).is_none());
+    }
+
+    fn utf16_be(string: &str) -> Vec<u8> {
+        words(&string.encode_utf16().map(|unit| unit as i32).collect::<Vec<_>>())
+    }
+
+    // A `name` table with a Japanese and a US English family name on the Windows platform, and a
+    // full name only on the Macintosh platform.
+    fn name() -> Vec<u8> {
+        let mut name = words(&[
+            0, 3, 42,
+            3, 1, 0x0411, 1, 16, 0,
+            3, 1, 0x0409, 1, 12, 16,
+            1, 0, 0, 4, 3, 28,
+        ]);
+        name.extend_from_slice(&utf16_be("Goshikku"));
+        name.extend_from_slice(&utf16_be("Gothic"));
+        name.extend_from_slice(b"Mac");
+        name
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(super::name(&name(), super::NAME_ID_FAMILY), Some("Gothic".to_owned()));
+        assert_eq!(super::name(&name(), super::NAME_ID_FULL_NAME), None);
+        assert_eq!(super::name(&name(), super::NAME_ID_POSTSCRIPT_NAME), None);
+    }
+
+    // A `MATH` table whose value records hold 0, 10, 20, and so on, and which gives glyph 7 an
+    // italic correction of 35.
+    fn math() -> Vec<u8> {
+        let mut math = words(&[1, 0, 10, 224, 0]);
+        math.extend_from_slice(&words(&[80, 60, 1300, 1500]));
+        for record_index in 0..51 {
+            math.extend_from_slice(&words(&[record_index * 10, 0]));
+        }
+        math.extend_from_slice(&words(&[50]));
+        math.extend_from_slice(&words(&[8, 0, 0, 0, 8, 1, 35, 0, 1, 1, 7]));
+        math
+    }
+
+    #[test]
+    fn test_math_constants() {
+        let constants = super::math_constants(&math()).unwrap();
+        assert_eq!(constants.len(), super::MATH_CONSTANT_COUNT);
+        assert_eq!(&constants[0..6], &[80, 60, 1300, 1500, 0, 10]);
+        assert_eq!(constants[54], 500);
+        assert_eq!(constants[55], 50);
+        assert_eq!(super::math_constants(&math()[0..100]), None);
+    }
+
+    #[test]
+    fn test_math_italic_correction() {
+        assert_eq!(super::math_italic_correction(&math(), 7), Some(35));
+        assert_eq!(super::math_italic_correction(&math(), 8), None);
+    }
+
+    // A `GPOS` table with one mark-to-base lookup, which attaches mark glyph 10 at its anchor
+    // (100, -20) to base glyph 5 at its anchor (300, 500).
+    fn gpos() -> Vec<u8> {
+        words(&[
+            // Header and lookup list.
+            1, 0, 0, 0, 10,
+            1, 4,
+            // Lookup and its subtable.
+            4, 0, 1, 8,
+            1, 12, 18, 1, 24, 36,
+            // Mark coverage and base coverage.
+            1, 1, 10,
+            1, 1, 5,
+            // Mark array and the mark anchor.
+            1, 0, 6,
+            1, 100, -20,
+            // Base array and the base anchor.
+            1, 4,
+            1, 300, 500,
+        ])
+    }
+
+    #[test]
+    fn test_mark_attachment() {
+        assert_eq!(super::mark_attachment(&gpos(), super::GPOS_LOOKUP_MARK_TO_BASE, 5, 10),
+                   Some(((300, 500), (100, -20))));
+        assert_eq!(super::mark_attachment(&gpos(), super::GPOS_LOOKUP_MARK_TO_BASE, 5, 11), None);
+        assert_eq!(super::mark_attachment(&gpos(), super::GPOS_LOOKUP_MARK_TO_MARK, 5, 10), None);
+    }
+
+    // A `BASE` table whose horizontal axis puts the ideographic baseline at -120 and the Roman
+    // baseline at 0 for the default script.
+    fn base() -> Vec<u8> {
+        let mut base = words(&[1, 0, 8, 0, 4, 14, 2]);
+        base.extend_from_slice(b"ideoromn");
+        base.extend_from_slice(&words(&[1]));
+        base.extend_from_slice(b"DFLT");
+        base.extend_from_slice(&words(&[8, 6, 0, 0, 1, 2, 8, 12, 1, -120, 1, 0]));
+        base
+    }
+
+    #[test]
+    fn test_baseline() {
+        assert_eq!(super::baseline(&base(), *b"ideo", *b"DFLT"), Some(-120));
+        assert_eq!(super::baseline(&base(), *b"romn", *b"DFLT"), Some(0));
+        assert_eq!(super::baseline(&base(), *b"ideo", *b"latn"), Some(-120));
+        assert_eq!(super::baseline(&base(), *b"hang", *b"DFLT"), None);
+    }
+
+    // A `GDEF` table that classifies glyph 5 as a base and glyphs 10 and 11 as marks, and gives
+    // ligature glyph 20 a coordinate caret, a contour point caret, and a caret with a device
+    // table.
+    fn gdef() -> Vec<u8> {
+        words(&[
+            // Header.
+            1, 0, 12, 0, 28, 0,
+            // Glyph class definitions.
+            2, 2, 5, 5, 1, 10, 11, 3,
+            // Ligature caret list and its coverage.
+            6, 1, 12,
+            1, 1, 20,
+            // Ligature glyph and its caret values.
+            3, 8, 12, 16,
+            1, 400,
+            2, 3,
+            3, 800, 0,
+        ])
+    }
+
+    #[test]
+    fn test_glyph_class() {
+        assert_eq!(super::glyph_class(&gdef(), 5), Some(super::GDEF_CLASS_BASE));
+        assert_eq!(super::glyph_class(&gdef(), 10), Some(super::GDEF_CLASS_MARK));
+        assert_eq!(super::glyph_class(&gdef(), 11), Some(super::GDEF_CLASS_MARK));
+        assert_eq!(super::glyph_class(&gdef(), 7), Some(0));
+    }
+
+    // A `cmap` table with only a format 14 subtable, which maps U+2764 followed by VS1 to glyph
+    // 42 through its non-default UVS table.
+    fn cmap_14() -> Vec<u8> {
+        let mut cmap = words(&[0, 1, 0, 5, 0, 12]);
+        cmap.extend_from_slice(&words(&[14, 0, 30, 0, 1]));
+        cmap.extend_from_slice(&[0x00, 0xfe, 0x00]);
+        cmap.extend_from_slice(&words(&[0, 0, 0, 21, 0, 1]));
+        cmap.extend_from_slice(&[0x00, 0x27, 0x64]);
+        cmap.extend_from_slice(&words(&[42]));
+        cmap
+    }
+
+    #[test]
+    fn test_variation_sequence_glyph() {
+        assert_eq!(super::variation_sequence_glyph(&cmap_14(), 0x2764, 0xfe00), Some(42));
+        assert_eq!(super::variation_sequence_glyph(&cmap_14(), 0x2764, 0xfe0f), None);
+        assert_eq!(super::variation_sequence_glyph(&cmap_14(), 0x2603, 0xfe00), None);
+    }
+
+    #[test]
+    fn test_ligature_carets() {
+        assert_eq!(super::ligature_carets(&gdef(), 20), Some(vec![400, 800]));
+        assert_eq!(super::ligature_carets(&gdef(), 21), None);
+    }
+
+    // A table directory with a single 8-byte table tagged `test`.
+    fn sfnt() -> Vec<u8> {
+        let mut sfnt = words(&[1, 0, 1, 16, 0, 0]);
+        sfnt.extend_from_slice(b"test");
+        sfnt.extend_from_slice(&words(&[0, 3, 0, 28, 0, 8]));
+        sfnt.extend_from_slice(&words(&[0, 1, 0, 2]));
+        sfnt
+    }
+
+    #[test]
+    fn test_validate_sfnt() {
+        assert_eq!(super::validate_sfnt(&sfnt(), 0), Ok(()));
+
+        let mut corrupt_table = sfnt();
+        corrupt_table[31] = 3;
+        assert_eq!(super::validate_sfnt(&corrupt_table, 0), Err(Some(*b"test")));
+        assert_eq!(super::validate_sfnt(&sfnt()[0..32], 0), Err(Some(*b"test")));
+
+        let mut unknown_version = sfnt();
+        unknown_version[0] = 2;
+        assert_eq!(super::validate_sfnt(&unknown_version, 0), Err(None));
+        assert_eq!(super::validate_sfnt(&sfnt()[0..20], 0), Err(None));
+    }
+
+    #[test]
+    fn test_table_checksum() {
+        let table = words(&[0, 1, 0, 2, -1, -1]);
+        assert_eq!(super::table_checksum(&table, false), 2);
+        assert_eq!(super::table_checksum(&table, true), 3);
+        assert_eq!(super::table_checksum(&[0, 0, 0, 1, 1], false), 0x01000001);
+    }
+}
@@ -0,0 +1,689 @@
+// pathfinder/font-renderer/src/directwrite/woff.rs
+//
+// Copyright © 2017 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Unwraps WOFF and WOFF2 web fonts into the OpenType data that DirectWrite reads.
+//!
+//! WOFF compresses each table with zlib. WOFF2 compresses all the tables together with Brotli,
+//! and may additionally transform the `glyf`, `loca`, and `hmtx` tables into a more compressible
+//! form, which has to be undone here.
+
+use brotli_decompressor::Decompressor;
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+use super::tables::{self, read_i16, read_u16, read_u32};
+
+const WOFF_SIGNATURE: [u8; 4] = *b"wOFF";
+const WOFF2_SIGNATURE: [u8; 4] = *b"wOF2";
+
+const WOFF_HEADER_SIZE: usize = 44;
+const WOFF_TABLE_ENTRY_SIZE: usize = 20;
+const WOFF2_HEADER_SIZE: usize = 48;
+
+const TTC_FLAVOR: u32 = 0x74746366;  // 'ttcf'
+
+// The tags that a WOFF2 table directory entry can refer to by index, in order. Index 63 means
+// that the tag follows the entry's flags instead.
+static WOFF2_KNOWN_TAGS: [[u8; 4]; 63] = [
+    *b"cmap", *b"head", *b"hhea", *b"hmtx", *b"maxp", *b"name", *b"OS/2", *b"post",
+    *b"cvt ", *b"fpgm", *b"glyf", *b"loca", *b"prep", *b"CFF ", *b"VORG", *b"EBDT",
+    *b"EBLC", *b"gasp", *b"hdmx", *b"kern", *b"LTSH", *b"PCLT", *b"VDMX", *b"vhea",
+    *b"vmtx", *b"BASE", *b"GDEF", *b"GPOS", *b"GSUB", *b"EBSC", *b"JSTF", *b"MATH",
+    *b"CBDT", *b"CBLC", *b"COLR", *b"CPAL", *b"SVG ", *b"sbix", *b"acnt", *b"avar",
+    *b"bdat", *b"bloc", *b"bsln", *b"cvar", *b"fdsc", *b"feat", *b"fmtx", *b"fvar",
+    *b"gvar", *b"hsty", *b"just", *b"lcar", *b"mort", *b"morx", *b"opbd", *b"prop",
+    *b"trak", *b"Zapf", *b"Silf", *b"Glat", *b"Gloc", *b"Feat", *b"Sill",
+];
+const WOFF2_TAG_INDEX_MASK: u8 = 0x3f;
+const WOFF2_ARBITRARY_TAG_INDEX: u8 = 63;
+
+// `glyf` and `loca` are transformed unless their transform version is 3; other tables are
+// transformed unless it's 0.
+const WOFF2_GLYF_NULL_TRANSFORM: u8 = 3;
+
+// The option flags of a transformed `glyf` table, and the flags of a transformed `hmtx` table.
+const WOFF2_GLYF_HAS_OVERLAP_SIMPLE_BITMAP: u16 = 0x0001;
+const WOFF2_HMTX_PROPORTIONAL_LSBS_OMITTED: u8 = 0x01;
+const WOFF2_HMTX_MONOSPACED_LSBS_OMITTED: u8 = 0x02;
+
+// The number of substreams that a transformed `glyf` table is split into, and the size of its
+// header.
+const WOFF2_GLYF_SUBSTREAM_COUNT: usize = 7;
+const WOFF2_GLYF_HEADER_SIZE: usize = 36;
+
+// Flags of the points of simple glyphs.
+const GLYF_ON_CURVE_POINT: u8 = 0x01;
+const GLYF_X_SHORT_VECTOR: u8 = 0x02;
+const GLYF_Y_SHORT_VECTOR: u8 = 0x04;
+const GLYF_X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const GLYF_Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+const GLYF_OVERLAP_SIMPLE: u8 = 0x40;
+
+// Flags of the components of composite glyphs.
+const GLYF_ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const GLYF_WE_HAVE_A_SCALE: u16 = 0x0008;
+const GLYF_MORE_COMPONENTS: u16 = 0x0020;
+const GLYF_WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const GLYF_WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const GLYF_WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// The reasons that a web font can't be unwrapped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WoffError {
+    /// The font is truncated or malformed. This carries the tag of the bad table, or `None` if
+    /// the header or the table directory is bad.
+    Corrupt(Option<[u8; 4]>),
+    /// The font is a WOFF2 collection, which isn't supported.
+    Unsupported,
+}
+
+const CORRUPT_DIRECTORY: WoffError = WoffError::Corrupt(None);
+
+/// Returns true if the data is a WOFF or WOFF2 font, rather than raw OpenType data.
+pub fn is_woff(data: &[u8]) -> bool {
+    match data.get(0..4) {
+        Some(signature) => signature == &WOFF_SIGNATURE[..] || signature == &WOFF2_SIGNATURE[..],
+        None => false,
+    }
+}
+
+/// Unwraps a WOFF or WOFF2 font into OpenType data.
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    match data.get(0..4) {
+        Some(signature) if signature == &WOFF_SIGNATURE[..] => decode_woff(data),
+        Some(signature) if signature == &WOFF2_SIGNATURE[..] => decode_woff2(data),
+        _ => Err(CORRUPT_DIRECTORY),
+    }
+}
+
+fn decode_woff(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let flavor = read_u32(data, 4).ok_or(CORRUPT_DIRECTORY)?;
+    let table_count = read_u16(data, 12).ok_or(CORRUPT_DIRECTORY)? as usize;
+
+    let mut sfnt_tables = Vec::with_capacity(table_count);
+    for table_index in 0..table_count {
+        let entry_offset = WOFF_HEADER_SIZE + table_index * WOFF_TABLE_ENTRY_SIZE;
+        let entry = data.get(entry_offset..(entry_offset + WOFF_TABLE_ENTRY_SIZE))
+                        .ok_or(CORRUPT_DIRECTORY)?;
+        let tag = [entry[0], entry[1], entry[2], entry[3]];
+        let table = woff_table(data, entry).ok_or(WoffError::Corrupt(Some(tag)))?;
+        sfnt_tables.push(SfntTable {
+            tag: tag,
+            checksum: read_u32(entry, 16).unwrap(),
+            data: table,
+        })
+    }
+    Ok(build_sfnt(flavor, sfnt_tables))
+}
+
+// Returns the contents of the table that the given WOFF table directory entry points to,
+// decompressing it if it's stored compressed.
+fn woff_table(data: &[u8], entry: &[u8]) -> Option<Vec<u8>> {
+    let offset = read_u32(entry, 4)? as usize;
+    let compressed_length = read_u32(entry, 8)? as usize;
+    let original_length = read_u32(entry, 12)? as usize;
+    let compressed_table = data.get(offset..offset.checked_add(compressed_length)?)?;
+    if compressed_length > original_length {
+        return None
+    }
+    if compressed_length == original_length {
+        return Some(compressed_table.to_vec())
+    }
+
+    let mut table = vec![];
+    ZlibDecoder::new(compressed_table).take(original_length as u64)
+                                      .read_to_end(&mut table)
+                                      .ok()?;
+    if table.len() != original_length {
+        return None
+    }
+    Some(table)
+}
+
+fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let mut header = Stream::new(data);
+    header.skip(4).ok_or(CORRUPT_DIRECTORY)?;
+    let flavor = header.u32().ok_or(CORRUPT_DIRECTORY)?;
+    if flavor == TTC_FLAVOR {
+        return Err(WoffError::Unsupported)
+    }
+    header.skip(4).ok_or(CORRUPT_DIRECTORY)?;
+    let table_count = header.u16().ok_or(CORRUPT_DIRECTORY)? as usize;
+    header.skip(6).ok_or(CORRUPT_DIRECTORY)?;
+    let compressed_size = header.u32().ok_or(CORRUPT_DIRECTORY)? as usize;
+
+    let mut directory = Stream::new(data);
+    directory.skip(WOFF2_HEADER_SIZE).ok_or(CORRUPT_DIRECTORY)?;
+    let mut entries = Vec::with_capacity(table_count);
+    for _ in 0..table_count {
+        entries.push(Woff2TableEntry::read(&mut directory).ok_or(CORRUPT_DIRECTORY)?);
+    }
+
+    // The directory gives the total size of the decompressed tables, so don't let a hostile
+    // stream expand past it.
+    let decompressed_size = entries.iter().fold(Some(0usize), |size, entry| {
+        size.and_then(|size| size.checked_add(entry.stream_length))
+    }).ok_or(CORRUPT_DIRECTORY)?;
+    let compressed_tables = directory.bytes(compressed_size).ok_or(CORRUPT_DIRECTORY)?;
+    let mut decompressed_tables = vec![];
+    if Decompressor::new(compressed_tables, 4096).take(decompressed_size as u64 + 1)
+                                                 .read_to_end(&mut decompressed_tables)
+                                                 .is_err() ||
+            decompressed_tables.len() != decompressed_size {
+        return Err(CORRUPT_DIRECTORY)
+    }
+
+    // The tables are stored back to back, in directory order, without padding.
+    let mut decompressed_stream = Stream::new(&decompressed_tables);
+    let mut table_data = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let table = decompressed_stream.bytes(entry.stream_length)
+                                       .ok_or(WoffError::Corrupt(Some(entry.tag)))?;
+        table_data.push(table);
+    }
+    let untransformed_table = |tag: [u8; 4]| {
+        entries.iter().position(|entry| entry.tag == tag && !entry.transformed).map(|index| {
+            table_data[index]
+        })
+    };
+
+    let (mut glyf_table, mut loca_table, mut x_mins) = (None, None, None);
+    if let Some(glyf_index) = entries.iter().position(|entry| {
+        entry.tag == tables::GLYF && entry.transformed
+    }) {
+        let (glyf, loca, glyph_x_mins) = reconstruct_glyf(table_data[glyf_index])
+            .ok_or(WoffError::Corrupt(Some(tables::GLYF)))?;
+        glyf_table = Some(glyf);
+        loca_table = Some(loca);
+        x_mins = Some(glyph_x_mins);
+    }
+
+    let mut sfnt_tables = Vec::with_capacity(entries.len());
+    for (entry, &table) in entries.iter().zip(table_data.iter()) {
+        let table = if !entry.transformed {
+            Some(table.to_vec())
+        } else if entry.tag == tables::GLYF {
+            glyf_table.take()
+        } else if entry.tag == tables::LOCA {
+            loca_table.take()
+        } else if entry.tag == tables::HMTX {
+            match (x_mins.as_ref(), untransformed_table(tables::HHEA)) {
+                (Some(x_mins), Some(hhea)) => reconstruct_hmtx(table, hhea, x_mins),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        let table = table.ok_or(WoffError::Corrupt(Some(entry.tag)))?;
+        sfnt_tables.push(SfntTable {
+            tag: entry.tag,
+            checksum: tables::table_checksum(&table, entry.tag == tables::HEAD),
+            data: table,
+        })
+    }
+    Ok(build_sfnt(flavor, sfnt_tables))
+}
+
+struct Woff2TableEntry {
+    tag: [u8; 4],
+    transformed: bool,
+    // The length of the table within the decompressed data, which is the transformed length if
+    // the table is transformed.
+    stream_length: usize,
+}
+
+impl Woff2TableEntry {
+    fn read(directory: &mut Stream) -> Option<Woff2TableEntry> {
+        let flags = directory.u8()?;
+        let tag = match flags & WOFF2_TAG_INDEX_MASK {
+            WOFF2_ARBITRARY_TAG_INDEX => {
+                let tag = directory.bytes(4)?;
+                [tag[0], tag[1], tag[2], tag[3]]
+            }
+            tag_index => WOFF2_KNOWN_TAGS[tag_index as usize],
+        };
+
+        let transform_version = flags >> 6;
+        let transformed = if tag == tables::GLYF || tag == tables::LOCA {
+            transform_version != WOFF2_GLYF_NULL_TRANSFORM
+        } else {
+            transform_version != 0
+        };
+
+        let original_length = directory.uint_base_128()? as usize;
+        let stream_length = if transformed {
+            directory.uint_base_128()? as usize
+        } else {
+            original_length
+        };
+        Some(Woff2TableEntry {
+            tag: tag,
+            transformed: transformed,
+            stream_length: stream_length,
+        })
+    }
+}
+
+// Rebuilds the `glyf` and `loca` tables from a transformed `glyf` table. Also returns the minimum
+// X coordinate of each glyph, which the `hmtx` transform may have dropped the left side bearings
+// in favor of.
+fn reconstruct_glyf(transformed: &[u8]) -> Option<(Vec<u8>, Vec<u8>, Vec<i16>)> {
+    let mut header = Stream::new(transformed);
+    header.skip(2)?;
+    let option_flags = header.u16()?;
+    let glyph_count = header.u16()? as usize;
+    let index_format = header.u16()?;
+    let mut substream_sizes = [0; WOFF2_GLYF_SUBSTREAM_COUNT];
+    for substream_size in &mut substream_sizes {
+        *substream_size = header.u32()? as usize;
+    }
+
+    let mut substreams = Stream::new(transformed);
+    substreams.skip(WOFF2_GLYF_HEADER_SIZE)?;
+    let mut contour_count_stream = Stream::new(substreams.bytes(substream_sizes[0])?);
+    let mut point_count_stream = Stream::new(substreams.bytes(substream_sizes[1])?);
+    let mut flag_stream = Stream::new(substreams.bytes(substream_sizes[2])?);
+    let mut glyph_stream = Stream::new(substreams.bytes(substream_sizes[3])?);
+    let mut composite_stream = Stream::new(substreams.bytes(substream_sizes[4])?);
+    let mut bbox_stream = Stream::new(substreams.bytes(substream_sizes[5])?);
+    let mut instruction_stream = Stream::new(substreams.bytes(substream_sizes[6])?);
+    let overlap_simple_bitmap = if option_flags & WOFF2_GLYF_HAS_OVERLAP_SIMPLE_BITMAP != 0 {
+        Some(substreams.bytes((glyph_count + 7) / 8)?)
+    } else {
+        None
+    };
+    let bbox_bitmap = bbox_stream.bytes(((glyph_count + 31) / 32) * 4)?;
+
+    let mut glyf = vec![];
+    let mut glyph_offsets = Vec::with_capacity(glyph_count + 1);
+    let mut x_mins = Vec::with_capacity(glyph_count);
+    for glyph_index in 0..glyph_count {
+        glyph_offsets.push(glyf.len());
+
+        let has_bbox = bit_is_set(bbox_bitmap, glyph_index);
+        let contour_count = contour_count_stream.i16()?;
+        let x_min = if contour_count == 0 {
+            // An empty glyph, such as the space.
+            if has_bbox {
+                return None
+            }
+            0
+        } else if contour_count < 0 {
+            // Composite glyphs must have an explicit bounding box.
+            if !has_bbox {
+                return None
+            }
+            let bbox = bbox_stream.bytes(8)?;
+            let (components, has_instructions) = read_composite_components(&mut composite_stream)?;
+            push_u16(&mut glyf, contour_count as u16);
+            glyf.extend_from_slice(bbox);
+            glyf.extend_from_slice(components);
+            if has_instructions {
+                let instruction_length = glyph_stream.u255_16()?;
+                push_u16(&mut glyf, instruction_length);
+                glyf.extend_from_slice(instruction_stream.bytes(instruction_length as usize)?);
+            }
+            read_i16(bbox, 0)?
+        } else {
+            let mut end_points = Vec::with_capacity(contour_count as usize);
+            let mut point_count = 0;
+            for _ in 0..contour_count {
+                point_count += point_count_stream.u255_16()? as usize;
+                if point_count > 0x10000 {
+                    return None
+                }
+                end_points.push(point_count.checked_sub(1)? as u16);
+            }
+            let points = read_points(flag_stream.bytes(point_count)?, &mut glyph_stream)?;
+            let instruction_length = glyph_stream.u255_16()?;
+            let instructions = instruction_stream.bytes(instruction_length as usize)?;
+
+            let bbox = if has_bbox {
+                let bbox = bbox_stream.bytes(8)?;
+                [read_i16(bbox, 0)?, read_i16(bbox, 2)?, read_i16(bbox, 4)?, read_i16(bbox, 6)?]
+            } else {
+                points_bbox(&points)
+            };
+            let overlaps = match overlap_simple_bitmap {
+                Some(overlap_simple_bitmap) => bit_is_set(overlap_simple_bitmap, glyph_index),
+                None => false,
+            };
+
+            push_u16(&mut glyf, contour_count as u16);
+            for &value in &bbox {
+                push_u16(&mut glyf, value as u16);
+            }
+            for &end_point in &end_points {
+                push_u16(&mut glyf, end_point);
+            }
+            push_u16(&mut glyf, instruction_length);
+            glyf.extend_from_slice(instructions);
+            push_points(&mut glyf, &points, overlaps);
+            bbox[0]
+        };
+        x_mins.push(x_min);
+
+        // Keep each glyph aligned so that either `loca` format can point to it.
+        while glyf.len() % 4 != 0 {
+            glyf.push(0)
+        }
+    }
+    glyph_offsets.push(glyf.len());
+
+    let mut loca = vec![];
+    for &glyph_offset in &glyph_offsets {
+        if index_format == 0 {
+            if glyph_offset / 2 > 0xffff {
+                return None
+            }
+            push_u16(&mut loca, (glyph_offset / 2) as u16)
+        } else {
+            push_u32(&mut loca, glyph_offset as u32)
+        }
+    }
+    Some((glyf, loca, x_mins))
+}
+
+// Reads the components of a composite glyph from the composite stream. Returns their bytes, which
+// are stored the same way as in the `glyf` table, and whether the glyph has instructions.
+fn read_composite_components<'a>(composite_stream: &mut Stream<'a>) -> Option<(&'a [u8], bool)> {
+    let start = composite_stream.offset;
+    let mut has_instructions = false;
+    loop {
+        let flags = composite_stream.u16()?;
+        let argument_size = if flags & GLYF_ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        let transform_size = if flags & GLYF_WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & GLYF_WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & GLYF_WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        composite_stream.skip(2 + argument_size + transform_size)?;
+        has_instructions = has_instructions || flags & GLYF_WE_HAVE_INSTRUCTIONS != 0;
+        if flags & GLYF_MORE_COMPONENTS == 0 {
+            break
+        }
+    }
+    Some((&composite_stream.data[start..composite_stream.offset], has_instructions))
+}
+
+// A point of a simple glyph, in font units.
+#[derive(Clone, Copy)]
+struct GlyphPoint {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+// Decodes the points of a simple glyph from their flags and the triplet-encoded deltas in the
+// glyph stream.
+fn read_points(flags: &[u8], glyph_stream: &mut Stream) -> Option<Vec<GlyphPoint>> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 { value } else { -value }
+    }
+
+    let (mut x, mut y) = (0, 0);
+    let mut points = Vec::with_capacity(flags.len());
+    for &flag in flags {
+        let on_curve = flag & 0x80 == 0;
+        let flag = flag & 0x7f;
+        let (dx, dy) = if flag < 10 {
+            let b0 = glyph_stream.u8()? as i32;
+            (0, with_sign(flag, (((flag & 14) as i32) << 7) + b0))
+        } else if flag < 20 {
+            let b0 = glyph_stream.u8()? as i32;
+            (with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b0), 0)
+        } else if flag < 84 {
+            let (b0, b1) = ((flag - 20) as i32, glyph_stream.u8()? as i32);
+            (with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+             with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)))
+        } else if flag < 120 {
+            let b0 = (flag - 84) as i32;
+            let (b1, b2) = (glyph_stream.u8()? as i32, glyph_stream.u8()? as i32);
+            (with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+             with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2))
+        } else if flag < 124 {
+            let b1 = glyph_stream.u8()? as i32;
+            let b2 = glyph_stream.u8()? as i32;
+            let b3 = glyph_stream.u8()? as i32;
+            (with_sign(flag, (b1 << 4) + (b2 >> 4)), with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3))
+        } else {
+            let (dx, dy) = (glyph_stream.u16()? as i32, glyph_stream.u16()? as i32);
+            (with_sign(flag, dx), with_sign(flag >> 1, dy))
+        };
+        x += dx;
+        y += dy;
+        points.push(GlyphPoint {
+            x: x,
+            y: y,
+            on_curve: on_curve,
+        })
+    }
+    Some(points)
+}
+
+// Returns the bounding box of the given points as `[x_min, y_min, x_max, y_max]`.
+fn points_bbox(points: &[GlyphPoint]) -> [i16; 4] {
+    let (mut x_min, mut y_min) = (points[0].x, points[0].y);
+    let (mut x_max, mut y_max) = (x_min, y_min);
+    for point in &points[1..] {
+        x_min = x_min.min(point.x);
+        y_min = y_min.min(point.y);
+        x_max = x_max.max(point.x);
+        y_max = y_max.max(point.y);
+    }
+    [x_min as i16, y_min as i16, x_max as i16, y_max as i16]
+}
+
+// Appends the flags and coordinates of the given points in the `glyf` format, which stores each
+// coordinate as a delta from the previous point.
+fn push_points(glyf: &mut Vec<u8>, points: &[GlyphPoint], overlaps: bool) {
+    let mut flags = Vec::with_capacity(points.len());
+    let (mut x_coordinates, mut y_coordinates) = (vec![], vec![]);
+    let (mut last_x, mut last_y) = (0, 0);
+    for (point_index, point) in points.iter().enumerate() {
+        let mut flag = if point.on_curve { GLYF_ON_CURVE_POINT } else { 0 };
+        if overlaps && point_index == 0 {
+            flag |= GLYF_OVERLAP_SIMPLE
+        }
+        flag |= push_coordinate(&mut x_coordinates,
+                                point.x - last_x,
+                                GLYF_X_SHORT_VECTOR,
+                                GLYF_X_IS_SAME_OR_POSITIVE);
+        flag |= push_coordinate(&mut y_coordinates,
+                                point.y - last_y,
+                                GLYF_Y_SHORT_VECTOR,
+                                GLYF_Y_IS_SAME_OR_POSITIVE);
+        flags.push(flag);
+        last_x = point.x;
+        last_y = point.y;
+    }
+    glyf.extend_from_slice(&flags);
+    glyf.extend_from_slice(&x_coordinates);
+    glyf.extend_from_slice(&y_coordinates);
+}
+
+// Appends a coordinate delta in the shortest form that the `glyf` table can store it in, and
+// returns the point flags that describe that form.
+fn push_coordinate(coordinates: &mut Vec<u8>, delta: i32, short_flag: u8, same_or_positive_flag: u8)
+                   -> u8 {
+    if delta == 0 {
+        same_or_positive_flag
+    } else if delta > -256 && delta < 256 {
+        coordinates.push(delta.abs() as u8);
+        if delta > 0 { short_flag | same_or_positive_flag } else { short_flag }
+    } else {
+        push_u16(coordinates, delta as i16 as u16);
+        0
+    }
+}
+
+// Rebuilds the `hmtx` table from a transformed one, filling in omitted left side bearings from
+// the minimum X coordinates of the glyphs.
+fn reconstruct_hmtx(transformed: &[u8], hhea: &[u8], x_mins: &[i16]) -> Option<Vec<u8>> {
+    let glyph_count = x_mins.len();
+    let metric_count = read_u16(hhea, 34)? as usize;
+    if metric_count == 0 || metric_count > glyph_count {
+        return None
+    }
+
+    let mut stream = Stream::new(transformed);
+    let flags = stream.u8()?;
+    let advances = (0..metric_count).map(|_| stream.u16()).collect::<Option<Vec<_>>>()?;
+    let mut left_side_bearings = Vec::with_capacity(glyph_count);
+    for glyph_index in 0..glyph_count {
+        let omitted_flag = if glyph_index < metric_count {
+            WOFF2_HMTX_PROPORTIONAL_LSBS_OMITTED
+        } else {
+            WOFF2_HMTX_MONOSPACED_LSBS_OMITTED
+        };
+        if flags & omitted_flag != 0 {
+            left_side_bearings.push(x_mins[glyph_index])
+        } else {
+            left_side_bearings.push(stream.i16()?)
+        }
+    }
+
+    let mut hmtx = Vec::with_capacity(metric_count * 2 + glyph_count * 2);
+    for (glyph_index, &left_side_bearing) in left_side_bearings.iter().enumerate() {
+        if glyph_index < metric_count {
+            push_u16(&mut hmtx, advances[glyph_index]);
+        }
+        push_u16(&mut hmtx, left_side_bearing as u16);
+    }
+    Some(hmtx)
+}
+
+struct SfntTable {
+    tag: [u8; 4],
+    checksum: u32,
+    data: Vec<u8>,
+}
+
+// Lays out the given tables as an OpenType font with the given sfnt version.
+fn build_sfnt(flavor: u32, mut sfnt_tables: Vec<SfntTable>) -> Vec<u8> {
+    sfnt_tables.sort_by_key(|sfnt_table| sfnt_table.tag);
+
+    let table_count = sfnt_tables.len();
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= table_count {
+        entry_selector += 1
+    }
+    let search_range = (1 << entry_selector) * 16;
+
+    let mut sfnt = vec![];
+    push_u32(&mut sfnt, flavor);
+    push_u16(&mut sfnt, table_count as u16);
+    push_u16(&mut sfnt, search_range as u16);
+    push_u16(&mut sfnt, entry_selector as u16);
+    push_u16(&mut sfnt, (table_count * 16).saturating_sub(search_range) as u16);
+
+    let mut table_offset = 12 + table_count * 16;
+    for sfnt_table in &sfnt_tables {
+        sfnt.extend_from_slice(&sfnt_table.tag);
+        push_u32(&mut sfnt, sfnt_table.checksum);
+        push_u32(&mut sfnt, table_offset as u32);
+        push_u32(&mut sfnt, sfnt_table.data.len() as u32);
+        table_offset += (sfnt_table.data.len() + 3) & !3;
+    }
+    for sfnt_table in &sfnt_tables {
+        sfnt.extend_from_slice(&sfnt_table.data);
+        while sfnt.len() % 4 != 0 {
+            sfnt.push(0)
+        }
+    }
+    sfnt
+}
+
+#[inline]
+fn bit_is_set(bitmap: &[u8], index: usize) -> bool {
+    bitmap[index / 8] & (0x80 >> (index % 8)) != 0
+}
+
+#[inline]
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&[(value >> 8) as u8, value as u8])
+}
+
+#[inline]
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    push_u16(data, (value >> 16) as u16);
+    push_u16(data, value as u16)
+}
+
+// Reads big-endian values from the front of a byte slice.
+struct Stream<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(data: &'a [u8]) -> Stream<'a> {
+        Stream {
+            data: data,
+            offset: 0,
+        }
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, length: usize) -> Option<()> {
+        self.bytes(length).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).and_then(|bytes| read_u16(bytes, 0))
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        self.u16().map(|value| value as i16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).and_then(|bytes| read_u32(bytes, 0))
+    }
+
+    // Reads a `UIntBase128`: up to five bytes holding seven bits each, most significant first,
+    // with the high bit set on all but the last.
+    fn uint_base_128(&mut self) -> Option<u32> {
+        let mut value: u32 = 0;
+        for byte_index in 0..5 {
+            let byte = self.u8()?;
+            if (byte_index == 0 && byte == 0x80) || value & 0xfe000000 != 0 {
+                return None
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Some(value)
+            }
+        }
+        None
+    }
+
+    // Reads a `255UInt16`, which stores values below 253 in one byte.
+    fn u255_16(&mut self) -> Option<u16> {
+        match self.u8()? {
+            253 => self.u16(),
+            254 => self.u8().map(|value| value as u16 + 253 * 2),
+            255 => self.u8().map(|value| value as u16 + 253),
+            value => Some(value as u16),
+        }
+    }
+}
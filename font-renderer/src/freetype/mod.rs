@@ -28,7 +28,7 @@ use std::sync::Arc;
 
 use self::fixed::{FromFtF26Dot6, ToFtF26Dot6};
 use self::outline::Outline;
-use {FontInstance, GlyphDimensions, GlyphImage, GlyphKey};
+use {FontInstance, GlyphDimensions, GlyphImage, GlyphKey, PixelFormat};
 
 mod fixed;
 mod outline;
@@ -159,16 +159,19 @@ impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
             Vector2D::new(f32::to_ft_f26dot6(glyph_key.subpixel_offset.into()), 0);
 
         // Move the outline curves to be at the origin, taking the subpixel positioning into
-        // account.
-        unsafe {
+        // account. FreeType positions the bitmap relative to the moved outline, so remember how
+        // many whole pixels it moved by.
+        let pixel_shift = unsafe {
             let outline = &(*slot).outline;
             let mut control_box: FT_BBox = mem::uninitialized();
             FT_Outline_Get_CBox(outline, &mut control_box);
-            FT_Outline_Translate(
-                outline,
-                subpixel_offset.x - fixed::floor(control_box.xMin + subpixel_offset.x),
-                subpixel_offset.y - fixed::floor(control_box.yMin + subpixel_offset.y));
-        }
+            let shift = Vector2D::new(fixed::floor(control_box.xMin + subpixel_offset.x),
+                                      fixed::floor(control_box.yMin + subpixel_offset.y));
+            FT_Outline_Translate(outline,
+                                 subpixel_offset.x - shift.x,
+                                 subpixel_offset.y - shift.y);
+            Vector2D::new((shift.x / 64) as i32, (shift.y / 64) as i32)
+        };
 
         // Set the LCD filter.
         //
@@ -195,7 +198,8 @@ impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
 
             debug_assert_eq!((*bitmap).width % 3, 0);
             let pixel_size = Size2D::new((*bitmap).width as u32 / 3, (*bitmap).rows as u32);
-            let pixel_origin = Point2D::new((*slot).bitmap_left, (*slot).bitmap_top);
+            let pixel_origin = Point2D::new((*slot).bitmap_left, (*slot).bitmap_top) +
+                pixel_shift;
 
             // Allocate the RGBA8 buffer.
             let src_stride = (*bitmap).pitch as usize;
@@ -217,13 +221,18 @@ impl<FK> FontContext<FK> where FK: Clone + Hash + Eq + Ord {
                 }
             }
 
-            // Return the result.
+            // Return the result. `bitmap_top` is measured upward from the baseline, so the lower
+            // left corner is the height of the bitmap below it.
             Ok(GlyphImage {
                 dimensions: GlyphDimensions {
-                    origin: pixel_origin,
+                    origin: Point2D::new(pixel_origin.x,
+                                         pixel_origin.y - pixel_size.height as i32),
                     size: pixel_size,
                     advance: f32::from_ft_f26dot6((*slot).metrics.horiAdvance),
                 },
+                origin: Point2D::new(pixel_origin.x, -pixel_origin.y),
+                format: PixelFormat::Rgba32,
+                stride: dest_stride * 4,
                 pixels: convert_vec_u32_to_vec_u8(dest_pixels),
             })
         }
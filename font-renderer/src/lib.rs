@@ -31,6 +31,9 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
 #[cfg(test)]
 extern crate env_logger;
 
@@ -43,10 +46,16 @@ extern crate core_text;
 #[cfg(any(target_os = "linux", feature = "freetype"))]
 extern crate freetype_sys;
 
+#[cfg(all(target_os = "windows", feature = "woff"))]
+extern crate brotli_decompressor;
 #[cfg(target_os = "windows")]
 extern crate dwrite;
 #[cfg(target_os = "windows")]
-extern crate kernel32;
+extern crate flate2;
+#[cfg(target_os = "windows")]
+extern crate gdi32;
+#[cfg(all(target_os = "windows", feature = "embedded-bitmaps"))]
+extern crate image;
 #[cfg(target_os = "windows")]
 extern crate uuid;
 #[cfg(target_os = "windows")]
@@ -56,7 +65,7 @@ extern crate winapi;
 extern crate widestring;
 
 use app_units::Au;
-use euclid::{Point2D, Size2D};
+use euclid::{Point2D, Size2D, Transform2D, Vector2D};
 
 #[cfg(test)]
 mod tests;
@@ -64,7 +73,11 @@ mod tests;
 #[cfg(all(target_os = "macos", not(feature = "freetype")))]
 pub use core_graphics::{FontContext, GlyphOutline};
 #[cfg(all(target_os = "windows", not(feature = "freetype")))]
-pub use directwrite::FontContext;
+pub use directwrite::{Contours, FontContext, FontError, FontFileBacking, GlyphOutline};
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+pub use directwrite::LoadedFonts;
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+pub use directwrite::PathfinderComPtr;
 #[cfg(any(target_os = "linux", feature = "freetype"))]
 pub use freetype::FontContext;
 
@@ -82,7 +95,9 @@ mod freetype;
 pub const SUBPIXEL_GRANULARITY: u8 = 4;
 
 /// A font at one specific size.
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
+/// 
+/// Instances own the variation settings of variable fonts, so they're `Clone` but not `Copy`.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
 pub struct FontInstance<FK> where FK: Clone {
     /// The opaque font key that this font instance represents.
     pub font_key: FK,
@@ -91,21 +106,168 @@ pub struct FontInstance<FK> where FK: Clone {
     /// 
     /// This is in app units (1/60 pixels) to eliminate floating point error.
     pub size: Au,
+
+    /// The position of this instance along the axes of a variable font.
+    /// 
+    /// This is empty for the default instance, as well as for fonts that aren't variable.
+    /// Backends that don't support font variations ignore it.
+    pub variations: Vec<FontVariation>,
 }
 
 impl<FK> FontInstance<FK> where FK: Clone {
     /// Creates a new instance of a font at the given size.
     #[inline]
     pub fn new(font_key: &FK, size: Au) -> FontInstance<FK> {
+        FontInstance::with_variations(font_key, size, vec![])
+    }
+
+    /// Creates a new instance of a variable font at the given size and position in design space.
+    #[inline]
+    pub fn with_variations(font_key: &FK, size: Au, variations: Vec<FontVariation>)
+                           -> FontInstance<FK> {
         FontInstance {
             font_key: (*font_key).clone(),
             size: size,
+            variations: variations,
+        }
+    }
+}
+
+/// A value along one axis of a variable font: for example, a weight of 650 on the `wght` axis.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
+pub struct FontVariation {
+    /// The OpenType tag of the axis.
+    pub tag: [u8; 4],
+    /// The value along the axis, as a 16.16 fixed-point number.
+    /// 
+    /// Fixed point keeps `FontInstance` totally ordered so that it can be used as a map key.
+    pub value: i32,
+}
+
+impl FontVariation {
+    /// Creates a new axis value from a floating-point value.
+    #[inline]
+    pub fn new(tag: [u8; 4], value: f32) -> FontVariation {
+        FontVariation {
+            tag: tag,
+            value: (value * 65536.0).round() as i32,
         }
     }
+
+    /// Returns the value along the axis as a floating-point value.
+    #[inline]
+    pub fn to_f32(&self) -> f32 {
+        self.value as f32 / 65536.0
+    }
+}
+
+/// The slant of a font face.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FontStyle {
+    /// An upright face.
+    Normal,
+    /// A face that is slanted without changing the shapes of the glyphs.
+    Oblique,
+    /// A face with cursive, slanted glyph shapes.
+    Italic,
+}
+
+/// One axis of a variable font, as declared in its `fvar` table.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VariationAxis {
+    /// The OpenType tag of the axis: for example, `wght` or `wdth`.
+    pub tag: [u8; 4],
+    /// The minimum value along the axis.
+    pub min_value: f32,
+    /// The value along the axis that the default instance uses.
+    pub default_value: f32,
+    /// The maximum value along the axis.
+    pub max_value: f32,
+}
+
+/// A position in the design space of a variable font that the font designer has given a name
+/// to, such as "Bold Condensed".
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedInstance {
+    /// The subfamily name of the instance.
+    pub name: String,
+    /// The value of the instance along each axis of the font.
+    /// 
+    /// These can be passed directly to `FontInstance::with_variations`.
+    pub coordinates: Vec<FontVariation>,
+}
+
+/// The role that a glyph plays, as classified by the OpenType `GDEF` table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GlyphClass {
+    /// A glyph that stands on its own, such as a letter.
+    Base,
+    /// A glyph that represents several characters, such as `fi`.
+    Ligature,
+    /// A combining glyph that attaches to another, such as an accent.
+    Mark,
+    /// A part of a glyph that represents a single character.
+    Component,
+    /// The font doesn't classify this glyph.
+    Unknown,
+}
+
+/// The rule that determines which regions enclosed by a glyph outline are filled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FillMode {
+    /// A point is inside the outline if the contours wind around it a nonzero number of times.
+    Winding,
+    /// A point is inside the outline if a ray from it crosses an odd number of contours.
+    EvenOdd,
+}
+
+/// The format in which a font stores its glyph outlines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum OutlineFlavor {
+    /// Quadratic outlines in the `glyf` table, hinted with TrueType instructions.
+    TrueType,
+    /// Cubic outlines in the `CFF ` table, hinted with PostScript hints.
+    Cff,
+    /// Cubic outlines in the `CFF2` table, which supports font variations.
+    Cff2,
+}
+
+/// The kind of antialiasing that the native rasterizer applies.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum RasterizationMode {
+    /// No antialiasing: each pixel is either fully covered or not at all.
+    Aliased,
+    /// Grayscale antialiasing, producing one coverage value per pixel.
+    Grayscale,
+    /// Subpixel antialiasing (ClearType on Windows), producing separate red, green, and blue
+    /// coverage values for each pixel.
+    Subpixel,
+}
+
+/// How the native rasterizer hints and positions glyph outlines, on backends that let it be
+/// chosen.
+/// 
+/// Small sizes generally look sharpest with the GDI modes, which hint the outlines to the pixel
+/// grid, while large sizes look best with the natural or outline modes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum RenderingMode {
+    /// The mode that the rasterizer recommends for the font and size.
+    Recommended,
+    /// Hinting that fits stems to whole pixels and rounds advances to whole pixels, as classic
+    /// GDI does.
+    GdiClassic,
+    /// Like `GdiClassic`, but with the widths of the glyphs that GDI uses at natural resolution.
+    GdiNatural,
+    /// Light hinting with fractional advances, antialiased horizontally only.
+    Natural,
+    /// Like `Natural`, but antialiased vertically as well.
+    NaturalSymmetric,
+    /// No hinting at all: the outlines are filled as geometry. Suited to large sizes.
+    Outline,
 }
 
 /// A subpixel offset, from 0 to `SUBPIXEL_GRANULARITY`.
-#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
 pub struct SubpixelOffset(pub u8);
 
 impl Into<f32> for SubpixelOffset {
@@ -123,7 +285,7 @@ impl Into<f64> for SubpixelOffset {
 }
 
 /// A handle to the resolution-independent image of a single glyph in a single font.
-#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct GlyphKey {
     /// The OpenType glyph index.
     pub glyph_index: u32,
@@ -142,25 +304,539 @@ impl GlyphKey {
     }
 }
 
-/// The resolution-independent dimensions of a glyph, in font units.
+/// Everything that determines the image that the native rasterizer produces for a glyph, for
+/// use as the key of a glyph cache or atlas.
+/// 
+/// Build these with `FontContext::glyph_cache_key`, so that two keys compare equal exactly when
+/// the rasterizer would produce the same image for them.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct GlyphCacheKey<FK> where FK: Clone {
+    /// The opaque key of the font.
+    pub font_key: FK,
+    /// The position of the font instance along the axes of a variable font.
+    pub variations: Vec<FontVariation>,
+    /// The OpenType glyph index.
+    pub glyph_index: u32,
+    /// The size of the font, in app units.
+    pub size: Au,
+    /// The subpixel offset, from 0 to `SUBPIXEL_GRANULARITY`.
+    pub subpixel_offset: SubpixelOffset,
+    /// The kind of antialiasing.
+    pub mode: RasterizationMode,
+    /// The hinting mode. This is always `RenderingMode::Recommended` for aliased rasterization,
+    /// which ignores it.
+    pub rendering_mode: RenderingMode,
+}
+
+/// The dimensions of a glyph, in pixels at a particular font size.
+/// 
+/// Y points up, and positions are measured from the pen position on the baseline, which is the
+/// same coordinate space that glyph outlines use. The bounding box is rounded out to whole
+/// pixels, so it always contains the outline of the glyph.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct GlyphDimensions {
-    /// The origin of the glyph.
+    /// The lower left corner of the bounding box of the glyph.
     pub origin: Point2D<i32>,
-    /// The total size of the glyph.
+    /// The size of the bounding box of the glyph.
+    /// 
+    /// The width includes room for the subpixel offset of the glyph key, which moves the glyph
+    /// to the right.
     pub size: Size2D<u32>,
     /// The advance of the glyph: that is, the distance from this glyph to the next one.
     pub advance: f32,
 }
 
+/// The metrics of a glyph in font design units, exactly as the font specifies them.
+/// 
+/// These correspond to DirectWrite's `DWRITE_GLYPH_METRICS`. Unlike pixel values, they don't
+/// depend on the font size or on any rounding, which makes them suitable for exact comparisons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GlyphDesignMetrics {
+    /// The distance from the horizontal origin to the left edge of the glyph's black box.
+    pub left_side_bearing: i32,
+    /// The horizontal advance of the glyph.
+    pub advance_width: u32,
+    /// The distance from the right edge of the glyph's black box to the end of the advance.
+    pub right_side_bearing: i32,
+    /// The distance from the vertical origin down to the top of the glyph's black box.
+    pub top_side_bearing: i32,
+    /// The vertical advance of the glyph.
+    pub advance_height: u32,
+    /// The distance from the bottom of the glyph's black box down to the end of the vertical
+    /// advance.
+    pub bottom_side_bearing: i32,
+    /// The height of the vertical origin above the baseline.
+    pub vertical_origin_y: i32,
+    /// The number of design units per em in the font, for scaling these metrics.
+    pub units_per_em: u16,
+}
+
+/// The extents of a glyph both in font design units and in pixels at a particular font size.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GlyphExtents {
+    /// The unscaled metrics of the glyph.
+    pub design_metrics: GlyphDesignMetrics,
+    /// The dimensions of the glyph in pixels, as `glyph_dimensions` returns them.
+    pub dimensions: GlyphDimensions,
+}
+
+/// The metrics of a glyph when it's set in vertical text, in pixels.
+/// 
+/// Y points up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VerticalGlyphDimensions {
+    /// The vertical origin of the glyph, relative to its horizontal origin on the baseline.
+    /// 
+    /// In vertical text, the pen is placed at this point.
+    pub origin: Point2D<f32>,
+    /// The vertical advance of the glyph: that is, the distance down from this glyph to the next
+    /// one.
+    pub advance: f32,
+    /// The distance from the vertical origin down to the top of the glyph.
+    pub top_side_bearing: f32,
+    /// The distance from the bottom of the glyph down to the end of the vertical advance.
+    pub bottom_side_bearing: f32,
+}
+
+/// Font-wide metrics, in pixels at a particular font size.
+/// 
+/// Positions are measured from the baseline, with Y pointing up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FontMetrics {
+    /// The distance from the baseline to the top of the tallest glyphs.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest descenders, as a positive
+    /// number.
+    pub descent: f32,
+    /// The recommended extra space between the descent of one line and the ascent of the next.
+    pub line_gap: f32,
+    /// The position of the top of the underline. This is usually negative.
+    pub underline_position: f32,
+    /// The thickness of the underline.
+    pub underline_thickness: f32,
+    /// The position of the top of the strikeout line.
+    pub strikeout_position: f32,
+    /// The thickness of the strikeout line.
+    pub strikeout_thickness: f32,
+}
+
+/// The line spacing metrics that an OpenType font declares in each of the places it can declare
+/// them, in pixels at a particular font size.
+/// 
+/// Ascents and descents are distances from the baseline, with descents as positive numbers.
+/// Browsers and platforms disagree on which set to use; the `hhea` set is the usual default, and
+/// the typographic set is preferred when `use_typo_metrics` is true.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LineMetrics {
+    /// The ascender from the `hhea` table.
+    pub hhea_ascent: f32,
+    /// The descender from the `hhea` table.
+    pub hhea_descent: f32,
+    /// The line gap from the `hhea` table.
+    pub hhea_line_gap: f32,
+    /// The typographic ascender (`sTypoAscender`) from the `OS/2` table.
+    pub typo_ascent: f32,
+    /// The typographic descender (`sTypoDescender`) from the `OS/2` table.
+    pub typo_descent: f32,
+    /// The typographic line gap (`sTypoLineGap`) from the `OS/2` table.
+    pub typo_line_gap: f32,
+    /// The Windows clipping ascent (`usWinAscent`) from the `OS/2` table.
+    pub win_ascent: f32,
+    /// The Windows clipping descent (`usWinDescent`) from the `OS/2` table.
+    pub win_descent: f32,
+    /// Whether the `USE_TYPO_METRICS` bit of `fsSelection` in the `OS/2` table is set, which asks
+    /// for the typographic metrics to be used for line spacing.
+    pub use_typo_metrics: bool,
+}
+
+/// Constants that control the layout of mathematical formulas, read from the OpenType `MATH`
+/// table.
+/// 
+/// Percentages are as stored in the font. Everything else is in pixels at a particular font size,
+/// with Y pointing up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MathConstants {
+    /// The percentage by which to scale down the first level of subscripts and superscripts.
+    pub script_percent_scale_down: i16,
+    /// The percentage by which to scale down the second level of subscripts and superscripts.
+    pub script_script_percent_scale_down: i16,
+    /// The minimum height of a subformula that requires its delimiters to grow.
+    pub delimited_sub_formula_min_height: f32,
+    /// The minimum height of n-ary operators, such as summation and integral signs, in display
+    /// style.
+    pub display_operator_min_height: f32,
+    /// The white space to leave between lines of math.
+    pub math_leading: f32,
+    /// The height of the math axis, around which fractions and operators are centered.
+    pub axis_height: f32,
+    /// The maximum height of a base that accents can be placed over without being raised.
+    pub accent_base_height: f32,
+    /// The maximum height of a base that accents can be placed over without being flattened.
+    pub flattened_accent_base_height: f32,
+    /// The standard shift down of subscripts.
+    pub subscript_shift_down: f32,
+    /// The maximum height of the top of a subscript.
+    pub subscript_top_max: f32,
+    /// The minimum distance from the bottom of the base down to the baseline of a subscript.
+    pub subscript_baseline_drop_min: f32,
+    /// The standard shift up of superscripts.
+    pub superscript_shift_up: f32,
+    /// The standard shift up of superscripts in cramped style.
+    pub superscript_shift_up_cramped: f32,
+    /// The minimum height of the bottom of a superscript.
+    pub superscript_bottom_min: f32,
+    /// The maximum distance from the top of the base down to the baseline of a superscript.
+    pub superscript_baseline_drop_max: f32,
+    /// The minimum gap between a superscript and a subscript on the same base.
+    pub sub_superscript_gap_min: f32,
+    /// The maximum height that the bottom of a superscript is raised to when there's also a
+    /// subscript.
+    pub superscript_bottom_max_with_subscript: f32,
+    /// The extra horizontal space to add after subscripts and superscripts.
+    pub space_after_script: f32,
+    /// The minimum gap between the top of a large operator and the bottom of its upper limit.
+    pub upper_limit_gap_min: f32,
+    /// The minimum distance from the top of a large operator up to the baseline of its upper limit.
+    pub upper_limit_baseline_rise_min: f32,
+    /// The minimum gap between the bottom of a large operator and the top of its lower limit.
+    pub lower_limit_gap_min: f32,
+    /// The minimum distance from the bottom of a large operator down to the baseline of its lower
+    /// limit.
+    pub lower_limit_baseline_drop_min: f32,
+    /// The standard shift up of the top element of a stack.
+    pub stack_top_shift_up: f32,
+    /// The standard shift up of the top element of a stack in display style.
+    pub stack_top_display_style_shift_up: f32,
+    /// The standard shift down of the bottom element of a stack.
+    pub stack_bottom_shift_down: f32,
+    /// The standard shift down of the bottom element of a stack in display style.
+    pub stack_bottom_display_style_shift_down: f32,
+    /// The minimum gap between the elements of a stack.
+    pub stack_gap_min: f32,
+    /// The minimum gap between the elements of a stack in display style.
+    pub stack_display_style_gap_min: f32,
+    /// The standard shift up of the top element of a stretch stack.
+    pub stretch_stack_top_shift_up: f32,
+    /// The standard shift down of the bottom element of a stretch stack.
+    pub stretch_stack_bottom_shift_down: f32,
+    /// The minimum gap between the stretched element of a stretch stack and the element above it.
+    pub stretch_stack_gap_above_min: f32,
+    /// The minimum gap between the stretched element of a stretch stack and the element below it.
+    pub stretch_stack_gap_below_min: f32,
+    /// The standard shift up of the numerator of a fraction.
+    pub fraction_numerator_shift_up: f32,
+    /// The standard shift up of the numerator of a fraction in display style.
+    pub fraction_numerator_display_style_shift_up: f32,
+    /// The standard shift down of the denominator of a fraction.
+    pub fraction_denominator_shift_down: f32,
+    /// The standard shift down of the denominator of a fraction in display style.
+    pub fraction_denominator_display_style_shift_down: f32,
+    /// The minimum gap between the bottom of the numerator and the fraction bar.
+    pub fraction_numerator_gap_min: f32,
+    /// The minimum gap between the bottom of the numerator and the fraction bar in display style.
+    pub fraction_num_display_style_gap_min: f32,
+    /// The thickness of the fraction bar.
+    pub fraction_rule_thickness: f32,
+    /// The minimum gap between the fraction bar and the top of the denominator.
+    pub fraction_denominator_gap_min: f32,
+    /// The minimum gap between the fraction bar and the top of the denominator in display style.
+    pub fraction_denom_display_style_gap_min: f32,
+    /// The horizontal gap between the numerator and denominator of a skewed fraction.
+    pub skewed_fraction_horizontal_gap: f32,
+    /// The vertical gap between the numerator and denominator of a skewed fraction.
+    pub skewed_fraction_vertical_gap: f32,
+    /// The gap between an overbar and the top of the element under it.
+    pub overbar_vertical_gap: f32,
+    /// The thickness of an overbar.
+    pub overbar_rule_thickness: f32,
+    /// The extra white space to leave above an overbar.
+    pub overbar_extra_ascender: f32,
+    /// The gap between an underbar and the bottom of the element over it.
+    pub underbar_vertical_gap: f32,
+    /// The thickness of an underbar.
+    pub underbar_rule_thickness: f32,
+    /// The extra white space to leave below an underbar.
+    pub underbar_extra_descender: f32,
+    /// The gap between the radicand and the overbar of a radical.
+    pub radical_vertical_gap: f32,
+    /// The gap between the radicand and the overbar of a radical in display style.
+    pub radical_display_style_vertical_gap: f32,
+    /// The thickness of the overbar of a radical.
+    pub radical_rule_thickness: f32,
+    /// The extra white space to leave above the overbar of a radical.
+    pub radical_extra_ascender: f32,
+    /// The horizontal space to leave before the degree of a radical.
+    pub radical_kern_before_degree: f32,
+    /// The horizontal space to leave after the degree of a radical, as a negative number if the
+    /// degree overlaps the radical sign.
+    pub radical_kern_after_degree: f32,
+    /// The height of the bottom of the degree of a radical, as a percentage of the height of the
+    /// radical sign.
+    pub radical_degree_bottom_raise_percent: i16,
+}
+
+/// One layer of a color glyph.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorGlyphLayer {
+    /// The index of the glyph whose outline forms this layer.
+    pub glyph_index: u32,
+    /// The color of this layer as red, green, blue, and alpha components from 0.0 to 1.0, or
+    /// `None` if the layer should be drawn in the current text color.
+    pub color: Option<[f32; 4]>,
+}
+
+/// The paint graph of a color glyph from a `COLR` version 1 table.
+/// 
+/// Positions and lengths are in pixels at the size of the font instance, relative to the pen
+/// position on the baseline, with Y pointing up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaintGraph {
+    /// The paint at the root of the graph.
+    pub root: PaintNode,
+}
+
+/// One node of a `COLR` version 1 paint graph.
+/// 
+/// Paints that fill, such as solid colors and gradients, cover the whole plane; they're limited
+/// to the shape of a glyph by an enclosing `PaintNode::Glyph`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PaintNode {
+    /// Paints that are composited on top of each other, from bottom to top.
+    Layers(Vec<PaintNode>),
+    /// A solid color.
+    Solid {
+        /// The color as red, green, blue, and alpha components from 0.0 to 1.0, or `None` for the
+        /// current text color.
+        color: Option<[f32; 4]>,
+        /// An opacity from 0.0 to 1.0 to multiply the color by.
+        alpha: f32,
+    },
+    /// A linear gradient.
+    /// 
+    /// The gradient runs from `p0` to `p1`, and `p2` rotates it: color lines are perpendicular to
+    /// the line from `p0` to `p2`.
+    LinearGradient {
+        color_line: ColorLine,
+        p0: Point2D<f32>,
+        p1: Point2D<f32>,
+        p2: Point2D<f32>,
+    },
+    /// A radial gradient between two circles.
+    RadialGradient {
+        color_line: ColorLine,
+        center0: Point2D<f32>,
+        radius0: f32,
+        center1: Point2D<f32>,
+        radius1: f32,
+    },
+    /// A sweep (conic) gradient around a center point.
+    SweepGradient {
+        color_line: ColorLine,
+        center: Point2D<f32>,
+        /// The angle at which the color line starts, in degrees counterclockwise from the
+        /// positive X axis.
+        start_angle: f32,
+        /// The angle at which the color line ends, in degrees counterclockwise from the positive
+        /// X axis.
+        end_angle: f32,
+    },
+    /// A paint clipped to the outline of a glyph.
+    Glyph {
+        /// The index of the glyph whose outline clips the paint.
+        glyph_index: u32,
+        /// The paint that fills the outline.
+        paint: Box<PaintNode>,
+    },
+    /// The paint graph of another color glyph, which can be fetched by its glyph index.
+    ColrGlyph(u32),
+    /// A paint drawn with a transform applied.
+    Transform {
+        transform: Transform2D<f32>,
+        paint: Box<PaintNode>,
+    },
+    /// Two paints combined with a compositing or blending mode.
+    Composite {
+        /// The paint drawn on top.
+        source: Box<PaintNode>,
+        /// The `CompositeMode` value from the OpenType specification: for example, 3 for source
+        /// over.
+        mode: u8,
+        /// The paint drawn underneath.
+        backdrop: Box<PaintNode>,
+    },
+}
+
+/// The colors of a `COLR` version 1 gradient.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorLine {
+    /// How the gradient continues outside the range of its stops.
+    pub extend: GradientExtend,
+    /// The color stops, in the order that the font lists them.
+    pub stops: Vec<ColorStop>,
+}
+
+/// How a gradient continues outside the range of its color stops.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GradientExtend {
+    /// The colors of the first and last stops continue indefinitely.
+    Pad,
+    /// The gradient repeats.
+    Repeat,
+    /// The gradient repeats, reversing direction every other time.
+    Reflect,
+}
+
+/// One color stop of a `COLR` version 1 gradient.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    /// The position of the stop along the gradient, where 0.0 is the start and 1.0 is the end.
+    pub offset: f32,
+    /// The color as red, green, blue, and alpha components from 0.0 to 1.0, or `None` for the
+    /// current text color.
+    pub color: Option<[f32; 4]>,
+    /// An opacity from 0.0 to 1.0 to multiply the color by.
+    pub alpha: f32,
+}
+
+/// An OpenType feature tag, such as `*b"liga"` or `*b"ss01"`.
+pub type FeatureTag = [u8; 4];
+
+/// A glyph produced by shaping a string, positioned relative to the pen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShapedGlyph {
+    /// The OpenType glyph index.
+    pub glyph_index: u16,
+    /// The byte offset in the string of the first character of the cluster that this glyph
+    /// belongs to.
+    pub cluster: usize,
+    /// The distance to advance the pen after this glyph, in pixels.
+    pub advance: f32,
+    /// The offset of this glyph from the pen position, in pixels, with Y pointing up and X
+    /// pointing in the reading direction.
+    /// 
+    /// For combining marks, this places the mark's anchor on the anchor of the glyph that it
+    /// attaches to.
+    pub offset: Vector2D<f32>,
+}
+
+/// The names of a font.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FontNames {
+    /// The PostScript name, such as `NimbusSanL-Regu`.
+    pub postscript_name: Option<String>,
+    /// The full name, including the style, such as `Nimbus Sans L Regular`.
+    pub full_name: Option<String>,
+    /// The family name, such as `Nimbus Sans L`.
+    pub family_name: Option<String>,
+}
+
+/// A description of one face in a font collection, for choosing a face before loading it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionFaceInfo {
+    /// The index of the face in the collection, to pass as the `font_index` of a load.
+    pub font_index: u32,
+    /// The family name, such as `Noto Sans CJK JP`.
+    pub family_name: String,
+    /// The name of the face within its family, such as `Bold Italic`.
+    pub face_name: String,
+    /// The weight, from 1 to 999, where 400 is regular and 700 is bold.
+    pub weight: u16,
+    /// The stretch, from 1 (ultra-condensed) to 9 (ultra-expanded), where 5 is normal.
+    pub stretch: u8,
+    /// The slant of the face.
+    pub style: FontStyle,
+}
+
+/// The layout of each pixel in a `GlyphImage`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum PixelFormat {
+    /// 8 bits per pixel: a single coverage or alpha value.
+    A8,
+    /// 24 bits per pixel: red, green, and blue, in that order.
+    Rgb24,
+    /// 32 bits per pixel: red, green, blue, and alpha, in that order.
+    Rgba32,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes that each pixel occupies.
+    #[inline]
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::A8 => 1,
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Rgba32 => 4,
+        }
+    }
+}
+
 /// A bitmap image of a glyph.
+/// 
+/// To draw the image, place the top left corner of the bitmap at the pen position plus `origin`,
+/// in a space where Y points down. Rows of `pixels` are always stored top to bottom, whichever
+/// backend produced the image.
 pub struct GlyphImage {
     /// The dimensions of this image.
+    /// 
+    /// The size is that of the bitmap, and the advance is that of the glyph. As with any
+    /// `GlyphDimensions`, the origin is the lower left corner of the bitmap, with Y pointing up.
+    /// `origin` is always `(dimensions.origin.x, -(dimensions.origin.y + height))`.
     pub dimensions: GlyphDimensions,
-    /// The actual pixels.
+    /// The offset from the pen position to the top left corner of the bitmap, in pixels, with Y
+    /// pointing down.
+    /// 
+    /// For a glyph that sits on the baseline, `origin.x` is the left side bearing and `origin.y`
+    /// is the negated height of the glyph above the baseline.
+    pub origin: Point2D<i32>,
+    /// The layout of each pixel in `pixels`.
     /// 
-    /// This is 8 bits per pixel grayscale when grayscale antialiasing is in use and 24 bits per
-    /// pixel RGB when subpixel antialiasing is in use.
+    /// This is `A8` grayscale when grayscale antialiasing is in use and `Rgb24` when subpixel
+    /// antialiasing is in use. Embedded color bitmaps are `Rgba32`, with alpha not premultiplied.
+    /// The FreeType and Core Graphics native rasterizers instead always return `Rgba32` reference
+    /// images: black text on an opaque white background.
+    pub format: PixelFormat,
+    /// The number of bytes from the start of one row of `pixels` to the start of the next.
+    pub stride: usize,
+    /// The actual pixels, with rows stored top to bottom.
     pub pixels: Vec<u8>,
 }
+
+impl GlyphImage {
+    /// Returns the offset from the pen position to the top left corner of the bitmap, in pixels,
+    /// with Y pointing down.
+    #[inline]
+    pub fn origin(&self) -> Point2D<i32> {
+        self.origin
+    }
+
+    /// Returns the width of the bitmap in pixels.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.dimensions.size.width
+    }
+
+    /// Returns the height of the bitmap in pixels.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.dimensions.size.height
+    }
+
+    /// Returns true if the bitmap has no pixels, as is the case for a space.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dimensions.size.width == 0 || self.dimensions.size.height == 0
+    }
+
+    /// Returns the pixels of the given row of the bitmap, counting from the top, without any
+    /// padding at the end of the row.
+    /// 
+    /// Panics if `y` is not less than the height.
+    #[inline]
+    pub fn row(&self, y: u32) -> &[u8] {
+        assert!(y < self.dimensions.size.height);
+        let start = y as usize * self.stride;
+        let length = self.dimensions.size.width as usize * self.format.bytes_per_pixel();
+        &self.pixels[start..(start + length)]
+    }
+}
@@ -2,12 +2,16 @@
 
 use app_units::Au;
 use env_logger;
-use euclid::Size2D;
+use euclid::{Point2D, Size2D};
 use euclid::approxeq::ApproxEq;
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+use lyon_path::PathEvent;
 use pathfinder_path_utils::{PathBuffer, Subpath};
 use std::fs::File;
 use std::io::Read;
 use std::sync::Arc;
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+use {FontError, FontVariation, OutlineFlavor, RasterizationMode, RenderingMode};
 use {FontContext, FontInstance, FontKey, GlyphDimensions, GlyphKey, SubpixelOffset};
 
 static TEST_FONT_PATH: &'static str = "../resources/fonts/nimbus-sans/NimbusSanL-Regu.ttf";
@@ -98,6 +102,21 @@ static EXPECTED_GLYPH_SUBPATHS: [Subpath; 2] = [
     },
 ];
 
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+fn read_font(path: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+// Returns a context with the test font loaded under the key `()`.
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+fn load_test_font() -> FontContext<()> {
+    let mut font_context = FontContext::new().unwrap();
+    font_context.add_font_from_memory(&(), Arc::new(read_font(TEST_FONT_PATH)), 0).unwrap();
+    font_context
+}
+
 #[test]
 fn test_font_context_glyph_dimensions() {
     let mut font_context = FontContext::new().unwrap();
@@ -118,6 +137,354 @@ fn test_font_context_glyph_dimensions() {
     })
 }
 
+// In NimbusSanL-Regu, 'a' has the ink box (86, -47)-(1096, 1104) in a 2048-unit em and an
+// advance of 1139 units.
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_dimensions_exact() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let glyph_dimensions = font_context.glyph_dimensions(&font_instance, &glyph_key, true)
+                                       .unwrap();
+
+    assert_eq!(glyph_dimensions.origin, Point2D::new(0, -1));
+    assert_eq!(glyph_dimensions.size, Size2D::new(9, 10));
+    assert!(glyph_dimensions.advance.approx_eq(&(1139.0 / 128.0)));
+}
+
+// The dimensions and the outline are in the same Y-up space, so the bounding box that
+// `glyph_dimensions` reports must enclose every point of the outline, and must be no more than a
+// pixel bigger than it on any side.
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_dimensions_enclose_outline() {
+    let mut font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let glyph_outline = font_context.glyph_outline(&font_instance, &glyph_key).unwrap();
+
+    let mut points = vec![];
+    for event in glyph_outline.iter() {
+        match event {
+            PathEvent::MoveTo(to) | PathEvent::LineTo(to) => points.push(to),
+            PathEvent::QuadraticTo(ctrl, to) => points.extend_from_slice(&[ctrl, to]),
+            PathEvent::CubicTo(ctrl1, ctrl2, to) => points.extend_from_slice(&[ctrl1, ctrl2, to]),
+            PathEvent::Arc(..) | PathEvent::Close => {}
+        }
+    }
+    assert!(!points.is_empty());
+
+    let (mut lower_left, mut upper_right) = (points[0], points[0]);
+    for point in &points {
+        lower_left = Point2D::new(lower_left.x.min(point.x), lower_left.y.min(point.y));
+        upper_right = Point2D::new(upper_right.x.max(point.x), upper_right.y.max(point.y));
+    }
+
+    for &exact in &[false, true] {
+        let glyph_dimensions = font_context.glyph_dimensions(&font_instance, &glyph_key, exact)
+                                           .unwrap();
+        let origin = glyph_dimensions.origin.to_f32();
+        let size = glyph_dimensions.size.to_f32();
+        assert!(origin.x <= lower_left.x && lower_left.x < origin.x + 1.0);
+        assert!(origin.y <= lower_left.y && lower_left.y < origin.y + 1.0);
+        assert!(origin.x + size.width >= upper_right.x &&
+                upper_right.x > origin.x + size.width - 1.0);
+        assert!(origin.y + size.height >= upper_right.y &&
+                upper_right.y > origin.y + size.height - 1.0);
+    }
+
+    // The rasterizer's black box is in the same space. Antialiasing can spill coverage into the
+    // pixels around the outline, so allow a little slack.
+    let glyph_image = font_context.rasterize_glyph(&font_instance,
+                                                   &glyph_key,
+                                                   RasterizationMode::Grayscale,
+                                                   RenderingMode::Recommended)
+                                  .unwrap();
+    let origin = glyph_image.dimensions.origin.to_f32();
+    let size = glyph_image.dimensions.size.to_f32();
+    assert!(origin.x <= lower_left.x + 1.0 && lower_left.x < origin.x + 2.0);
+    assert!(origin.y <= lower_left.y + 1.0 && lower_left.y < origin.y + 2.0);
+    assert!(origin.x + size.width >= upper_right.x - 1.0 &&
+            upper_right.x > origin.x + size.width - 2.0);
+    assert!(origin.y + size.height >= upper_right.y - 1.0 &&
+            upper_right.y > origin.y + size.height - 2.0);
+    assert_eq!(glyph_image.origin,
+               Point2D::new(glyph_image.dimensions.origin.x,
+                            -(glyph_image.dimensions.origin.y + glyph_image.height() as i32)));
+}
+
+// Bahnschrift is a variable font that ships with Windows 10. Its advances widen with the weight,
+// so they must differ between two instances if the variations are taken into account.
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_variable_font_advances() {
+    let mut font_context = FontContext::new().unwrap();
+    font_context.add_system_font(&(), "Bahnschrift", 0).unwrap();
+
+    let light = FontInstance::with_variations(&(),
+                                              TEST_FONT_SIZE,
+                                              vec![FontVariation::new(*b"wght", 300.0)]);
+    let bold = FontInstance::with_variations(&(),
+                                             TEST_FONT_SIZE,
+                                             vec![FontVariation::new(*b"wght", 700.0)]);
+    let glyph_indices = font_context.load_glyph_indices_for_characters(&light, &['a' as u32])
+                                    .unwrap();
+    let glyph_key = GlyphKey::new(glyph_indices[0] as u32, SubpixelOffset(0));
+
+    let light_advance = font_context.glyph_advances(&light, &[glyph_key]).unwrap()[0];
+    let bold_advance = font_context.glyph_advances(&bold, &[glyph_key]).unwrap()[0];
+    assert!(bold_advance > light_advance);
+
+    let light_dimensions = font_context.glyph_dimensions(&light, &glyph_key, true).unwrap();
+    let bold_dimensions = font_context.glyph_dimensions(&bold, &glyph_key, true).unwrap();
+    assert!(light_dimensions.advance.approx_eq(&light_advance));
+    assert!(bold_dimensions.advance.approx_eq(&bold_advance));
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_system_font_with_nul_in_name() {
+    let mut font_context = FontContext::new().unwrap();
+    assert_eq!(font_context.add_system_font(&(), "Arial\0Bold", 0),
+               Err(FontError::InvalidFamilyName));
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_add_font_from_memory_checked() {
+    let mut font_context = FontContext::new().unwrap();
+
+    let bytes = read_font(TEST_FONT_PATH);
+    let mut truncated_bytes = bytes.clone();
+    truncated_bytes.truncate(bytes.len() / 2);
+    match font_context.add_font_from_memory_checked(&0, Arc::new(truncated_bytes), 0) {
+        Err(FontError::CorruptFont(Some(_))) => {}
+        result => panic!("expected a corrupt table, got {:?}", result),
+    }
+    assert_eq!(font_context.add_font_from_memory_checked(&1, Arc::new(vec![0; 8]), 0),
+               Err(FontError::CorruptFont(None)));
+
+    font_context.add_font_from_memory_checked(&2, Arc::new(bytes), 0).unwrap();
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_is_composite() {
+    let mut font_context = FontContext::new().unwrap();
+
+    font_context.add_font_from_memory(&0, Arc::new(read_font(TEST_FONT_PATH)), 0).unwrap();
+    assert_eq!(font_context.glyph_is_composite(&0, TEST_GLYPH_ID as u16), Ok(false));
+
+    let bytes = read_font("../resources/fonts/eb-garamond/EBGaramond12-Regular.otf");
+    font_context.add_font_from_memory(&1, Arc::new(bytes), 0).unwrap();
+    assert_eq!(font_context.glyph_is_composite(&1, 1), Err(FontError::Unsupported));
+
+    assert_eq!(font_context.outline_flavor(&0), Ok(OutlineFlavor::TrueType));
+    assert_eq!(font_context.outline_flavor(&1), Ok(OutlineFlavor::Cff));
+}
+
+// Inter UI is available both as a TrueType font and as a WOFF2 web font with transformed `glyf`
+// and `loca` tables, so unwrapping the web font must produce the same glyphs.
+#[cfg(all(target_os = "windows", not(feature = "freetype"), feature = "woff"))]
+#[test]
+fn test_directwrite_add_woff_from_memory() {
+    let mut font_context = FontContext::new().unwrap();
+
+    let bytes = read_font("../resources/fonts/inter-ui/Inter-UI-Regular.ttf");
+    font_context.add_font_from_memory(&0, Arc::new(bytes), 0).unwrap();
+    let bytes = read_font("../resources/fonts/inter-ui/Inter-UI-Regular.woff2");
+    font_context.add_woff_from_memory(&1, Arc::new(bytes), 0).unwrap();
+
+    let ttf_instance = FontInstance::new(&0, TEST_FONT_SIZE);
+    let woff_instance = FontInstance::new(&1, TEST_FONT_SIZE);
+    let glyph_indices = font_context.load_glyph_indices_for_characters(&ttf_instance,
+                                                                       &['a' as u32, 'g' as u32])
+                                    .unwrap();
+    assert_eq!(font_context.load_glyph_indices_for_characters(&woff_instance,
+                                                              &['a' as u32, 'g' as u32])
+                           .unwrap(),
+               glyph_indices);
+
+    for &glyph_index in &glyph_indices {
+        let glyph_key = GlyphKey::new(glyph_index as u32, SubpixelOffset(0));
+        assert_eq!(font_context.glyph_advances(&ttf_instance, &[glyph_key]).unwrap(),
+                   font_context.glyph_advances(&woff_instance, &[glyph_key]).unwrap());
+        let ttf_outline: Vec<PathEvent> =
+            font_context.glyph_outline(&ttf_instance, &glyph_key).unwrap().iter().collect();
+        let woff_outline: Vec<PathEvent> =
+            font_context.glyph_outline(&woff_instance, &glyph_key).unwrap().iter().collect();
+        assert_eq!(ttf_outline, woff_outline);
+    }
+
+    assert_eq!(font_context.add_woff_from_memory(&2, Arc::new(b"wOF2".to_vec()), 0),
+               Err(FontError::CorruptFont(None)));
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_measure_text() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let advance = font_context.glyph_advances(&font_instance, &[glyph_key]).unwrap()[0];
+    assert_eq!(font_context.measure_text(&font_instance, "").unwrap(), 0.0);
+    assert!(font_context.measure_text(&font_instance, "aaa").unwrap().approx_eq(&(advance * 3.0)));
+}
+
+// The glyph sits on the baseline, so the top left corner of its image is above and to the right
+// of the pen position, and the image reaches down to the baseline. It's cropped to the ink, so
+// its first and last rows aren't empty.
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_image_origin() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let glyph_image = font_context.rasterize_glyph(&font_instance,
+                                                   &glyph_key,
+                                                   RasterizationMode::Grayscale,
+                                                   RenderingMode::Recommended)
+                                  .unwrap();
+    assert!(!glyph_image.is_empty());
+    assert!(glyph_image.origin().x >= 0);
+    assert!(glyph_image.origin().y < 0);
+    assert!(glyph_image.origin().y + glyph_image.height() as i32 >= 0);
+    assert!(glyph_image.row(0).iter().any(|&coverage| coverage != 0));
+    assert!(glyph_image.row(glyph_image.height() - 1).iter().any(|&coverage| coverage != 0));
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_rasterize_glyph_with_invalid_gamma() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    for &gamma in &[0.0, -2.2, ::std::f32::NAN, ::std::f32::INFINITY] {
+        match font_context.rasterize_glyph_with_gamma(&font_instance,
+                                                      &glyph_key,
+                                                      RasterizationMode::Grayscale,
+                                                      RenderingMode::Recommended,
+                                                      gamma) {
+            Err(FontError::InvalidArgument) => {}
+            _ => panic!("gamma {} was accepted", gamma),
+        }
+    }
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_rasterize_glyph_into() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let glyph_image = font_context.rasterize_glyph(&font_instance,
+                                                   &glyph_key,
+                                                   RasterizationMode::Grayscale,
+                                                   RenderingMode::Recommended)
+                                  .unwrap();
+
+    let dest_stride = 64;
+    let mut dest = vec![0; dest_stride * 64];
+    let dimensions = font_context.rasterize_glyph_into(&font_instance,
+                                                       &glyph_key,
+                                                       RasterizationMode::Grayscale,
+                                                       RenderingMode::Recommended,
+                                                       &mut dest,
+                                                       dest_stride,
+                                                       (3, 5))
+                                 .unwrap();
+    assert_eq!(dimensions, glyph_image.dimensions);
+    for y in 0..glyph_image.height() {
+        let dest_row_offset = (5 + y as usize) * dest_stride + 3;
+        assert_eq!(&dest[dest_row_offset..(dest_row_offset + glyph_image.width() as usize)],
+                   glyph_image.row(y));
+    }
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_outline_at_zero_subpixel_offset() {
+    let mut font_context = load_test_font();
+    font_context.set_outline_dilation(0.02);
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let offset_outline: Vec<PathEvent> =
+        font_context.glyph_outline_at_subpixel_offset(&font_instance, &glyph_key, (0.0, 0.0))
+                    .unwrap()
+                    .iter()
+                    .collect();
+    let outline: Vec<PathEvent> =
+        font_context.glyph_outline(&font_instance, &glyph_key).unwrap().iter().collect();
+    assert_eq!(offset_outline, outline);
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_loaded_fonts() {
+    let mut font_context = FontContext::new().unwrap();
+    assert_eq!(font_context.loaded_count(), 0);
+
+    let bytes = Arc::new(read_font(TEST_FONT_PATH));
+    font_context.add_font_from_memory(&2, bytes.clone(), 0).unwrap();
+    font_context.add_font_from_memory(&1, bytes, 0).unwrap();
+    assert_eq!(font_context.loaded_count(), 2);
+    assert_eq!(font_context.loaded_fonts().cloned().collect::<Vec<_>>(), vec![1, 2]);
+
+    font_context.delete_font(&1);
+    assert_eq!(font_context.loaded_fonts().cloned().collect::<Vec<_>>(), vec![2]);
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_extents() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    let extents = font_context.glyph_extents(&font_instance, &glyph_key, false).unwrap();
+    assert_eq!(Ok(extents.dimensions),
+               font_context.glyph_dimensions(&font_instance, &glyph_key, false));
+
+    let design_metrics = extents.design_metrics;
+    let scale = TEST_FONT_SIZE.to_f32_px() / design_metrics.units_per_em as f32;
+    let advance = design_metrics.advance_width as f32 * scale;
+    assert!(advance.approx_eq(&extents.dimensions.advance));
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_shape_with_ranges() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let text = "office";
+    let shaped_glyphs = font_context.shape(&font_instance, text, *b"Latn", "", &[], false)
+                                    .unwrap();
+    let ranges = [(1..1, vec![(*b"liga", 0)]), (2..4, vec![])];
+    assert_eq!(font_context.shape_with_ranges(&font_instance, text, *b"Latn", "", &ranges, false)
+                           .unwrap(),
+               shaped_glyphs);
+
+    let overlapping_ranges = [(0..3, vec![(*b"liga", 0)]), (2..4, vec![(*b"ss01", 1)])];
+    match font_context.shape_with_ranges(&font_instance,
+                                         text,
+                                         *b"Latn",
+                                         "",
+                                         &overlapping_ranges,
+                                         false) {
+        Err(FontError::InvalidArgument) => {}
+        result => panic!("overlapping ranges were accepted: {:?}", result),
+    }
+}
+
 #[test]
 fn test_font_context_glyph_outline() {
     drop(env_logger::init());
@@ -164,3 +531,36 @@ fn test_font_context_glyph_outline() {
         assert_eq!(expected_subpath.last_endpoint_index, actual_subpath.last_endpoint_index);
     }
 }
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_gdi_glyph_dimensions_with_invalid_ppem() {
+    let font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    assert!(font_context.gdi_glyph_dimensions(&font_instance, &glyph_key, 16.0).is_ok());
+    for &ppem in &[0.0, -16.0, ::std::f32::NAN, ::std::f32::INFINITY] {
+        match font_context.gdi_glyph_dimensions(&font_instance, &glyph_key, ppem) {
+            Err(FontError::InvalidArgument) => {}
+            _ => panic!("ppem {} was accepted", ppem),
+        }
+    }
+}
+
+#[cfg(all(target_os = "windows", not(feature = "freetype")))]
+#[test]
+fn test_directwrite_glyph_outline_at_invalid_pixels_per_dip() {
+    let mut font_context = load_test_font();
+
+    let font_instance = FontInstance::new(&(), TEST_FONT_SIZE);
+    let glyph_key = GlyphKey::new(TEST_GLYPH_ID, SubpixelOffset(0));
+    for &pixels_per_dip in &[0.0, -2.0, ::std::f32::NAN, ::std::f32::INFINITY] {
+        match font_context.glyph_outline_at_pixels_per_dip(&font_instance,
+                                                           &glyph_key,
+                                                           pixels_per_dip) {
+            Err(FontError::InvalidArgument) => {}
+            _ => panic!("pixels per DIP {} was accepted", pixels_per_dip),
+        }
+    }
+}
@@ -78,12 +78,9 @@ fn convert_font(font_path: &Path, output_path: &Path) -> Result<(), ()> {
     try!(font_file.read_to_end(&mut font_data).map_err(drop));
 
     // TODO(pcwalton): Allow the user to select a face by index.
-    let mut font_context = try!(FontContext::new());
-    try!(font_context.add_font_from_memory(&(), Arc::new(font_data), 0));
-    let font_instance = FontInstance {
-        font_key: (),
-        size: Au::from_f64_px(FONT_SIZE),
-    };
+    let mut font_context = try!(FontContext::new().map_err(drop));
+    try!(font_context.add_font_from_memory(&(), Arc::new(font_data), 0).map_err(drop));
+    let font_instance = FontInstance::new(&(), Au::from_f64_px(FONT_SIZE));
 
     let mut paths: Vec<(u16, Vec<PathEvent>)> = vec![];
     let mut mesh_pack = MeshPack::new();